@@ -36,6 +36,12 @@ grep -v 'pub mod mock_time_uniswap_v3_pool_deployer;' "$input_file" > "$output_f
 # Replace the original file with the modified one
 mv "$output_file" "$input_file"
 
+# Keep the hand-written modules, which the codegen system does not know about
+echo "pub mod multiprocess;" >> "$input_file"
+echo "pub mod pool_id;" >> "$input_file"
+echo "pub mod safe_cast_checked;" >> "$input_file"
+echo "pub use crate::safe_cast_checked as checked;" >> crates/bindings/src/safe_cast_lib.rs
+
 
 
 
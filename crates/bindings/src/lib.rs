@@ -44,9 +44,11 @@ pub mod low_gas_safe_math;
 pub mod migrations;
 pub mod mock_erc20;
 pub mod mock_time_uniswap_v3_pool;
+pub mod multiprocess;
 pub mod no_delegate_call;
 pub mod objective;
 pub mod oracle;
+pub mod pool_id;
 pub mod portfolio_lib;
 pub mod portfolio_like;
 pub mod portfolio_virtual;
@@ -55,6 +57,7 @@ pub mod rmm01_extended_lib;
 pub mod rmm01_lib;
 pub mod rmm01_portfolio;
 pub mod safe_cast;
+pub mod safe_cast_checked;
 pub mod safe_cast_lib;
 pub mod safe_transfer_lib;
 pub mod shared_types;
@@ -0,0 +1,257 @@
+//! Builds the instruction payload of `IPortfolioActions::multiprocess`, mirroring the encoders of the Portfolio `FVMLib`.
+//! This module is written by hand and is not overwritten by the codegen system.
+//!
+//! ## Byte layout
+//!
+//! Amounts are compressed into a `power` byte followed by a 16 byte big-endian `base`, with `amount = base * 10^power` and `power` the number of trailing decimal zeros of the amount.
+//! Offsets are in bytes.
+//!
+//! | Instruction | Layout |
+//! |---|---|
+//! | Allocate, deallocate | `[use_max << 4 \| opcode]` `pool_id: 8` `pointer_asset: 1` `pointer_quote: 1` `liquidity: 17` `asset: 17` `quote: 17` |
+//! | Swap | `[use_max << 4 \| opcode]` `pool_id: 8` `pointer_output: 1` `input: 17` `output: 17` |
+//! | Create pair | `[opcode]` `asset: 20` `quote: 20` |
+//! | Create pool | `[opcode]` `pair_id: 3` `controller: 20` `priority_fee: 2` `fee: 2` `volatility: 2` `duration: 2` `jit: 2` `pointer_price: 1` `max_price: 17` `price: 17` |
+//!
+//! The pointers are the offsets of the compressed amounts that follow the first one.
+//! A payload with a single instruction is the instruction itself. Several instructions are wrapped as `[INSTRUCTION_JUMP]` `count: 1` followed by `length: 1` `instruction` for each instruction.
+
+use ethers::core::types::{Address, Bytes};
+
+/// Opcode of an allocate instruction.
+pub const ALLOCATE: u8 = 0x01;
+/// Opcode of a deallocate instruction.
+pub const DEALLOCATE: u8 = 0x03;
+/// Opcode of a swap that sells the quote token.
+pub const SWAP_QUOTE: u8 = 0x05;
+/// Opcode of a swap that sells the asset token.
+pub const SWAP_ASSET: u8 = 0x06;
+/// Opcode of a create pool instruction.
+pub const CREATE_POOL: u8 = 0x0B;
+/// Opcode of a create pair instruction.
+pub const CREATE_PAIR: u8 = 0x0C;
+/// Opcode that wraps several instructions into one payload.
+pub const INSTRUCTION_JUMP: u8 = 0xAA;
+
+/// Length of a compressed amount, i.e., the `power` byte and the 16 byte `base`.
+const AMOUNT_LENGTH: usize = 17;
+
+/// Builds the payload of `multiprocess` from typed instructions, which are executed in the order they are added.
+/// # Fields
+/// * `instructions` - The encoded instructions in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MultiprocessBuilder {
+    /// The encoded instructions in order.
+    instructions: Vec<Vec<u8>>,
+}
+
+impl MultiprocessBuilder {
+    /// Starts an empty payload.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds liquidity to a pool.
+    /// # Arguments
+    /// * `use_max` - Allocates the agent's whole internal balance instead of `delta_liquidity`.
+    /// * `pool_id` - The pool to allocate to.
+    /// * `delta_liquidity` - The liquidity to add.
+    /// * `max_delta_asset` - The most asset tokens the allocation may take.
+    /// * `max_delta_quote` - The most quote tokens the allocation may take.
+    pub fn allocate(
+        self,
+        use_max: bool,
+        pool_id: u64,
+        delta_liquidity: u128,
+        max_delta_asset: u128,
+        max_delta_quote: u128,
+    ) -> Self {
+        self.allocate_or_deallocate(
+            ALLOCATE,
+            use_max,
+            pool_id,
+            [delta_liquidity, max_delta_asset, max_delta_quote],
+        )
+    }
+
+    /// Removes liquidity from a pool.
+    /// # Arguments
+    /// * `use_max` - Deallocates the agent's whole position instead of `delta_liquidity`.
+    /// * `pool_id` - The pool to deallocate from.
+    /// * `delta_liquidity` - The liquidity to remove.
+    /// * `min_delta_asset` - The fewest asset tokens the deallocation has to return.
+    /// * `min_delta_quote` - The fewest quote tokens the deallocation has to return.
+    pub fn deallocate(
+        self,
+        use_max: bool,
+        pool_id: u64,
+        delta_liquidity: u128,
+        min_delta_asset: u128,
+        min_delta_quote: u128,
+    ) -> Self {
+        self.allocate_or_deallocate(
+            DEALLOCATE,
+            use_max,
+            pool_id,
+            [delta_liquidity, min_delta_asset, min_delta_quote],
+        )
+    }
+
+    /// Swaps in a pool.
+    /// # Arguments
+    /// * `use_max` - Sells the agent's whole internal balance instead of `input`.
+    /// * `pool_id` - The pool to swap in.
+    /// * `input` - The amount of tokens sold.
+    /// * `output` - The amount of tokens bought.
+    /// * `sell_asset` - Sells the asset token for the quote token if `true`, the other way around if `false`.
+    pub fn swap(
+        mut self,
+        use_max: bool,
+        pool_id: u64,
+        input: u128,
+        output: u128,
+        sell_asset: bool,
+    ) -> Self {
+        let opcode = if sell_asset { SWAP_ASSET } else { SWAP_QUOTE };
+        let mut instruction = vec![pack(use_max, opcode)];
+        instruction.extend_from_slice(&pool_id.to_be_bytes());
+        instruction.push((instruction.len() + 1 + AMOUNT_LENGTH) as u8);
+        instruction.extend_from_slice(&compress(input));
+        instruction.extend_from_slice(&compress(output));
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Creates a pair of tokens that pools can be created for.
+    /// # Arguments
+    /// * `asset` - The asset token.
+    /// * `quote` - The quote token.
+    pub fn create_pair(mut self, asset: Address, quote: Address) -> Self {
+        let mut instruction = vec![CREATE_PAIR];
+        instruction.extend_from_slice(asset.as_bytes());
+        instruction.extend_from_slice(quote.as_bytes());
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Creates a pool for a pair.
+    /// # Arguments
+    /// * `pair_id` - The pair of the pool. Only the lower 24 bits are encoded.
+    /// * `controller` - The account that may change the parameters of the pool, the zero address for an immutable pool.
+    /// * `priority_fee` - The fee paid by the controller in basis points.
+    /// * `fee` - The swap fee in basis points.
+    /// * `volatility` - The volatility parameter in basis points.
+    /// * `duration` - The duration parameter in days.
+    /// * `jit` - The just-in-time liquidity policy in seconds.
+    /// * `max_price` - The strike price of the pool.
+    /// * `price` - The initial price of the pool.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_pool(
+        mut self,
+        pair_id: u32,
+        controller: Address,
+        priority_fee: u16,
+        fee: u16,
+        volatility: u16,
+        duration: u16,
+        jit: u16,
+        max_price: u128,
+        price: u128,
+    ) -> Self {
+        let mut instruction = vec![CREATE_POOL];
+        instruction.extend_from_slice(&pair_id.to_be_bytes()[1..]);
+        instruction.extend_from_slice(controller.as_bytes());
+        for parameter in [priority_fee, fee, volatility, duration, jit] {
+            instruction.extend_from_slice(&parameter.to_be_bytes());
+        }
+        instruction.push((instruction.len() + 1 + AMOUNT_LENGTH) as u8);
+        instruction.extend_from_slice(&compress(max_price));
+        instruction.extend_from_slice(&compress(price));
+        self.instructions.push(instruction);
+        self
+    }
+
+    /// Encodes the instructions into the payload that `multiprocess` accepts.
+    /// # Returns
+    /// * `Bytes` - The single instruction, or the jump-encoded instructions if there are several.
+    /// # Panics
+    /// * If there are more than 255 instructions, which the jump encoding cannot count.
+    pub fn build(mut self) -> Bytes {
+        if self.instructions.len() == 1 {
+            return self.instructions.remove(0).into();
+        }
+        let count = u8::try_from(self.instructions.len())
+            .expect("A payload holds at most 255 instructions.");
+        let mut payload = vec![INSTRUCTION_JUMP, count];
+        for instruction in self.instructions {
+            payload.push(instruction.len() as u8);
+            payload.extend(instruction);
+        }
+        payload.into()
+    }
+
+    /// Encodes an allocate or deallocate instruction, whose three amounts are the liquidity and the asset and quote limits.
+    fn allocate_or_deallocate(
+        mut self,
+        opcode: u8,
+        use_max: bool,
+        pool_id: u64,
+        amounts: [u128; 3],
+    ) -> Self {
+        let mut instruction = vec![pack(use_max, opcode)];
+        instruction.extend_from_slice(&pool_id.to_be_bytes());
+        let pointer_asset = instruction.len() + 2 + AMOUNT_LENGTH;
+        instruction.push(pointer_asset as u8);
+        instruction.push((pointer_asset + AMOUNT_LENGTH) as u8);
+        for amount in amounts {
+            instruction.extend_from_slice(&compress(amount));
+        }
+        self.instructions.push(instruction);
+        self
+    }
+}
+
+/// Packs the `use_max` flag into the upper and the opcode into the lower half of the first byte of an instruction.
+fn pack(use_max: bool, opcode: u8) -> u8 {
+    (u8::from(use_max) << 4) | (opcode & 0x0F)
+}
+
+/// Compresses an amount into its `power` byte and 16 byte `base`, where `power` is the number of trailing decimal zeros.
+fn compress(amount: u128) -> [u8; AMOUNT_LENGTH] {
+    let mut power = 0_u8;
+    let mut base = amount;
+    while base != 0 && base % 10 == 0 {
+        base /= 10;
+        power += 1;
+    }
+    let mut compressed = [0; AMOUNT_LENGTH];
+    compressed[0] = power;
+    compressed[1..].copy_from_slice(&base.to_be_bytes());
+    compressed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, MultiprocessBuilder, INSTRUCTION_JUMP, SWAP_ASSET};
+
+    #[test]
+    fn several_instructions_are_jump_encoded() {
+        assert_eq!(compress(5_000)[0], 3);
+        assert_eq!(compress(5_000)[16], 5);
+
+        let swap = MultiprocessBuilder::new()
+            .swap(false, 1, 1_000, 990, true)
+            .build();
+        assert_eq!(swap.len(), 1 + 8 + 1 + 17 + 17);
+        assert_eq!(swap[0], SWAP_ASSET);
+
+        let payload = MultiprocessBuilder::new()
+            .swap(false, 1, 1_000, 990, true)
+            .swap(true, 2, 1_000, 990, false)
+            .build();
+        assert_eq!(payload[..2], [INSTRUCTION_JUMP, 2]);
+        assert_eq!(payload[2] as usize, swap.len());
+        assert_eq!(payload[3..3 + swap.len()], swap[..]);
+        assert_eq!(payload[3 + swap.len() + 1], 0x10 | 0x05);
+    }
+}
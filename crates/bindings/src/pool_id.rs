@@ -0,0 +1,87 @@
+//! Packs and unpacks the `uint64` pool ids of Portfolio.
+//! This module is written by hand and is not overwritten by the codegen system.
+//!
+//! A pool id is `pair_id: 24 bits` `is_mutable: 8 bits` `pool_nonce: 32 bits` from the most to the least significant bits, like `abi.encodePacked(pairId, isMutable, poolNonce)` on chain.
+//! The pair id is the pair nonce of Portfolio at the time the pair was created.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The id of a Portfolio pool, e.g., for `change_parameters` or [`MultiprocessBuilder::swap`](crate::multiprocess::MultiprocessBuilder::swap).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PoolId(u64);
+
+impl PoolId {
+    /// Packs the components of a pool id.
+    /// # Arguments
+    /// * `pair_nonce` - The id of the pair of the pool. Only the lower 24 bits are used.
+    /// * `pool_nonce` - The pool nonce of Portfolio at the time the pool was created.
+    /// * `is_mutable` - Whether the pool has a controller that can change its parameters.
+    pub fn new(pair_nonce: u32, pool_nonce: u32, is_mutable: bool) -> Self {
+        Self(
+            (u64::from(pair_nonce & 0x00FF_FFFF) << 40)
+                | (u64::from(is_mutable) << 32)
+                | u64::from(pool_nonce),
+        )
+    }
+
+    /// Wraps a packed pool id, e.g., one decoded from a `Swap` event.
+    pub fn from_u64(pool_id: u64) -> Self {
+        Self(pool_id)
+    }
+
+    /// Returns the packed pool id that the contract functions take.
+    pub fn into_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Returns the id of the pair of the pool.
+    pub fn pair_nonce(&self) -> u32 {
+        (self.0 >> 40) as u32
+    }
+
+    /// Returns the pool nonce of Portfolio at the time the pool was created.
+    pub fn pool_nonce(&self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns whether the pool has a controller that can change its parameters.
+    pub fn is_mutable(&self) -> bool {
+        (self.0 >> 32) as u8 != 0
+    }
+}
+
+impl From<u64> for PoolId {
+    fn from(pool_id: u64) -> Self {
+        Self::from_u64(pool_id)
+    }
+}
+
+impl From<PoolId> for u64 {
+    fn from(pool_id: PoolId) -> Self {
+        pool_id.into_u64()
+    }
+}
+
+impl Display for PoolId {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{:#018x}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PoolId;
+
+    #[test]
+    fn pool_id_round_trips() {
+        let pool_id = PoolId::new(3, 7, true);
+        assert_eq!(pool_id.into_u64(), 0x0000_0301_0000_0007);
+        assert_eq!(pool_id.to_string(), "0x0000030100000007");
+
+        let decoded = PoolId::from(0x0000_0301_0000_0007_u64);
+        assert_eq!(decoded.pair_nonce(), 3);
+        assert_eq!(decoded.pool_nonce(), 7);
+        assert!(decoded.is_mutable());
+        assert!(!PoolId::new(3, 7, false).is_mutable());
+    }
+}
@@ -0,0 +1,89 @@
+//! Rust-side equivalents of the `SafeCastLib` casts, e.g., to check off-chain that a value fits before sending a transaction that would revert.
+//! This module is written by hand and is not overwritten by the codegen system. It is re-exported as `safe_cast_lib::checked`.
+//!
+//! A cast succeeds exactly when the on-chain `require(x < 1 << bits)` passes.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use ethers::core::types::U256;
+
+/// The error of a cast whose value does not fit into the target width, where the on-chain cast reverts.
+/// # Fields
+/// * `value` - The value that was cast.
+/// * `bits` - The width of the target type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastError {
+    /// The value that was cast.
+    pub value: U256,
+    /// The width of the target type.
+    pub bits: usize,
+}
+
+impl Display for CastError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} does not fit into {} bits", self.value, self.bits)
+    }
+}
+
+impl std::error::Error for CastError {}
+
+/// Checks that a value fits into an unsigned integer of the given width, e.g., 248 for `safeCastTo248`, which has no Rust counterpart.
+/// # Arguments
+/// * `x` - The value to cast.
+/// * `bits` - The width of the target type.
+/// # Returns
+/// * `Result<U256, CastError>` - The value, or an error if the on-chain cast reverts.
+pub fn to_uint(x: U256, bits: usize) -> Result<U256, CastError> {
+    if x.bits() > bits {
+        return Err(CastError { value: x, bits });
+    }
+    Ok(x)
+}
+
+/// Mirrors `safeCastTo128`.
+pub fn to_u128(x: U256) -> Result<u128, CastError> {
+    to_uint(x, 128).map(|x| x.as_u128())
+}
+
+/// Mirrors `safeCastTo64`.
+pub fn to_u64(x: U256) -> Result<u64, CastError> {
+    to_uint(x, 64).map(|x| x.as_u64())
+}
+
+/// Mirrors `safeCastTo32`.
+pub fn to_u32(x: U256) -> Result<u32, CastError> {
+    to_uint(x, 32).map(|x| x.as_u32())
+}
+
+/// Mirrors `safeCastTo16`.
+pub fn to_u16(x: U256) -> Result<u16, CastError> {
+    to_uint(x, 16).map(|x| x.as_u32() as u16)
+}
+
+/// Mirrors `safeCastTo8`.
+pub fn to_u8(x: U256) -> Result<u8, CastError> {
+    to_uint(x, 8).map(|x| x.as_u32() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::core::types::U256;
+
+    use super::{to_u128, to_u8, to_uint, CastError};
+
+    #[test]
+    fn casts_fail_where_the_library_reverts() {
+        assert_eq!(to_u128(U256::from(u128::MAX)), Ok(u128::MAX));
+        assert_eq!(
+            to_u128(U256::from(u128::MAX) + 1),
+            Err(CastError {
+                value: U256::from(u128::MAX) + 1,
+                bits: 128
+            })
+        );
+        assert_eq!(to_u8(U256::from(255)), Ok(255));
+        assert!(to_u8(U256::from(256)).is_err());
+        assert!(to_uint(U256::MAX, 248).is_err());
+        assert_eq!(to_uint(U256::MAX >> 8, 248), Ok(U256::MAX >> 8));
+    }
+}
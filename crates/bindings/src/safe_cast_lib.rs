@@ -339,3 +339,5 @@ pub mod safe_cast_lib {
         }
     }
 }
+
+pub use crate::safe_cast_checked as checked;
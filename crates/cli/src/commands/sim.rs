@@ -177,7 +177,7 @@ fn intitalization_calls(
         &arbiter_token_x,
         call_data.clone(),
         Uint::from(0),
-    ); // TODO: SOME KIND OF ERROR HANDLING IS NECESSARY FOR THESE TYPES OF CALLS
+    )?;
     println!(
         "Minted token_x to arber {:#?}",
         execution_result.is_success()
@@ -188,7 +188,7 @@ fn intitalization_calls(
         &arbiter_token_y,
         call_data,
         Uint::from(0),
-    ); // TODO: SOME KIND OF ERROR HANDLING IS NECESSARY FOR THESE TYPES OF CALLS
+    )?;
     println!(
         "Minted token_y to arber: {:#?}",
         execution_result.is_success()
@@ -202,7 +202,7 @@ fn intitalization_calls(
         &arbiter_token_y,
         call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Minted token_y to liquid_excahnge: {:#?}",
         result.is_success()
@@ -219,7 +219,7 @@ fn intitalization_calls(
         &arbiter_token_x,
         call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Aproved token_x to liquid_excahnge for arber: {:#?}",
         result.is_success()
@@ -232,7 +232,7 @@ fn intitalization_calls(
         &arbiter_token_y,
         call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Aproved token_y to liquid_excahnge for arber: {:#?}",
         result.is_success()
@@ -247,7 +247,7 @@ fn intitalization_calls(
         &arbiter_token_y,
         call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Aproved token_y to portfolio for arber: {:#?}",
         result.is_success()
@@ -259,7 +259,7 @@ fn intitalization_calls(
         &arbiter_token_x,
         call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Aproved token_y to portfolio for arber: {:#?}",
         result.is_success()
@@ -276,7 +276,7 @@ fn intitalization_calls(
         &encoder_target,
         encoder_create_pair_call_data,
         Uint::from(0),
-    );
+    )?;
     // Encoder Target encoding
     let encoded_data = manager.unpack_execution(encoded_create_pair_result)?;
     let decoded_encoded_data: Bytes = encoder_target.decode_output("createPair", encoded_data)?;
@@ -289,7 +289,7 @@ fn intitalization_calls(
         &portfolio,
         portfolio_create_pair_call_data,
         Uint::from(0),
-    );
+    )?;
     println!(
         "Encoded create pair with encoder target encoding: {:#?}",
         encoded_create_pair_result.is_success()
@@ -312,7 +312,7 @@ fn intitalization_calls(
         &portfolio,
         encoded_pair,
         Uint::from(0),
-    );
+    )?;
     let result = manager.unpack_execution(pairs)?;
     let decoded_pairs_response: (H160, u8, H160, u8) =
         i_portfolio.decode_output("pairs", result)?;
@@ -349,7 +349,7 @@ fn intitalization_calls(
         &encoder_target,
         call,
         Uint::from(0),
-    );
+    )?;
 
     let result_object: Bytes = manager.unpack_execution(result)?;
     let decoded_encoded_data: Bytes = encoder_target.decode_output("createPair", result_object)?;
@@ -367,7 +367,7 @@ fn intitalization_calls(
         &portfolio,
         create_pool_call,
         Uint::from(0),
-    );
+    )?;
     assert!(result.is_success());
     println!("pair_id: {:#?}", pair_result.0);
 
@@ -398,7 +398,7 @@ fn intitalization_calls(
         &encoder_target,
         allocate_call,
         Uint::from(0),
-    );
+    )?;
     assert!(allocate_encode_result.is_success());
     let result_object: Bytes = manager.unpack_execution(allocate_encode_result)?;
     let decoded_encoded_data: Bytes =
@@ -476,7 +476,7 @@ mod tests {
             &encoder_target,
             encoder_create_pair_call_data,
             Uint::from(0),
-        );
+        )?;
         assert_eq!(encoded_create_pair_result.is_success(), true);
 
         let encoded_data = manager.unpack_execution(encoded_create_pair_result)?;
@@ -574,7 +574,7 @@ mod tests {
             &portfolio,
             create_pair_call_data,
             Uint::from(0),
-        );
+        )?;
         assert_eq!(encoded_create_pair_result.is_success(), true);
 
         // This wont return anything, so we now have to call the i_portfolio pairs
@@ -606,7 +606,7 @@ mod tests {
             &portfolio,
             encoded_pair,
             Uint::from(0),
-        );
+        )?;
         assert_eq!(request.is_success(), true);
         let unpacked = manager.unpack_execution(request)?;
 
@@ -693,7 +693,7 @@ mod tests {
             &portfolio,
             abi_encoded_create_pool,
             Uint::from(0),
-        );
+        )?;
         let result_object: Bytes = manager.unpack_execution(encoded_create_pool_result.clone())?;
         // `0x | CREATE_POOL (1 byte) | pairId (3 bytes) | controller (20 bytes) | priorityFee (2 bytes) | fee (2 bytes) | vol (2 bytes) | dur (2 bytes) | jit (2 bytes) | pointerPrice (1 byte) | powerMaxPrice (1 byte) | baseMaxPrice (? bytes) | powerPrice (1 byte) | basePrice (? bytes)`\
         println!(
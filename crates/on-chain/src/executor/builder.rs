@@ -0,0 +1,223 @@
+#![warn(missing_docs)]
+//! ## Builder
+//!
+//! Configurable construction of an [`Architect`], for callers that need more control than [`Architect::new`] gives, e.g., a persistent searcher identity.
+
+use std::{fs, path::Path};
+
+use ethers::{core::rand::thread_rng, prelude::*};
+use ethers_flashbots::*;
+use url::Url;
+
+use super::{
+    gas::ProviderGasEstimator, mev_share::RelayKind, retry::RetryPolicy, Architect, ArchitectError,
+    FLASHBOTS_RELAY_URL,
+};
+
+/// Builds an [`Architect`] with chainable setters.
+/// # Fields
+/// * `relay` - URL of the relay that bundles are submitted to, the Flashbots relay if unset. (Option<Url>)
+/// * `bundle_signer` - Searcher identity that signs the requests to the relays, a fresh random key if unset. (Option<LocalWallet>)
+/// * `target_block_offset` - Number of blocks after the current one that the bundle targets. (u64)
+/// * `simulation_block_offset` - Number of blocks relative to the current one whose state the bundle is simulated on. (i64)
+#[derive(Debug, Clone)]
+pub struct ArchitectBuilder {
+    /// URL of the relay that bundles are submitted to, the Flashbots relay if unset.
+    pub relay: Option<Url>,
+    /// Searcher identity that signs the requests to the relays, a fresh random key if unset.
+    pub bundle_signer: Option<LocalWallet>,
+    /// Number of blocks after the current one that the bundle targets.
+    pub target_block_offset: u64,
+    /// Number of blocks relative to the current one whose state the bundle is simulated on.
+    pub simulation_block_offset: i64,
+}
+
+impl Default for ArchitectBuilder {
+    fn default() -> Self {
+        Self {
+            relay: None,
+            bundle_signer: None,
+            target_block_offset: 1,
+            simulation_block_offset: 0,
+        }
+    }
+}
+
+impl ArchitectBuilder {
+    /// Public constructor function that instantiates an `ArchitectBuilder` with the defaults of [`Architect::new`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the relay that bundles are submitted to.
+    /// # Arguments
+    /// * `relay` - URL of the relay.
+    pub fn relay(mut self, relay: Url) -> Self {
+        self.relay = Some(relay);
+        self
+    }
+
+    /// Set the searcher identity, e.g., to keep the reputation built with the relays across restarts.
+    /// # Arguments
+    /// * `bundle_signer` - Wallet that signs the requests to the relays. It does not need funds.
+    pub fn bundle_signer(mut self, bundle_signer: LocalWallet) -> Self {
+        self.bundle_signer = Some(bundle_signer);
+        self
+    }
+
+    /// Set the block the bundle targets relative to the current block.
+    /// # Arguments
+    /// * `target_block_offset` - Number of blocks after the current one, 1 for the next block.
+    pub fn target_block_offset(mut self, target_block_offset: u64) -> Self {
+        self.target_block_offset = target_block_offset;
+        self
+    }
+
+    /// Set the block whose state the bundle is simulated on relative to the current block.
+    /// # Arguments
+    /// * `simulation_block_offset` - Number of blocks after the current one, negative for past blocks.
+    pub fn simulation_block_offset(mut self, simulation_block_offset: i64) -> Self {
+        self.simulation_block_offset = simulation_block_offset;
+        self
+    }
+
+    /// Build the `Architect`, fetching the current block number from the provider.
+    /// # Arguments
+    /// * `provider` - Provider of the chain, e.g., over HTTP or a WebSocket.
+    /// * `wallet` - Wallet that signs the bundle transactions.
+    /// # Returns
+    /// * `Result<Architect<S, P>, ArchitectError>` - The architect, or an error if the relay URL is invalid or the provider fails.
+    pub async fn build<S: Signer, P: JsonRpcClient + Clone + 'static>(
+        self,
+        provider: Provider<P>,
+        wallet: S,
+    ) -> Result<Architect<S, P>, ArchitectError> {
+        let relay = match self.relay {
+            Some(relay) => relay,
+            None => Url::parse(FLASHBOTS_RELAY_URL)?,
+        };
+        // This is your searcher identity.
+        // It does not store funds and is not used for transaction execution.
+        let bundle_signer = self
+            .bundle_signer
+            .unwrap_or_else(|| LocalWallet::new(&mut thread_rng()));
+
+        let gas_estimator = Box::new(ProviderGasEstimator::new(provider.clone()));
+
+        // old approach doesn't work with new ethers.
+        let client = SignerMiddleware::new(
+            FlashbotsMiddleware::new(provider, relay.clone(), bundle_signer.clone()),
+            wallet,
+        );
+
+        let block_number = match client.get_block_number().await {
+            Err(err) => return Err(ArchitectError::ProviderError(err.to_string())),
+            Ok(num) => num,
+        };
+
+        Ok(Architect {
+            client,
+            bundle: BundleRequest::new()
+                .set_block(block_number + self.target_block_offset)
+                .set_simulation_block(offset_block(block_number, self.simulation_block_offset))
+                .set_simulation_timestamp(0),
+            relay,
+            bundle_signer,
+            broadcast_clients: Vec::new(),
+            gas_estimator,
+            relay_kind: RelayKind::default(),
+            retry_policy: RetryPolicy::default(),
+            replacement_uuid: None,
+            revertible_transactions: Vec::new(),
+            coinbase_payment: None,
+            validate_on_send: true,
+        })
+    }
+}
+
+/// Load a searcher identity for [`ArchitectBuilder::bundle_signer`] from a file.
+/// The key only signs the `X-Flashbots-Signature` header of relay requests, so it does not need to hold funds.
+/// # Arguments
+/// * `path` - Path of an encrypted JSON keystore, or of a file holding the hex encoded private key if no password is given.
+/// * `password` - Password of the keystore.
+/// # Returns
+/// * `Result<LocalWallet, ArchitectError>` - The bundle signer, or an error if the file cannot be read or decoded.
+pub fn load_bundle_signer(
+    path: impl AsRef<Path>,
+    password: Option<&str>,
+) -> Result<LocalWallet, ArchitectError> {
+    match password {
+        Some(password) => LocalWallet::decrypt_keystore(path, password)
+            .map_err(|err| ArchitectError::BundleSignerError(err.to_string())),
+        None => {
+            let key = fs::read_to_string(path)
+                .map_err(|err| ArchitectError::BundleSignerError(err.to_string()))?;
+            key.trim()
+                .trim_start_matches("0x")
+                .parse::<LocalWallet>()
+                .map_err(|err| ArchitectError::BundleSignerError(err.to_string()))
+        }
+    }
+}
+
+/// Offset a block number, saturating at the genesis block.
+/// # Arguments
+/// * `block` - The block number.
+/// * `offset` - Number of blocks to move, negative for past blocks.
+fn offset_block(block: U64, offset: i64) -> U64 {
+    if offset < 0 {
+        block.saturating_sub(U64::from(offset.unsigned_abs()))
+    } else {
+        block + offset as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{core::rand::thread_rng, prelude::*};
+
+    use super::{load_bundle_signer, offset_block, ArchitectBuilder};
+
+    #[test]
+    fn test_simulation_block_offset() {
+        assert_eq!(offset_block(U64::from(100), -2), U64::from(98));
+        assert_eq!(offset_block(U64::from(100), 3), U64::from(103));
+        assert_eq!(offset_block(U64::from(1), -5), U64::zero());
+    }
+
+    #[test]
+    fn test_load_bundle_signer_from_hex_file() {
+        let bundle_signer = LocalWallet::new(&mut thread_rng());
+        let path = std::env::temp_dir().join("arbiter_test_bundle_signer.key");
+        std::fs::write(
+            &path,
+            format!(
+                "0x{}\n",
+                ethers::utils::hex::encode(bundle_signer.signer().to_bytes())
+            ),
+        )
+        .unwrap();
+
+        let loaded = load_bundle_signer(&path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.address(), bundle_signer.address());
+    }
+
+    #[tokio::test]
+    async fn test_builder_keeps_bundle_signer() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let bundle_signer = LocalWallet::new(&mut thread_rng());
+
+        let architect = ArchitectBuilder::new()
+            .bundle_signer(bundle_signer.clone())
+            .target_block_offset(2)
+            .simulation_block_offset(-1)
+            .build(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap();
+        assert_eq!(architect.bundle_signer.address(), bundle_signer.address());
+        assert_eq!(architect.bundle.block(), Some(U64::from(102)));
+        assert_eq!(architect.bundle.simulation_block(), Some(U64::from(99)));
+    }
+}
@@ -0,0 +1,46 @@
+#![warn(missing_docs)]
+//! ## Gas
+//!
+//! Pluggable gas price estimation for the [`Architect`](super::Architect).
+
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+use ethers::prelude::*;
+
+use super::{is_transport_error, ArchitectError};
+
+/// Source of the gas price used for bundle transactions that do not set one.
+/// Implement this to plug in an external oracle or a fee-history based model.
+#[async_trait]
+pub trait GasEstimator: Debug + Send + Sync {
+    /// Returns the gas price in wei to bid for a transaction.
+    async fn gas_price(&self) -> Result<U256, ArchitectError>;
+}
+
+/// Default [`GasEstimator`] that asks the provider for its `eth_gasPrice`.
+/// # Fields
+/// * `provider` - Provider to talk to the chain. (Provider<P>)
+#[derive(Debug, Clone)]
+pub struct ProviderGasEstimator<P: JsonRpcClient = Http> {
+    /// Provider to talk to the chain.
+    pub provider: Provider<P>,
+}
+
+impl<P: JsonRpcClient> ProviderGasEstimator<P> {
+    /// Public constructor function that instantiates a `ProviderGasEstimator`.
+    pub fn new(provider: Provider<P>) -> Self {
+        Self { provider }
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient> GasEstimator for ProviderGasEstimator<P> {
+    async fn gas_price(&self) -> Result<U256, ArchitectError> {
+        match self.provider.get_gas_price().await {
+            Err(err) if is_transport_error(&err) => Err(ArchitectError::GasPriceError),
+            Err(err) => Err(ArchitectError::Rejected(err.to_string())),
+            Ok(gas_price) => Ok(gas_price),
+        }
+    }
+}
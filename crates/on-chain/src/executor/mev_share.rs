@@ -0,0 +1,199 @@
+#![warn(missing_docs)]
+//! ## MEV-Share
+//!
+//! Request types for submitting bundles with refunds to the originating users through MEV-Share's `mev_sendBundle`.
+
+use ethers::prelude::*;
+use ethers_flashbots::BundleTransaction;
+use serde::Serialize;
+
+/// Relay that the [`Architect`](super::Architect) submits bundles to.
+/// # Variants
+/// * `Flashbots` - Plain Flashbots relay, which does not support refunds.
+/// * `MevShare` - MEV-Share, which supports refunds to the originating user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelayKind {
+    /// Plain Flashbots relay, which does not support refunds.
+    #[default]
+    Flashbots,
+    /// MEV-Share, which supports refunds to the originating user.
+    MevShare,
+}
+
+/// Refund that a bundle pays to the originating user.
+/// # Fields
+/// * `recipient` - Address that receives the refund. (Address)
+/// * `percent` - Percent of the bundle profit that is refunded, between 0 and 100. (u64)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RefundConfig {
+    /// Address that receives the refund.
+    pub recipient: Address,
+    /// Percent of the bundle profit that is refunded, between 0 and 100.
+    pub percent: u64,
+}
+
+/// Parameters of an MEV-Share `mev_sendBundle` request.
+/// # Fields
+/// * `version` - Version of the request format.
+/// * `inclusion` - Blocks the bundle may be included in.
+/// * `body` - Transactions of the bundle in order.
+/// * `validity` - Refunds paid by the bundle.
+#[derive(Debug, Clone, Serialize)]
+pub struct MevShareBundle {
+    /// Version of the request format.
+    pub version: String,
+    /// Blocks the bundle may be included in.
+    pub inclusion: Inclusion,
+    /// Transactions of the bundle in order.
+    pub body: Vec<BodyItem>,
+    /// Refunds paid by the bundle.
+    pub validity: Validity,
+}
+
+/// Blocks an MEV-Share bundle may be included in.
+/// # Fields
+/// * `block` - First block the bundle may be included in.
+/// * `max_block` - Last block the bundle may be included in.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Inclusion {
+    /// First block the bundle may be included in.
+    pub block: U64,
+    /// Last block the bundle may be included in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_block: Option<U64>,
+}
+
+/// A signed transaction in the body of an MEV-Share bundle.
+/// # Fields
+/// * `tx` - Raw signed transaction.
+/// * `can_revert` - Whether the bundle is still valid if the transaction reverts.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BodyItem {
+    /// Raw signed transaction.
+    pub tx: Bytes,
+    /// Whether the bundle is still valid if the transaction reverts.
+    pub can_revert: bool,
+}
+
+/// Refunds paid by an MEV-Share bundle.
+/// # Fields
+/// * `refund` - Share of the profit of each body item that is refunded.
+/// * `refund_config` - Addresses that the refunds are paid to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Validity {
+    /// Share of the profit of each body item that is refunded.
+    pub refund: Vec<Refund>,
+    /// Addresses that the refunds are paid to.
+    pub refund_config: Vec<RefundRecipient>,
+}
+
+/// Share of the profit of a body item that is refunded.
+/// # Fields
+/// * `body_idx` - Index of the body item whose profit is refunded.
+/// * `percent` - Percent of the profit that is refunded.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Refund {
+    /// Index of the body item whose profit is refunded.
+    pub body_idx: u64,
+    /// Percent of the profit that is refunded.
+    pub percent: u64,
+}
+
+/// Address that refunds are paid to.
+/// # Fields
+/// * `address` - Address that receives the refund.
+/// * `percent` - Percent of the refund that the address receives.
+#[derive(Debug, Clone, Serialize)]
+pub struct RefundRecipient {
+    /// Address that receives the refund.
+    pub address: Address,
+    /// Percent of the refund that the address receives.
+    pub percent: u64,
+}
+
+impl MevShareBundle {
+    /// Builds an MEV-Share bundle that refunds a share of the profit of its first transaction, i.e., the user transaction being backrun.
+    /// # Arguments
+    /// * `block` - Block the bundle targets.
+    /// * `transactions` - Transactions of the bundle in order.
+    /// * `revertible` - Hashes of the transactions that may revert without the bundle being rejected.
+    /// * `refund` - Refund paid to the originating user.
+    pub fn new(
+        block: U64,
+        transactions: &[BundleTransaction],
+        revertible: &[H256],
+        refund: RefundConfig,
+    ) -> Self {
+        let body = transactions
+            .iter()
+            .map(|transaction| {
+                let tx = match transaction {
+                    BundleTransaction::Signed(transaction) => transaction.rlp(),
+                    BundleTransaction::Raw(raw) => raw.clone(),
+                };
+                let can_revert = revertible.contains(&H256::from(ethers::utils::keccak256(&tx)));
+                BodyItem { tx, can_revert }
+            })
+            .collect();
+        Self {
+            version: "v0.1".to_string(),
+            inclusion: Inclusion {
+                block,
+                max_block: None,
+            },
+            body,
+            validity: Validity {
+                refund: vec![Refund {
+                    body_idx: 0,
+                    percent: refund.percent,
+                }],
+                refund_config: vec![RefundRecipient {
+                    address: refund.recipient,
+                    percent: 100,
+                }],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::*;
+    use ethers_flashbots::BundleTransaction;
+
+    use super::{MevShareBundle, RefundConfig};
+
+    #[test]
+    fn test_refund_config_serialized() {
+        let recipient = Address::from_low_u64_be(7);
+        let probe = Bytes::from(vec![0x02, 0x02]);
+        let bundle = MevShareBundle::new(
+            U64::from(17_000_000),
+            &[
+                BundleTransaction::Raw(Bytes::from(vec![0x02, 0x01])),
+                BundleTransaction::Raw(probe.clone()),
+            ],
+            &[H256::from(ethers::utils::keccak256(&probe))],
+            RefundConfig {
+                recipient,
+                percent: 90,
+            },
+        );
+
+        let payload = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(payload["inclusion"]["block"], "0x1036640");
+        assert_eq!(payload["body"][0]["tx"], "0x0201");
+        assert_eq!(payload["body"][0]["canRevert"], false);
+        assert_eq!(payload["body"][1]["canRevert"], true);
+        assert_eq!(payload["validity"]["refund"][0]["bodyIdx"], 0);
+        assert_eq!(payload["validity"]["refund"][0]["percent"], 90);
+        assert_eq!(
+            payload["validity"]["refundConfig"][0]["address"],
+            format!("{:?}", recipient)
+        );
+    }
+}
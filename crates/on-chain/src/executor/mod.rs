@@ -3,47 +3,233 @@
 //!
 //! Executor is the bundling, simulation and execution module of Arbiter.
 
-use ethers::{
-    core::{rand::thread_rng, types::transaction::eip2718::TypedTransaction},
-    prelude::*,
-    signers::Signer,
-};
+use std::collections::HashSet;
+
+use ethers::{core::types::transaction::eip2718::TypedTransaction, prelude::*, signers::Signer};
 use ethers_flashbots::*;
+use futures::{
+    future::{join_all, try_join_all},
+    StreamExt,
+};
+use serde::Serialize;
+use thiserror::Error;
 use url::Url;
+use uuid::Uuid;
+
+use self::{
+    builder::ArchitectBuilder,
+    gas::GasEstimator,
+    mev_share::{MevShareBundle, RefundConfig, RelayKind},
+    private::PrivateTransaction,
+    replacement::ReplaceableBundle,
+    retry::RetryPolicy,
+    summary::BundleSummary,
+};
+
+pub mod builder;
+pub mod gas;
+pub mod mev_share;
+pub mod private;
+pub mod replacement;
+pub mod retry;
+pub mod summary;
+
+/// URL of the Flashbots relay that [`Architect::new`] submits to.
+pub const FLASHBOTS_RELAY_URL: &str = "https://relay.flashbots.net";
 
 /// Houses the bundle and client information for execution.
 /// # Fields
-/// * `client` - Client that signs transactions. (SignerMiddleware<FlashbotsMiddleware<Provider<Http>, LocalWallet>, S>)
+/// * `client` - Client that signs transactions. (SignerMiddleware<FlashbotsMiddleware<Provider<P>, LocalWallet>, S>)
 /// * `bundle` - Bundle to be executed. (BundleRequest)
+/// * `relay` - URL of the relay that bundles are submitted to. (Url)
+/// * `bundle_signer` - Searcher identity that signs the requests to the relays. (LocalWallet)
+/// * `broadcast_clients` - Clients of the additional relays that [`Architect::send_all`] broadcasts to. (Vec<FlashbotsMiddleware<Provider<P>, LocalWallet>>)
+/// * `gas_estimator` - Source of the gas price for transactions that do not set one. (Box<dyn GasEstimator>)
+/// * `relay_kind` - Relay that bundles are submitted to. (RelayKind)
+/// * `retry_policy` - How provider and relay calls are retried when they fail transiently. (RetryPolicy)
+/// * `replacement_uuid` - UUID that lets a later submission replace or cancel the bundle. (Option<Uuid>)
+/// * `revertible_transactions` - Hashes of the bundle transactions that may revert without the bundle being rejected. (Vec<H256>)
+/// * `coinbase_payment` - Amount paid to the block's coinbase by a final transaction appended at send time. (Option<U256>)
+/// * `validate_on_send` - Whether every send checks the bundle with [`Architect::validate`] before submitting it. (bool)
 #[derive(Debug)]
-pub struct Executor<S>
+pub struct Architect<S, P = Http>
 where
     S: Signer,
+    P: JsonRpcClient,
 {
     /// Client that signs transactions.
-    pub client: SignerMiddleware<FlashbotsMiddleware<Provider<Http>, LocalWallet>, S>,
+    pub client: SignerMiddleware<FlashbotsMiddleware<Provider<P>, LocalWallet>, S>,
     /// Bundle to be executed.
     pub bundle: BundleRequest,
+    /// URL of the relay that bundles are submitted to.
+    pub relay: Url,
+    /// Searcher identity that signs the requests to the relays.
+    pub bundle_signer: LocalWallet,
+    /// Clients of the additional relays that [`Architect::send_all`] broadcasts to.
+    pub broadcast_clients: Vec<FlashbotsMiddleware<Provider<P>, LocalWallet>>,
+    /// Source of the gas price for transactions that do not set one.
+    pub gas_estimator: Box<dyn GasEstimator>,
+    /// Relay that bundles are submitted to.
+    pub relay_kind: RelayKind,
+    /// How provider and relay calls are retried when they fail transiently.
+    pub retry_policy: RetryPolicy,
+    /// UUID that lets a later submission replace or cancel the bundle.
+    pub replacement_uuid: Option<Uuid>,
+    /// Hashes of the bundle transactions that may revert without the bundle being rejected.
+    pub revertible_transactions: Vec<H256>,
+    /// Amount paid to the block's coinbase by a final transaction appended at send time.
+    pub coinbase_payment: Option<U256>,
+    /// Whether every send checks the bundle with [`Architect::validate`] before submitting it.
+    pub validate_on_send: bool,
 }
 
 /// Errors for bundle construction or execution.
 /// # Variants
 /// * `RelayParseError` - Error with parsing the Flashbots relay URL.
-/// * `SigningError` - Error with signing a transaction.
+/// * `SigningError` - Error with signing a transaction, along with the reason the signer gave.
 /// * `BlockNumberError` - Error with fetching block number from middleware.
-#[derive(Debug)]
-pub enum Executor {
+/// * `ProviderError` - The provider could not be reached, e.g., a dropped connection.
+/// * `GasPriceError` - Error with estimating the gas price.
+/// * `FlashbotsError` - Error returned by the Flashbots middleware other than a failure to reach the provider or the relay, e.g., a rejected bundle.
+/// * `BundleSignerError` - Error with loading the bundle signer from a file.
+/// * `RelayError` - The relay could not be reached, e.g., a dropped connection.
+/// * `Rejected` - The provider or relay answered with a JSON-RPC error or a response that could not be used, along with the reason.
+/// * `MissingReplacementUuid` - A replaceable bundle was sent without a replacement UUID.
+/// * `RefundsUnsupported` - Refunds were requested from a relay that does not support them.
+/// * `InvalidRefundPercent` - The refund percent is above 100.
+/// * `InvalidTransaction` - A raw bundle transaction could not be decoded, along with the reason.
+/// * `GasLimitExceeded` - The gas declared by the bundle transactions does not fit the block gas limit.
+/// * `InjectedFailure` - A failure injected on purpose. Only available with the `test-utils` feature.
+#[derive(Debug, Error)]
+pub enum ArchitectError {
     /// Error with parsing the Flashbots relay URL.
     #[error(transparent)]
     RelayParseError(#[from] url::ParseError),
 
     /// Error with signing a transaction.
-    #[error("an error occured when signing a bundle transaction")]
-    SigningError,
+    #[error("an error occured when signing a bundle transaction: {0}")]
+    SigningError(String),
 
     /// Error with fetching block number from middleware.
     #[error("an error occured when fetching the current block number")]
     BlockNumberError,
+
+    /// The provider could not be reached, e.g., a dropped connection.
+    #[error("the provider could not be reached: {0}")]
+    ProviderError(String),
+
+    /// Error with estimating the gas price.
+    #[error("an error occured when estimating the gas price")]
+    GasPriceError,
+
+    /// Error returned by the Flashbots middleware other than a failure to reach the provider or the relay.
+    #[error(transparent)]
+    FlashbotsError(Box<dyn std::error::Error + Send + Sync>),
+
+    /// Error with loading the bundle signer from a file.
+    #[error("an error occured when loading the bundle signer: {0}")]
+    BundleSignerError(String),
+
+    /// The relay could not be reached, e.g., a dropped connection.
+    #[error("the relay could not be reached: {0}")]
+    RelayError(String),
+
+    /// The provider or relay answered with a JSON-RPC error or a response that could not be used.
+    #[error("the request was rejected: {0}")]
+    Rejected(String),
+
+    /// A replaceable bundle was sent without a replacement UUID.
+    #[error("the bundle has no replacement UUID, set one with `set_replacement_uuid`")]
+    MissingReplacementUuid,
+
+    /// Refunds were requested from a relay that does not support them.
+    #[error("the {0:?} relay does not support refunds, use MEV-Share instead")]
+    RefundsUnsupported(RelayKind),
+
+    /// The refund percent is above 100.
+    #[error("refund percent {0} is above 100")]
+    InvalidRefundPercent(u64),
+
+    /// A raw bundle transaction could not be decoded.
+    #[error("a bundle transaction could not be decoded: {0}")]
+    InvalidTransaction(String),
+
+    /// The gas declared by the bundle transactions does not fit the block gas limit.
+    #[error("the bundle declares {gas} gas, which exceeds the block gas limit of {gas_limit}")]
+    GasLimitExceeded {
+        /// Gas declared by the bundle transactions together.
+        gas: U256,
+        /// Gas limit of the latest block.
+        gas_limit: U256,
+    },
+
+    /// A failure injected on purpose.
+    #[cfg(feature = "test-utils")]
+    #[error("an injected failure occured")]
+    InjectedFailure,
+}
+
+impl<P: JsonRpcClient + 'static> From<FlashbotsMiddlewareError<Provider<P>, LocalWallet>>
+    for ArchitectError
+{
+    fn from(err: FlashbotsMiddlewareError<Provider<P>, LocalWallet>) -> Self {
+        match err {
+            FlashbotsMiddlewareError::MiddlewareError(err) => provider_error(err),
+            FlashbotsMiddlewareError::RelayError(err) => relay_error(err),
+            err => ArchitectError::FlashbotsError(Box::new(err)),
+        }
+    }
+}
+
+/// Whether a provider call failed because the provider could not be reached rather than because it answered with an error.
+/// # Arguments
+/// * `err` - Error of the provider call.
+pub(crate) fn is_transport_error(err: &ProviderError) -> bool {
+    match err {
+        ProviderError::JsonRpcClientError(err) => {
+            err.as_error_response().is_none() && err.as_serde_error().is_none()
+        }
+        ProviderError::HTTPError(_) => true,
+        _ => false,
+    }
+}
+
+/// Map a failed provider call to [`ArchitectError::ProviderError`] if the provider could not be reached and to [`ArchitectError::Rejected`] otherwise.
+/// # Arguments
+/// * `err` - Error of the provider call.
+fn provider_error(err: ProviderError) -> ArchitectError {
+    if is_transport_error(&err) {
+        ArchitectError::ProviderError(err.to_string())
+    } else {
+        ArchitectError::Rejected(err.to_string())
+    }
+}
+
+/// Map a failed relay request to [`ArchitectError::RelayError`] if the relay could not be reached and to [`ArchitectError::Rejected`] otherwise.
+/// # Arguments
+/// * `err` - Error of the relay request.
+fn relay_error<S: Signer>(err: RelayError<S>) -> ArchitectError {
+    match err {
+        RelayError::RequestError(err) => ArchitectError::RelayError(err.to_string()),
+        err => ArchitectError::Rejected(err.to_string()),
+    }
+}
+
+impl ArchitectError {
+    /// Whether the error may go away when the call is retried, e.g., a dropped connection to the provider or the relay.
+    /// JSON-RPC error responses, such as a relay rejecting a bundle, are not transient and are returned right away.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ArchitectError::BlockNumberError
+            | ArchitectError::ProviderError(_)
+            | ArchitectError::GasPriceError
+            | ArchitectError::FlashbotsError(_)
+            | ArchitectError::RelayError(_) => true,
+            #[cfg(feature = "test-utils")]
+            ArchitectError::InjectedFailure => true,
+            _ => false,
+        }
+    }
 }
 
 /// Type that represents an execution result from either a send or simulation.
@@ -53,43 +239,181 @@ pub enum Executor {
 /// * `Error` - Error from a send or simulation.
 #[deprecated(since = "0.0.1", note = "will be useful for actors in the future")]
 #[allow(warnings)]
-pub type ExecutionResult<T> = Result<T, FlashbotsMiddlewareError<Provider<Http>, LocalWallet>>;
+pub type ExecutionResult<T, P = Http> =
+    Result<T, FlashbotsMiddlewareError<Provider<P>, LocalWallet>>;
 #[deprecated(since = "0.0.1", note = "will be useful for actors in the future")]
 #[allow(warnings)]
-impl<S: Signer> Architect<S> {
-    /// Public constructor function that instantiates an `Architect`.
-    pub async fn new(provider: Provider<Http>, wallet: S) -> Result<Self, ArchitectError> {
-        // This is your searcher identity.
-        // It does not store funds and is not used for transaction execution.
-        let bundle_signer = LocalWallet::new(&mut thread_rng());
-        let bundle = BundleRequest::new();
-
-        let relay = match Url::parse("https://relay.flashbots.net") {
-            Err(err) => return Err(ArchitectError::RelayParseError(err)),
-            Ok(url) => url,
-        };
+impl<S: Signer, P: JsonRpcClient + Clone + 'static> Architect<S, P> {
+    /// Public constructor function that instantiates an `Architect` submitting to the Flashbots relay.
+    pub async fn new(provider: Provider<P>, wallet: S) -> Result<Self, ArchitectError> {
+        ArchitectBuilder::new().build(provider, wallet).await
+    }
 
-        // old approach doesn't work with new ethers.
-        let client = SignerMiddleware::new(
-            FlashbotsMiddleware::new(provider, relay, bundle_signer),
-            wallet,
-        );
+    /// Public constructor function that instantiates an `Architect` submitting to a specific relay, e.g., another builder or a local mev-boost relay for testing.
+    /// Use [`ArchitectBuilder`] for further control, e.g., over the searcher identity.
+    /// # Arguments
+    /// * `provider` - Provider of the chain.
+    /// * `wallet` - Wallet that signs the bundle transactions.
+    /// * `relay` - URL of the relay.
+    pub async fn new_with_relay(
+        provider: Provider<P>,
+        wallet: S,
+        relay: Url,
+    ) -> Result<Self, ArchitectError> {
+        ArchitectBuilder::new()
+            .relay(relay)
+            .build(provider, wallet)
+            .await
+    }
+
+    /// Public constructor function that instantiates an `Architect` with a persistent searcher identity, keeping the reputation the relays build around it across restarts.
+    /// The bundle signer only signs the `X-Flashbots-Signature` header of relay requests. It does not store funds and is not used for transaction execution.
+    /// See [`load_bundle_signer`](builder::load_bundle_signer) to read it from a file.
+    /// # Arguments
+    /// * `provider` - Provider of the chain.
+    /// * `wallet` - Wallet that signs the bundle transactions.
+    /// * `bundle_signer` - Searcher identity that signs the requests to the relays.
+    pub async fn from_signer_key(
+        provider: Provider<P>,
+        wallet: S,
+        bundle_signer: LocalWallet,
+    ) -> Result<Self, ArchitectError> {
+        ArchitectBuilder::new()
+            .bundle_signer(bundle_signer)
+            .build(provider, wallet)
+            .await
+    }
 
-        let block_number = match client.get_block_number().await {
-            Err(_) => return Err(ArchitectError::BlockNumberError),
-            Ok(num) => num,
+    /// Replace the [`GasEstimator`] used for transactions that do not set a gas price.
+    /// # Arguments
+    /// * `gas_estimator` - The gas estimator to use.
+    pub fn with_gas_estimator(mut self, gas_estimator: Box<dyn GasEstimator>) -> Self {
+        self.gas_estimator = gas_estimator;
+        self
+    }
+
+    /// Add relays that [`Architect::send_all`] broadcasts the bundle to along with the primary relay, e.g., several builders to maximize the odds of inclusion.
+    /// # Arguments
+    /// * `relays` - URLs of the additional relays.
+    pub fn with_relays(mut self, relays: Vec<Url>) -> Self {
+        let provider = self.client.inner().inner().clone();
+        for relay in relays {
+            self.broadcast_clients.push(FlashbotsMiddleware::new(
+                provider.clone(),
+                relay,
+                self.bundle_signer.clone(),
+            ));
+        }
+        self
+    }
+
+    /// Set the relay that bundles are submitted to.
+    /// # Arguments
+    /// * `relay_kind` - The relay to submit to.
+    pub fn with_relay_kind(mut self, relay_kind: RelayKind) -> Self {
+        self.relay_kind = relay_kind;
+        self
+    }
+
+    /// Set how provider and relay calls are retried when they fail transiently.
+    /// # Arguments
+    /// * `retry_policy` - The retry policy to use.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Set whether every send validates the bundle before submitting it, e.g., to skip the extra provider request when the bundle is known to fit.
+    /// # Arguments
+    /// * `validate_on_send` - Whether to validate, which is the default.
+    pub fn with_validation(mut self, validate_on_send: bool) -> Self {
+        self.validate_on_send = validate_on_send;
+        self
+    }
+
+    /// Set the earliest timestamp of a block the bundle may be included in.
+    /// # Arguments
+    /// * `ts` - UNIX timestamp in seconds.
+    pub fn set_min_timestamp(mut self, ts: u64) -> Self {
+        self.bundle = self.bundle.set_min_timestamp(ts);
+        self
+    }
+
+    /// Set the latest timestamp of a block the bundle may be included in, e.g., so that a time-sensitive liquidation is dropped instead of landing late.
+    /// # Arguments
+    /// * `ts` - UNIX timestamp in seconds.
+    pub fn set_max_timestamp(mut self, ts: u64) -> Self {
+        self.bundle = self.bundle.set_max_timestamp(ts);
+        self
+    }
+
+    /// Set the replacement UUID of the bundle so that it can later be replaced or cancelled with [`Architect::cancel`].
+    /// The relay only honors a replacement that targets the same block as the bundle it replaces, so keep the target block unchanged between the submissions.
+    /// # Arguments
+    /// * `uuid` - UUID identifying the bundle at the relay.
+    pub fn set_replacement_uuid(mut self, uuid: Uuid) -> Self {
+        self.replacement_uuid = Some(uuid);
+        self
+    }
+
+    /// Build the MEV-Share `mev_sendBundle` request for the stored bundle, refunding a share of the profit to the originating user.
+    /// Transactions in [`Architect::revertible_transactions`] may revert without the bundle being rejected.
+    /// # Arguments
+    /// * `refund` - Refund recipient and percent.
+    /// # Returns
+    /// * `Result<MevShareBundle, ArchitectError>` - The request, or an error if the relay does not support refunds.
+    pub fn mev_share_bundle(&self, refund: RefundConfig) -> Result<MevShareBundle, ArchitectError> {
+        if self.relay_kind != RelayKind::MevShare {
+            return Err(ArchitectError::RefundsUnsupported(self.relay_kind));
+        }
+        if refund.percent > 100 {
+            return Err(ArchitectError::InvalidRefundPercent(refund.percent));
+        }
+        let block = match self.bundle.block() {
+            None => return Err(ArchitectError::BlockNumberError),
+            Some(block) => block,
         };
+        Ok(MevShareBundle::new(
+            block,
+            self.bundle.transactions(),
+            &self.revertible_transactions,
+            refund,
+        ))
+    }
 
-        Ok(Self {
-            client,
-            bundle: bundle
-                .set_block(block_number + 1)
-                .set_simulation_block(block_number)
-                .set_simulation_timestamp(0),
-        })
+    /// Send the stored bundle to MEV-Share through `mev_sendBundle`, refunding a share of the profit to the originating user.
+    /// # Arguments
+    /// * `refund` - Refund recipient and percent.
+    /// # Returns
+    /// * `Result<(), ArchitectError>` - Whether the relay accepted the bundle, or an error if the relay does not support refunds.
+    pub async fn send_mev_share(&self, refund: RefundConfig) -> Result<(), ArchitectError> {
+        let bundle = self.mev_share_bundle(refund)?;
+        self.relay_request("mev_sendBundle", [bundle]).await
+    }
+
+    /// Set the gas price of a transaction from the [`GasEstimator`] if it does not have one yet.
+    /// An EIP-1559 transaction only gets its `max_fee_per_gas` filled, raised to its priority fee if needed, so the priority fee it bids is kept.
+    /// # Arguments
+    /// * `tx` - Transaction to price.
+    pub async fn fill_gas_price(&self, tx: &mut TypedTransaction) -> Result<(), ArchitectError> {
+        if tx.gas_price().is_some() {
+            return Ok(());
+        }
+        let gas_price = self.gas_estimator.gas_price().await?;
+        match tx {
+            TypedTransaction::Eip1559(inner) => {
+                let priority_fee = inner.max_priority_fee_per_gas.unwrap_or_default();
+                inner.max_fee_per_gas = Some(gas_price.max(priority_fee));
+            }
+            _ => tx.set_gas_price(gas_price),
+        }
+        Ok(())
     }
 
     /// Add and sign a transaction to the bundle to be executed.
+    /// Transactions without a gas price are priced by the [`GasEstimator`].
+    /// If the signer rejects a transaction, e.g., because of a chain id mismatch, the error is returned instead of panicking.
+    /// Transactions are signed concurrently and added to the bundle in the order they were given.
     /// # Arguments
     /// * `transaction` - Transaction to be added to the bundle.
     #[deprecated(since = "0.0.1", note = "will be useful for actors in the future")]
@@ -98,16 +422,233 @@ impl<S: Signer> Architect<S> {
         mut self,
         transactions: &Vec<TypedTransaction>,
     ) -> Result<Self, ArchitectError> {
-        for tx in transactions {
-            let signature = match self.client.signer().sign_transaction(tx).await {
-                Err(_) => return Err(ArchitectError::SigningError),
+        let signed_transactions = self.sign_transactions(transactions).await?;
+
+        for signed_transaction in signed_transactions {
+            self.bundle = self.bundle.push_transaction(signed_transaction);
+        }
+
+        Ok(self)
+    }
+
+    /// Push transactions that were signed elsewhere, e.g., a user's signed swap to backrun, onto the bundle as they are.
+    /// They are not re-signed, so the wallet of the `Architect` is not involved.
+    /// # Arguments
+    /// * `raw` - RLP encoded signed transactions to be added to the bundle in order.
+    pub fn push_raw_transactions(mut self, raw: &[Bytes]) -> Self {
+        for transaction in raw {
+            self.bundle = self
+                .bundle
+                .push_transaction(BundleTransaction::Raw(transaction.clone()));
+        }
+        self
+    }
+
+    /// Pay the block builder directly with a final transaction that sends `amount` to the coinbase, signed by the execution wallet.
+    /// The coinbase changes from block to block, so the payment is only resolved and signed when the bundle is sent, by every send.
+    /// # Arguments
+    /// * `amount` - Amount in wei to pay to the coinbase.
+    pub fn add_coinbase_payment(mut self, amount: U256) -> Self {
+        self.coinbase_payment = Some(amount);
+        self
+    }
+
+    /// Add and sign transactions that may revert without the bundle being rejected, e.g., a probe that can legitimately fail.
+    /// Their hashes are set as the `revertingTxHashes` of the bundle and kept in [`Architect::revertible_transactions`].
+    /// # Arguments
+    /// * `transactions` - Revert-tolerant transactions to be added to the bundle in order.
+    pub async fn add_transactions_allow_revert(
+        mut self,
+        transactions: &Vec<TypedTransaction>,
+    ) -> Result<Self, ArchitectError> {
+        let signed_transactions = self.sign_transactions(transactions).await?;
+
+        for signed_transaction in signed_transactions {
+            self.revertible_transactions
+                .push(H256::from(ethers::utils::keccak256(&signed_transaction)));
+            self.bundle = self.bundle.push_revertible_transaction(signed_transaction);
+        }
+
+        Ok(self)
+    }
+
+    /// Sign transactions for the bundle, pricing those without a gas price with the [`GasEstimator`].
+    /// # Arguments
+    /// * `transactions` - Transactions to sign.
+    /// # Returns
+    /// * `Result<Vec<Bytes>, ArchitectError>` - The signed transactions in the order they were given.
+    async fn sign_transactions(
+        &self,
+        transactions: &[TypedTransaction],
+    ) -> Result<Vec<Bytes>, ArchitectError> {
+        // Sign all transactions concurrently, which matters for signers that sign remotely.
+        // `try_join_all` yields the results in the order of the transactions, which is the order of the bundle.
+        try_join_all(transactions.iter().map(|tx| async move {
+            let mut tx = tx.clone();
+            self.fill_gas_price(&mut tx).await?;
+            let signature = match self.client.signer().sign_transaction(&tx).await {
+                Err(err) => return Err(ArchitectError::SigningError(err.to_string())),
                 Ok(sig) => sig,
             };
+            Ok(tx.rlp_signed(&signature))
+        }))
+        .await
+    }
+
+    /// Add and sign EIP-1559 transactions to the bundle to be executed, e.g., to have each transaction bid its own priority fee.
+    /// The bundle only lands if every `max_fee_per_gas` covers the base fee of the target block, which can be up to 12.5% higher than the base fee of the current block.
+    /// Whatever the base fee leaves over, capped at `max_priority_fee_per_gas`, is paid to the builder.
+    /// Transactions without a `max_fee_per_gas` are priced by the [`GasEstimator`].
+    /// # Arguments
+    /// * `transactions` - EIP-1559 transactions to be added to the bundle in order.
+    pub async fn add_1559_transactions(
+        self,
+        transactions: &Vec<Eip1559TransactionRequest>,
+    ) -> Result<Self, ArchitectError> {
+        let transactions = transactions
+            .iter()
+            .cloned()
+            .map(TypedTransaction::Eip1559)
+            .collect();
+        self.add_transactions(&transactions).await
+    }
+
+    /// Construct a backrun bundle for a target transaction without touching the stored bundle.
+    /// The target transaction is included as-is and the backrun is signed and placed directly after it.
+    /// # Arguments
+    /// * `target_tx` - Raw signed target transaction to backrun.
+    /// * `backrun` - Transaction that backruns the target.
+    /// # Returns
+    /// * `Result<BundleRequest, ArchitectError>` - Two-element bundle targeting the same blocks as the stored bundle.
+    pub async fn backrun_bundle(
+        &self,
+        target_tx: Bytes,
+        mut backrun: TypedTransaction,
+    ) -> Result<BundleRequest, ArchitectError> {
+        self.fill_gas_price(&mut backrun).await?;
+        let signature = match self.client.signer().sign_transaction(&backrun).await {
+            Err(err) => return Err(ArchitectError::SigningError(err.to_string())),
+            Ok(sig) => sig,
+        };
 
-            self.bundle = self.bundle.push_transaction(tx.rlp_signed(&signature));
+        Ok(self.bundle_with_transactions(&[
+            BundleTransaction::from(target_tx),
+            BundleTransaction::from(backrun.rlp_signed(&signature)),
+        ]))
+    }
+
+    /// Construct a bundle of transactions that targets the same blocks and timestamps as the stored bundle.
+    /// Transactions in [`Architect::revertible_transactions`] stay revert-tolerant.
+    /// # Arguments
+    /// * `transactions` - Transactions of the new bundle in order.
+    fn bundle_with_transactions(&self, transactions: &[BundleTransaction]) -> BundleRequest {
+        let mut bundle = BundleRequest::new();
+        for tx in transactions {
+            if self
+                .revertible_transactions
+                .contains(&bundle_transaction_hash(tx))
+            {
+                bundle = bundle.push_revertible_transaction(tx.clone());
+            } else {
+                bundle = bundle.push_transaction(tx.clone());
+            }
+        }
+        if let Some(block) = self.bundle.block() {
+            bundle = bundle.set_block(block);
+        }
+        if let Some(simulation_block) = self.bundle.simulation_block() {
+            bundle = bundle.set_simulation_block(simulation_block);
+        }
+        if let Some(simulation_timestamp) = self.bundle.simulation_timestamp() {
+            bundle = bundle.set_simulation_timestamp(simulation_timestamp);
         }
+        if let Some(min_timestamp) = self.bundle.min_timestamp() {
+            bundle = bundle.set_min_timestamp(min_timestamp);
+        }
+        if let Some(max_timestamp) = self.bundle.max_timestamp() {
+            bundle = bundle.set_max_timestamp(max_timestamp);
+        }
+        bundle
+    }
 
-        Ok(self)
+    /// Check whether the stored bundle and another bundle conflict, i.e., whether they write to the same storage slot.
+    /// Each bundle is traced on its own against the state of the simulation block with `trace_callMany`, which records the storage it touches.
+    /// Balance changes are not compared, since every bundle pays the same coinbase.
+    /// # Arguments
+    /// * `other` - Bundle to check against. It is traced against the same block as the stored bundle.
+    /// # Returns
+    /// * `Result<bool, ArchitectError>` - Whether the bundles write to an overlapping storage slot.
+    pub async fn bundles_conflict(
+        &mut self,
+        other: &BundleRequest,
+    ) -> Result<bool, ArchitectError> {
+        let ours = self.touched_storage(self.bundle.transactions()).await?;
+        let theirs = self.touched_storage(other.transactions()).await?;
+        Ok(!ours.is_disjoint(&theirs))
+    }
+
+    /// Trace transactions in order against the state of the simulation block and collect the storage slots they write.
+    /// # Arguments
+    /// * `transactions` - Transactions of the bundle in order.
+    async fn touched_storage(
+        &self,
+        transactions: &[BundleTransaction],
+    ) -> Result<HashSet<(Address, H256)>, ArchitectError> {
+        let calls = transactions
+            .iter()
+            .map(|transaction| {
+                let request = match transaction {
+                    BundleTransaction::Signed(transaction) => {
+                        let transaction: &Transaction = transaction;
+                        TransactionRequest::from(transaction)
+                    }
+                    BundleTransaction::Raw(raw) => {
+                        let transaction = ethers::utils::rlp::decode::<Transaction>(raw)
+                            .map_err(|err| ArchitectError::InvalidTransaction(err.to_string()))?;
+                        TransactionRequest::from(&transaction)
+                    }
+                };
+                Ok((request, vec![TraceType::StateDiff]))
+            })
+            .collect::<Result<Vec<_>, ArchitectError>>()?;
+        let block = self.bundle.simulation_block().map(BlockNumber::Number);
+        let client = self.client.inner();
+        let traces = self
+            .retry_policy
+            .run(|| client.trace_call_many(calls.clone(), block))
+            .await?;
+
+        let mut touched = HashSet::new();
+        for trace in traces {
+            let Some(StateDiff(accounts)) = trace.state_diff else {
+                continue;
+            };
+            for (address, account) in accounts {
+                for (slot, diff) in account.storage {
+                    if !matches!(diff, Diff::Same) {
+                        touched.insert((address, slot));
+                    }
+                }
+            }
+        }
+        Ok(touched)
+    }
+
+    /// Build a backrun bundle for a target transaction, store it as the bundle to be executed and simulate it.
+    /// # Arguments
+    /// * `target_tx` - Raw signed target transaction to backrun.
+    /// * `backrun` - Transaction that backruns the target.
+    /// # Returns
+    /// * `Result<I256, ArchitectError>` - Profit of the bundle, i.e., the coinbase difference minus the gas fees.
+    pub async fn build_backrun(
+        &mut self,
+        target_tx: Bytes,
+        backrun: TypedTransaction,
+    ) -> Result<I256, ArchitectError> {
+        self.bundle = self.backrun_bundle(target_tx, backrun).await?;
+        let simulated_bundle = self.simulate().await?;
+        Ok(I256::from_raw(simulated_bundle.coinbase_diff)
+            - I256::from_raw(simulated_bundle.gas_fees))
     }
 
     /// Simulate bundle execution.
@@ -115,46 +656,1254 @@ impl<S: Signer> Architect<S> {
     /// * `ExecutionResult<SimulatedBundle>` - Result of the simulation.
     #[deprecated(since = "0.0.1", note = "will be useful for actors in the future")]
     #[allow(warnings)]
-    pub async fn simulate(&mut self) -> ExecutionResult<SimulatedBundle> {
+    pub async fn simulate(&mut self) -> ExecutionResult<SimulatedBundle, P> {
         self.client.inner().simulate_bundle(&self.bundle).await
     }
 
-    /// Send the bundle.
+    /// Simulate bundle execution, retrying transient provider and relay failures according to the [`RetryPolicy`].
+    /// # Returns
+    /// * `Result<SimulatedBundle, ArchitectError>` - Result of the first successful simulation or the error of the last attempt.
+    pub async fn simulate_with_retries(&self) -> Result<SimulatedBundle, ArchitectError> {
+        let (client, bundle) = (&self.client, &self.bundle);
+        self.retry_policy
+            .run(|| client.inner().simulate_bundle(bundle))
+            .await
+    }
+
+    /// Simulate the bundle as if it were included at another block, e.g., a past block for backtesting.
+    /// The simulation and target blocks are only overridden for this call, the stored bundle is left as it is.
+    /// # Arguments
+    /// * `sim_block` - Block whose state the bundle is simulated on top of.
+    /// * `target_block` - Block the bundle is simulated to be included in.
+    /// # Returns
+    /// * `ExecutionResult<SimulatedBundle>` - Result of the simulation.
+    pub async fn simulate_at(
+        &mut self,
+        sim_block: U64,
+        target_block: U64,
+    ) -> ExecutionResult<SimulatedBundle, P> {
+        let bundle = self
+            .bundle_with_transactions(self.bundle.transactions())
+            .set_simulation_block(sim_block)
+            .set_block(target_block);
+        self.client.inner().simulate_bundle(&bundle).await
+    }
+
+    /// Simulate the bundle on top of the latest block as if it were included right away, e.g., to check whether it is profitable right now regardless of where it lands.
+    /// The simulation and target blocks are both set to the latest block for this call only, the stored bundle keeps targeting its block.
+    /// # Returns
+    /// * `ExecutionResult<SimulatedBundle>` - Result of the simulation.
+    pub async fn simulate_current(&mut self) -> ExecutionResult<SimulatedBundle, P> {
+        let latest_block = self.client.inner().get_block_number().await?;
+        self.simulate_at(latest_block, latest_block).await
+    }
+
+    /// Simulate the bundle and summarize the outcome of each transaction, including why reverted transactions failed.
+    /// # Returns
+    /// * `Result<BundleSummary, ArchitectError>` - Summary of the simulation.
+    pub async fn summarize(&mut self) -> Result<BundleSummary, ArchitectError> {
+        let simulated_bundle = self.client.inner().simulate_bundle(&self.bundle).await?;
+        Ok(BundleSummary::from(&simulated_bundle))
+    }
+
+    /// Check that the bundle can fit into a block, since a builder silently drops a bundle whose transactions declare more gas than the block gas limit.
+    /// The gas limits of the transactions are added up and compared with the gas limit of the latest block fetched from the provider.
     /// # Returns
-    /// * `ExecutionResult<PendingBundle>` - Result of the send.
+    /// * `Result<(), ArchitectError>` - An error if the bundle cannot fit or a raw transaction cannot be decoded.
+    pub async fn validate(&self) -> Result<(), ArchitectError> {
+        self.validate_transactions(self.bundle.transactions()).await
+    }
+
+    /// Check the bundle with [`Architect::validate_transactions`] before it is sent, unless disabled with [`Architect::with_validation`].
+    /// # Arguments
+    /// * `transactions` - Transactions of the bundle about to be sent, including any appended at send time.
+    async fn validate_before_send(
+        &self,
+        transactions: &[BundleTransaction],
+    ) -> Result<(), ArchitectError> {
+        if !self.validate_on_send {
+            return Ok(());
+        }
+        self.validate_transactions(transactions).await
+    }
+
+    /// Check that transactions can fit into a block together, as [`Architect::validate`] does for the stored bundle.
+    /// # Arguments
+    /// * `transactions` - Transactions of the bundle in order.
+    async fn validate_transactions(
+        &self,
+        transactions: &[BundleTransaction],
+    ) -> Result<(), ArchitectError> {
+        let client = self.client.inner();
+        let latest_block = self
+            .retry_policy
+            .run(|| client.get_block(BlockNumber::Latest))
+            .await?;
+        let gas_limit = match latest_block {
+            None => return Err(ArchitectError::BlockNumberError),
+            Some(block) => block.gas_limit,
+        };
+        let mut gas = U256::zero();
+        for transaction in transactions {
+            gas = gas.saturating_add(declared_gas(transaction)?);
+        }
+        if gas > gas_limit {
+            return Err(ArchitectError::GasLimitExceeded { gas, gas_limit });
+        }
+        Ok(())
+    }
+
+    /// Send the bundle, retrying transient provider and relay failures according to the [`RetryPolicy`].
+    /// Unless disabled with [`Architect::with_validation`], the bundle is checked with [`Architect::validate`] first, so that a bundle that cannot fit into a block fails before reaching the relay.
+    /// The payment set by [`Architect::add_coinbase_payment`] is appended, as it is by every send.
+    /// # Returns
+    /// * `Result<PendingBundle, ArchitectError>` - Result of the send.
     #[allow(warnings)]
     #[deprecated(since = "0.0.1", note = "will be useful for actors in the future")]
-    pub async fn send(
+    pub async fn send(&mut self) -> Result<PendingBundle<'_, P>, ArchitectError> {
+        let bundle = self.bundle_to_send().await?;
+        let (client, bundle) = (&self.client, &bundle);
+        self.retry_policy
+            .run(|| client.inner().send_bundle(bundle))
+            .await
+    }
+
+    /// Prepare the stored bundle for a send, which every send goes through.
+    /// The payment set by [`Architect::add_coinbase_payment`] is appended: it goes to the coinbase of the latest block fetched from the provider and its nonce follows the bundle transactions of the execution wallet.
+    /// Unless disabled with [`Architect::with_validation`], the bundle is then validated with the payment included, so the 21,000 gas of the payment counts towards the block gas limit.
+    /// # Returns
+    /// * `Result<BundleRequest, ArchitectError>` - The bundle to submit.
+    async fn bundle_to_send(&self) -> Result<BundleRequest, ArchitectError> {
+        let mut bundle = self.bundle_with_transactions(self.bundle.transactions());
+        if let Some(amount) = self.coinbase_payment {
+            let payment = self.coinbase_payment_transaction(amount).await?;
+            bundle = bundle.push_transaction(payment);
+        }
+        self.validate_before_send(bundle.transactions()).await?;
+        Ok(bundle)
+    }
+
+    /// Sign a transaction paying the coinbase of the latest block.
+    /// Fails rather than paying the zero address if the provider does not return the latest block or its coinbase.
+    /// # Arguments
+    /// * `amount` - Amount in wei to pay.
+    async fn coinbase_payment_transaction(&self, amount: U256) -> Result<Bytes, ArchitectError> {
+        let client = self.client.inner();
+        let coinbase = match client.get_block(BlockNumber::Latest).await {
+            Err(err) => return Err(err.into()),
+            Ok(None) => return Err(ArchitectError::BlockNumberError),
+            Ok(Some(Block { author: None, .. })) => {
+                return Err(ArchitectError::Rejected(
+                    "the latest block has no coinbase".to_string(),
+                ))
+            }
+            Ok(Some(Block {
+                author: Some(author),
+                ..
+            })) => author,
+        };
+        let address = self.client.signer().address();
+        let nonce = match client.get_transaction_count(address, None).await {
+            Err(err) => return Err(err.into()),
+            Ok(nonce) => nonce,
+        };
+        // The payment runs after the bundle transactions that the execution wallet sent.
+        let sent_in_bundle = self
+            .bundle
+            .transactions()
+            .iter()
+            .filter(|transaction| match transaction {
+                BundleTransaction::Signed(transaction) => transaction.from == address,
+                BundleTransaction::Raw(raw) => ethers::utils::rlp::decode::<Transaction>(raw)
+                    .map_or(false, |transaction| transaction.from == address),
+            })
+            .count();
+
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(coinbase, amount)
+                .from(address)
+                .nonce(nonce + sent_in_bundle)
+                .gas(21_000)
+                .chain_id(self.client.signer().chain_id()),
+        );
+        self.sign_transactions(std::slice::from_ref(&tx))
+            .await
+            .map(|mut signed| signed.remove(0))
+    }
+
+    /// Send the bundle and wait until its target block is mined.
+    /// Like [`Architect::send`], the bundle is validated and carries the coinbase payment, and transient failures of the send are retried.
+    /// # Returns
+    /// * `Result<Option<H256>, ArchitectError>` - Hash of the block that included the bundle, or `None` if the target block passed without including it.
+    pub async fn send_and_wait(&mut self) -> Result<Option<H256>, ArchitectError> {
+        let bundle = self.bundle_to_send().await?;
+        let (client, bundle) = (self.client.inner(), &bundle);
+        let pending_bundle = self.retry_policy.run(|| client.send_bundle(bundle)).await?;
+        let block = pending_bundle.block;
+        match pending_bundle.await {
+            Ok(_) => Ok(client.get_block(block).await?.and_then(|block| block.hash)),
+            Err(PendingBundleError::BundleNotIncluded) => Ok(None),
+            Err(PendingBundleError::ProviderError(err)) => {
+                Err(FlashbotsMiddlewareError::MiddlewareError(err).into())
+            }
+        }
+    }
+
+    /// Send the bundle along with its replacement UUID, replacing any bundle previously sent with the same UUID for the same block.
+    /// Like [`Architect::send`], the bundle is validated and carries the coinbase payment.
+    /// # Returns
+    /// * `Result<(), ArchitectError>` - Whether the relay accepted the bundle.
+    pub async fn send_replaceable(&self) -> Result<(), ArchitectError> {
+        let uuid = match self.replacement_uuid {
+            None => return Err(ArchitectError::MissingReplacementUuid),
+            Some(uuid) => uuid,
+        };
+        let bundle = self.bundle_to_send().await?;
+        self.send_replacement(bundle.transactions(), uuid).await
+    }
+
+    /// Cancel a bundle sent with a replacement UUID by submitting an empty bundle with the same UUID.
+    /// The cancellation targets the block of the stored bundle, which must be the block the cancelled bundle targeted for the relay to honor it.
+    /// # Arguments
+    /// * `uuid` - Replacement UUID of the bundle to cancel.
+    /// # Returns
+    /// * `Result<(), ArchitectError>` - Whether the relay accepted the cancellation.
+    pub async fn cancel(&mut self, uuid: Uuid) -> Result<(), ArchitectError> {
+        self.send_replacement(&[], uuid).await?;
+        if self.replacement_uuid == Some(uuid) {
+            self.replacement_uuid = None;
+        }
+        Ok(())
+    }
+
+    /// Submit transactions through `eth_sendBundle` with a replacement UUID, since the [`BundleRequest`] of the Flashbots middleware does not carry one.
+    /// Transient relay failures are retried according to the [`RetryPolicy`].
+    /// # Arguments
+    /// * `transactions` - Transactions of the bundle in order.
+    /// * `uuid` - Replacement UUID of the bundle.
+    async fn send_replacement(
+        &self,
+        transactions: &[BundleTransaction],
+        uuid: Uuid,
+    ) -> Result<(), ArchitectError> {
+        let block = match self.bundle.block() {
+            None => return Err(ArchitectError::BlockNumberError),
+            Some(block) => block,
+        };
+        let bundle = ReplaceableBundle::new(block, transactions, uuid);
+        self.relay_request("eth_sendBundle", [bundle]).await
+    }
+
+    /// Send a request straight to the relay, retrying transient failures according to the [`RetryPolicy`].
+    /// # Arguments
+    /// * `method` - JSON-RPC method of the request.
+    /// * `params` - Parameters of the request.
+    async fn relay_request<T: Serialize + Send + Sync>(
+        &self,
+        method: &str,
+        params: T,
+    ) -> Result<(), ArchitectError> {
+        let (relay, params) = (self.client.inner().relay(), &params);
+        self.retry_policy
+            .run(|| async move {
+                relay
+                    .request::<_, serde_json::Value>(method, params)
+                    .await
+                    .map_err(relay_error)
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Sign a single transaction and send it privately to the builders through the relay's `eth_sendPrivateTransaction`, keeping it out of the public mempool.
+    /// A transaction without a gas price is priced by the [`GasEstimator`]. Transient relay failures are retried according to the [`RetryPolicy`].
+    /// # Arguments
+    /// * `tx` - Transaction to send.
+    /// * `max_block` - Last block the transaction may be included in, after which the relay drops it. The relay picks a default if unset.
+    /// # Returns
+    /// * `Result<H256, ArchitectError>` - Hash of the signed transaction.
+    pub async fn send_private_transaction(
+        &mut self,
+        mut tx: TypedTransaction,
+        max_block: Option<U64>,
+    ) -> Result<H256, ArchitectError> {
+        self.fill_gas_price(&mut tx).await?;
+        let signature = match self.client.signer().sign_transaction(&tx).await {
+            Err(err) => return Err(ArchitectError::SigningError(err.to_string())),
+            Ok(sig) => sig,
+        };
+        let private_transaction = PrivateTransaction {
+            tx: tx.rlp_signed(&signature),
+            max_block_number: max_block,
+        };
+        self.relay_request("eth_sendPrivateTransaction", [private_transaction])
+            .await?;
+        Ok(tx.hash(&signature))
+    }
+
+    /// Get the stats of a sent bundle from the relay, e.g., whether the builder considered, simulated or sealed it.
+    /// # Arguments
+    /// * `bundle_hash` - Hash of the bundle, as returned when it was sent.
+    /// * `block` - Block the bundle targeted.
+    /// # Returns
+    /// * `ExecutionResult<BundleStats>` - Stats of the bundle.
+    pub async fn get_bundle_stats(
+        &self,
+        bundle_hash: H256,
+        block: U64,
+    ) -> ExecutionResult<BundleStats, P> {
+        self.client
+            .inner()
+            .get_bundle_stats(bundle_hash, block)
+            .await
+    }
+
+    /// Send the bundle to the primary relay and every additional relay concurrently.
+    /// A failure on one relay does not stop the bundle from reaching the others.
+    /// Like [`Architect::send`], the bundle is validated and carries the coinbase payment, which is prepared once before the bundle is sent to any relay.
+    /// # Returns
+    /// * `Result<Vec<ExecutionResult<PendingBundle>>, ArchitectError>` - Result of the send for each relay, the primary relay first and then the additional relays in the order they were added, or the validation error.
+    pub async fn send_all(
+        &self,
+    ) -> Result<Vec<ExecutionResult<PendingBundle<'_, P>>>, ArchitectError> {
+        let bundle = self.bundle_to_send().await?;
+        let clients = std::iter::once(self.client.inner()).chain(self.broadcast_clients.iter());
+        Ok(join_all(clients.map(|client| client.send_bundle(&bundle))).await)
+    }
+
+    /// Resend the bundle on every new block, retargeting it to the next block, until it is included or the deadline passes.
+    /// Inclusion is detected through the receipt of the last bundle transaction.
+    /// Like [`Architect::send`], each attempt is validated and carries a coinbase payment to the coinbase of that block, and transient failures of a send are retried.
+    /// # Arguments
+    /// * `deadline_blocks` - Number of blocks after the current one to keep resending for.
+    /// * `on_attempt` - Called after each send with the targeted block and the bundle hash returned by the relay, e.g., to trace attempts.
+    /// # Returns
+    /// * `Result<Option<TransactionReceipt>, ArchitectError>` - Receipt of the last bundle transaction if the bundle was included, `None` if the deadline passed.
+    pub async fn send_until_included<F: FnMut(U64, Option<H256>)>(
         &mut self,
-    ) -> ExecutionResult<
-        PendingBundle<
-            '_,
-            <FlashbotsMiddleware<Provider<Http>, LocalWallet> as Middleware>::Provider,
-        >,
-    > {
-        self.client.inner().send_bundle(&self.bundle).await
+        deadline_blocks: u64,
+        mut on_attempt: F,
+    ) -> Result<Option<TransactionReceipt>, ArchitectError> {
+        let last_transaction = match self.bundle.transactions().last() {
+            None => return Ok(None),
+            Some(transaction) => bundle_transaction_hash(transaction),
+        };
+        let client = self.client.inner();
+        let mut blocks = client.watch_blocks().await?;
+        let mut block_number = client.get_block_number().await?;
+        let deadline = block_number + deadline_blocks;
+
+        while block_number < deadline {
+            let target = block_number + 1;
+            let bundle = std::mem::replace(&mut self.bundle, BundleRequest::new());
+            self.bundle = bundle.set_block(target).set_simulation_block(block_number);
+            let bundle = self.bundle_to_send().await?;
+            let bundle_hash = self
+                .retry_policy
+                .run(|| client.send_bundle(&bundle))
+                .await?
+                .bundle_hash;
+            on_attempt(target, bundle_hash);
+
+            // Wait for the target block to be mined.
+            while block_number < target {
+                if blocks.next().await.is_none() {
+                    return Ok(None);
+                }
+                block_number = client.get_block_number().await?;
+            }
+            if let Some(receipt) = client.get_transaction_receipt(last_transaction).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Hash of a bundle transaction, which identifies it once it is included.
+/// # Arguments
+/// * `transaction` - Signed transaction, decoded or raw.
+fn bundle_transaction_hash(transaction: &BundleTransaction) -> H256 {
+    match transaction {
+        BundleTransaction::Signed(transaction) => transaction.hash,
+        BundleTransaction::Raw(raw) => H256::from(ethers::utils::keccak256(raw)),
+    }
+}
+
+/// Gas limit declared by a bundle transaction.
+/// # Arguments
+/// * `transaction` - Signed transaction, decoded or raw.
+fn declared_gas(transaction: &BundleTransaction) -> Result<U256, ArchitectError> {
+    match transaction {
+        BundleTransaction::Signed(transaction) => Ok(transaction.gas),
+        BundleTransaction::Raw(raw) => ethers::utils::rlp::decode::<Transaction>(raw)
+            .map(|transaction| transaction.gas)
+            .map_err(|err| ArchitectError::InvalidTransaction(err.to_string())),
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread::{self, JoinHandle},
+    };
+
+    use async_trait::async_trait;
     use ethers::{
-        core::rand::thread_rng, prelude::*, types::transaction::eip2718::TypedTransaction,
+        core::rand::thread_rng,
+        prelude::*,
+        types::transaction::{
+            eip2718::TypedTransaction,
+            eip2930::{AccessList, Eip2930TransactionRequest},
+        },
+    };
+    use ethers_flashbots::{
+        BundleRequest, BundleTransaction, SimulatedBundle, SimulatedTransaction,
+    };
+    use serde_json::{json, Value};
+
+    use super::{
+        gas::GasEstimator,
+        mev_share::{RefundConfig, RelayKind},
+        summary::{estimate_profit, BundleSummary, RevertReason},
+        Architect, ArchitectError,
     };
 
-    use super::Architect;
+    fn simulated_transaction(gas_used: u64) -> SimulatedTransaction {
+        SimulatedTransaction {
+            hash: H256::zero(),
+            coinbase_diff: U256::zero(),
+            coinbase_tip: U256::zero(),
+            gas_price: U256::zero(),
+            gas_used: gas_used.into(),
+            gas_fees: U256::zero(),
+            from: Address::zero(),
+            to: None,
+            value: None,
+            error: None,
+            revert: None,
+        }
+    }
+
+    fn simulated_bundle(transactions: Vec<SimulatedTransaction>) -> SimulatedBundle {
+        SimulatedBundle {
+            hash: H256::zero(),
+            coinbase_diff: U256::zero(),
+            coinbase_tip: U256::zero(),
+            gas_price: U256::zero(),
+            gas_used: U256::zero(),
+            gas_fees: U256::zero(),
+            simulation_block: U64::zero(),
+            transactions,
+        }
+    }
+
+    #[derive(Debug)]
+    struct FixedGasEstimator(U256);
+
+    #[async_trait]
+    impl GasEstimator for FixedGasEstimator {
+        async fn gas_price(&self) -> Result<U256, ArchitectError> {
+            Ok(self.0)
+        }
+    }
+
+    /// A relay that answers the JSON-RPC requests of an `Architect` with canned results in order and records the requests, so bundles can be tested offline.
+    struct MockRelay {
+        url: url::Url,
+        requests: Arc<Mutex<Vec<Value>>>,
+        server: JoinHandle<()>,
+    }
+
+    impl MockRelay {
+        /// Serve one request for each result, answering them in order.
+        fn serve(results: Vec<Value>) -> Self {
+            Self::respond(
+                results
+                    .into_iter()
+                    .map(|result| json!({ "result": result }))
+                    .collect(),
+            )
+        }
+
+        /// Serve one request for each response, answering them in order with either a `result` or an `error`.
+        fn respond(responses: Vec<Value>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url =
+                url::Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let recorded = Arc::clone(&requests);
+            let server = thread::spawn(move || {
+                for mut response in responses {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if line.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                content_length = value.trim().parse().unwrap();
+                            }
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    let request: Value = serde_json::from_slice(&body).unwrap();
+                    response["jsonrpc"] = json!("2.0");
+                    response["id"] = request["id"].clone();
+                    let response = response.to_string();
+                    recorded.lock().unwrap().push(request);
+                    write!(
+                        reader.get_mut(),
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        response.len(),
+                        response
+                    )
+                    .unwrap();
+                }
+            });
+            Self {
+                url,
+                requests,
+                server,
+            }
+        }
+
+        /// Wait until every result was served and return the recorded requests.
+        fn requests(self) -> Vec<Value> {
+            self.server.join().unwrap();
+            let requests = self.requests.lock().unwrap();
+            requests.clone()
+        }
+    }
+
+    /// A provider that answers the block number request of the `Architect` constructors without a network.
+    fn mocked_provider(block_number: u64) -> Provider<MockProvider> {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(block_number)).unwrap();
+        provider
+    }
 
     // We will need more tests in future but this just ensures basic functionality is working.
     #[tokio::test]
     async fn test_architect_creation() {
-        let provider = Provider::<Http>::try_from("https://mainnet.eth.aragon.network").unwrap();
-        let tx = TypedTransaction::Legacy(TransactionRequest::pay("vitalik.eth", 100));
+        let provider = mocked_provider(17_000_000);
+        let tx = TypedTransaction::Legacy(TransactionRequest::pay(Address::zero(), 100));
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .with_gas_estimator(Box::new(FixedGasEstimator(U256::one())))
+            .add_transactions(&vec![tx])
+            .await
+            .unwrap();
+        assert_eq!(architect.bundle.block(), Some(U64::from(17_000_001)));
+        assert_eq!(architect.bundle.transactions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_of_every_transaction_type_offline() {
+        let wallet = LocalWallet::new(&mut thread_rng());
+        let to = Address::from_low_u64_be(1);
+        let legacy = TransactionRequest::pay(to, 100)
+            .from(wallet.address())
+            .gas(21_000)
+            .gas_price(1)
+            .chain_id(1);
+        let transactions = vec![
+            TypedTransaction::Legacy(legacy.clone().nonce(0)),
+            TypedTransaction::Eip2930(Eip2930TransactionRequest::new(
+                legacy.nonce(1),
+                AccessList::default(),
+            )),
+            TypedTransaction::Eip1559(
+                Eip1559TransactionRequest::new()
+                    .to(to)
+                    .value(100)
+                    .from(wallet.address())
+                    .nonce(2)
+                    .gas(21_000)
+                    .max_fee_per_gas(2)
+                    .max_priority_fee_per_gas(1)
+                    .chain_id(1),
+            ),
+        ];
+        // Signatures are deterministic, so signing again yields the RLP the bundle has to carry.
+        let expected: Vec<Bytes> = transactions
+            .iter()
+            .map(|tx| tx.rlp_signed(&wallet.sign_transaction_sync(tx).unwrap()))
+            .collect();
+        assert!(expected[0][0] >= 0xc0);
+        assert_eq!(expected[1][0], 0x01);
+        assert_eq!(expected[2][0], 0x02);
+
+        let relay = MockRelay::serve(vec![
+            json!({
+                "bundleHash": H256::zero(),
+                "coinbaseDiff": "63000",
+                "ethSentToCoinbase": "0",
+                "bundleGasPrice": "1",
+                "totalGasUsed": 63_000,
+                "gasFees": "63000",
+                "stateBlockNumber": 100,
+                "results": [],
+            }),
+            json!({ "bundleHash": H256::zero() }),
+        ]);
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let mut architect = Architect::new_with_relay(provider, wallet, relay.url.clone())
+            .await
+            .unwrap()
+            .add_transactions(&transactions)
+            .await
+            .unwrap();
+
+        let simulated_bundle = architect.simulate().await.unwrap();
+        assert_eq!(simulated_bundle.gas_used, U256::from(63_000));
+        assert_eq!(simulated_bundle.simulation_block, U64::from(100));
+        // The bundle is validated against the latest block before it is sent.
+        mock.push(Block::<H256> {
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        })
+        .unwrap();
+        let pending_bundle = architect.send().await.unwrap();
+        assert_eq!(pending_bundle.block, U64::from(101));
+
+        let requests = relay.requests();
+        assert_eq!(requests[0]["method"], "eth_callBundle");
+        assert_eq!(requests[1]["method"], "eth_sendBundle");
+        for request in requests {
+            let bundle = &request["params"][0];
+            let txs: Vec<Bytes> = serde_json::from_value(bundle["txs"].clone()).unwrap();
+            assert_eq!(txs, expected);
+            assert_eq!(bundle["blockNumber"], json!(U64::from(101)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_simulate_current() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let relay = MockRelay::serve(vec![json!({
+            "bundleHash": H256::zero(),
+            "coinbaseDiff": "0",
+            "ethSentToCoinbase": "0",
+            "bundleGasPrice": "0",
+            "totalGasUsed": 0,
+            "gasFees": "0",
+            "stateBlockNumber": 105,
+            "results": [],
+        })]);
+        let mut architect = Architect::new_with_relay(
+            provider,
+            LocalWallet::new(&mut thread_rng()),
+            relay.url.clone(),
+        )
+        .await
+        .unwrap();
+
+        // The chain moved on since the bundle was built.
+        mock.push(U64::from(105)).unwrap();
+        architect.simulate_current().await.unwrap();
+        let requests = relay.requests();
+        assert_eq!(requests[0]["method"], "eth_callBundle");
+        assert_eq!(
+            requests[0]["params"][0]["blockNumber"],
+            json!(U64::from(105))
+        );
+        assert_eq!(
+            requests[0]["params"][0]["stateBlockNumber"],
+            json!(U64::from(105))
+        );
+        assert_eq!(architect.bundle.block(), Some(U64::from(101)));
+    }
+
+    #[tokio::test]
+    async fn test_validate_against_block_gas_limit() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let heavy = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::zero(), 100)
+                .gas(20_000_000)
+                .gas_price(1),
+        );
+        let latest = Block::<H256> {
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        };
+
+        let mut architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .add_transactions(&vec![heavy.clone()])
+            .await
+            .unwrap();
+        mock.push(latest.clone()).unwrap();
+        architect.validate().await.unwrap();
+
+        // Two of them no longer fit, so the send fails before reaching the relay.
+        architect = architect.add_transactions(&vec![heavy]).await.unwrap();
+        mock.push(latest).unwrap();
+        assert!(matches!(
+            architect.send().await,
+            Err(ArchitectError::GasLimitExceeded { gas, gas_limit })
+                if gas == U256::from(40_000_000) && gas_limit == U256::from(30_000_000)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_custom_relay() {
+        let relay = url::Url::parse("http://localhost:18550").unwrap();
+
+        let architect = Architect::new_with_relay(
+            mocked_provider(100),
+            LocalWallet::new(&mut thread_rng()),
+            relay.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(architect.relay, relay);
+    }
+
+    /// Needs a node that serves WebSockets at `ARBITER_WS_RPC_URL`, so it only runs when ignored tests are requested.
+    #[tokio::test]
+    #[ignore = "needs ARBITER_WS_RPC_URL"]
+    async fn test_ws_provider() {
+        let url = std::env::var("ARBITER_WS_RPC_URL").unwrap();
+        let provider = Provider::<Ws>::connect(url).await.unwrap();
+
+        let architect: Architect<LocalWallet, Ws> =
+            Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+                .await
+                .unwrap();
+        assert!(architect.bundle.block().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_window() {
+        let provider = mocked_provider(100);
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .set_min_timestamp(1_700_000_000)
+            .set_max_timestamp(1_700_000_120);
+        assert_eq!(architect.bundle.min_timestamp(), Some(1_700_000_000));
+        assert_eq!(architect.bundle.max_timestamp(), Some(1_700_000_120));
+
+        // Bundles built for simulation keep the window.
+        let bundle = architect.bundle_with_transactions(architect.bundle.transactions());
+        assert_eq!(bundle.min_timestamp(), Some(1_700_000_000));
+        assert_eq!(bundle.max_timestamp(), Some(1_700_000_120));
+        let payload = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(payload["minTimestamp"], 1_700_000_000);
+        assert_eq!(payload["maxTimestamp"], 1_700_000_120);
+    }
+
+    #[tokio::test]
+    async fn test_revertible_transactions() {
+        let provider = mocked_provider(100);
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100).gas_price(1),
+        );
+        let probe = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(2), 100).gas_price(1),
+        );
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .add_transactions(&vec![tx])
+            .await
+            .unwrap()
+            .add_transactions_allow_revert(&vec![probe])
+            .await
+            .unwrap();
+        assert_eq!(architect.revertible_transactions.len(), 1);
+
+        // Bundles built for simulation keep the probe revert-tolerant and only the probe.
+        let bundle = architect.bundle_with_transactions(architect.bundle.transactions());
+        let payload = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(
+            payload["revertingTxHashes"],
+            serde_json::json!([architect.revertible_transactions[0]])
+        );
+    }
 
-        let _architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+    #[tokio::test]
+    async fn test_push_raw_transactions() {
+        let provider = mocked_provider(100);
+        let user = LocalWallet::new(&mut thread_rng());
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100)
+                .from(user.address())
+                .gas_price(1),
+        );
+        let raw = tx.rlp_signed(&user.sign_transaction_sync(&tx).unwrap());
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .push_raw_transactions(&[raw.clone()]);
+        assert!(matches!(
+            architect.bundle.transactions(),
+            [BundleTransaction::Raw(pushed)] if *pushed == raw
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_coinbase_payment_follows_bundle() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let wallet = LocalWallet::new(&mut thread_rng());
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100)
+                .from(wallet.address())
+                .nonce(0)
+                .gas_price(1),
+        );
+
+        // The payment has no gas price, so it is priced by the estimator rather than by the mocked provider.
+        let architect = Architect::new(provider, wallet)
+            .await
+            .unwrap()
+            .with_gas_estimator(Box::new(FixedGasEstimator(U256::from(7))))
+            .add_transactions(&vec![tx])
+            .await
+            .unwrap()
+            .add_coinbase_payment(U256::exp10(16));
+
+        // The mocked responses are served last in first out: the latest block, then the transaction count.
+        let coinbase = Address::from_low_u64_be(0xc0);
+        mock.push(U256::zero()).unwrap();
+        mock.push(Block::<H256> {
+            author: Some(coinbase),
+            ..Default::default()
+        })
+        .unwrap();
+        let payment = architect
+            .coinbase_payment_transaction(architect.coinbase_payment.unwrap())
+            .await
+            .unwrap();
+
+        // A fresh wallet has no transactions on chain, so the payment takes the nonce after the bundle transaction.
+        let payment = ethers::utils::rlp::decode::<Transaction>(&payment).unwrap();
+        assert_eq!(payment.to, Some(coinbase));
+        assert_eq!(payment.value, U256::exp10(16));
+        assert_eq!(payment.nonce, U256::one());
+        assert_eq!(payment.gas_price, Some(U256::from(7)));
+
+        // Without a coinbase the payment is not signed at all.
+        mock.push(Block::<H256>::default()).unwrap();
+        let result = architect
+            .coinbase_payment_transaction(architect.coinbase_payment.unwrap())
+            .await;
+        assert!(matches!(result, Err(ArchitectError::Rejected(_))));
+    }
+
+    #[tokio::test]
+    async fn test_every_send_validates() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let wallet = LocalWallet::new(&mut thread_rng());
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100)
+                .from(wallet.address())
+                .nonce(0)
+                .gas(29_990_000)
+                .gas_price(1),
+        );
+        let latest = Block::<H256> {
+            author: Some(Address::from_low_u64_be(0xc0)),
+            gas_limit: U256::from(30_000_000),
+            ..Default::default()
+        };
+
+        let mut architect = Architect::new(provider, wallet)
             .await
             .unwrap()
+            .with_gas_estimator(Box::new(FixedGasEstimator(U256::one())))
             .add_transactions(&vec![tx])
+            .await
+            .unwrap()
+            .add_coinbase_payment(U256::exp10(16))
+            .set_replacement_uuid(uuid::Uuid::from_u128(1));
+
+        // The bundle fits on its own, but not with the payment appended.
+        mock.push(latest.clone()).unwrap();
+        architect.validate().await.unwrap();
+
+        // Every send appends the payment and fails before reaching the relay.
+        // The mocked responses are served last in first out: the coinbase, the transaction count, then the block to validate against.
+        let push_send_responses = || {
+            mock.push(latest.clone()).unwrap();
+            mock.push(U256::zero()).unwrap();
+            mock.push(latest.clone()).unwrap();
+        };
+        let exceeded = U256::from(30_011_000);
+        push_send_responses();
+        assert!(matches!(
+            architect.send().await,
+            Err(ArchitectError::GasLimitExceeded { gas, .. }) if gas == exceeded
+        ));
+        push_send_responses();
+        assert!(matches!(
+            architect.send_all().await,
+            Err(ArchitectError::GasLimitExceeded { gas, .. }) if gas == exceeded
+        ));
+        push_send_responses();
+        assert!(matches!(
+            architect.send_replaceable().await,
+            Err(ArchitectError::GasLimitExceeded { gas, .. }) if gas == exceeded
+        ));
+        push_send_responses();
+        assert!(matches!(
+            architect.send_and_wait().await,
+            Err(ArchitectError::GasLimitExceeded { gas, .. }) if gas == exceeded
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_relays() {
+        let provider = mocked_provider(100);
+        let relays = vec![
+            url::Url::parse("https://rpc.titanbuilder.xyz").unwrap(),
+            url::Url::parse("https://rsync-builder.xyz").unwrap(),
+        ];
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .with_relays(relays);
+        assert_eq!(architect.broadcast_clients.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_1559_priority_fees() {
+        let gwei = U256::exp10(9);
+        // The last transaction leaves its maximum fee to the gas estimator.
+        let transactions: Vec<Eip1559TransactionRequest> =
+            [(Some(100), 1), (Some(100), 2), (None, 3)]
+                .into_iter()
+                .map(|(max_fee, priority_fee)| {
+                    let tx = Eip1559TransactionRequest::new()
+                        .to(Address::zero())
+                        .gas(21_000)
+                        .max_priority_fee_per_gas(gwei * priority_fee);
+                    match max_fee {
+                        Some(max_fee) => tx.max_fee_per_gas(gwei * max_fee),
+                        None => tx,
+                    }
+                })
+                .collect();
+
+        let architect = Architect::new(mocked_provider(100), LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .with_gas_estimator(Box::new(FixedGasEstimator(gwei * 50)))
+            .add_1559_transactions(&transactions)
+            .await
+            .unwrap();
+
+        let fees: Vec<(Option<U256>, Option<U256>)> = architect
+            .bundle
+            .transactions()
+            .iter()
+            .map(|transaction| match transaction {
+                BundleTransaction::Raw(raw) => {
+                    let signed = ethers::utils::rlp::decode::<Transaction>(raw).unwrap();
+                    (signed.max_fee_per_gas, signed.max_priority_fee_per_gas)
+                }
+                _ => panic!("Signed transactions are added as raw bytes."),
+            })
+            .collect();
+        assert_eq!(
+            fees,
+            vec![
+                (Some(gwei * 100), Some(gwei)),
+                (Some(gwei * 100), Some(gwei * 2)),
+                (Some(gwei * 50), Some(gwei * 3)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backrun_bundle() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(U64::from(100)).unwrap();
+        let target_tx = Bytes::from(vec![0x02, 0x01]);
+        let backrun = TypedTransaction::Legacy(TransactionRequest::new().to(Address::zero()));
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
             .await
             .unwrap();
+        // The backrun has no gas price, so the default estimator asks the provider for one.
+        mock.push(U256::from(1)).unwrap();
+        let bundle = architect
+            .backrun_bundle(target_tx.clone(), backrun)
+            .await
+            .unwrap();
+
+        assert_eq!(bundle.transactions().len(), 2);
+        match &bundle.transactions()[0] {
+            BundleTransaction::Raw(raw) => assert_eq!(*raw, target_tx),
+            _ => panic!("The target transaction should be included as raw bytes."),
+        }
+        assert_eq!(bundle.block(), architect.bundle.block());
+    }
+
+    #[tokio::test]
+    async fn test_custom_gas_estimator() {
+        let provider = mocked_provider(100);
+        let gas_price = U256::from(42_000_000_000_u64);
+
+        let architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+            .await
+            .unwrap()
+            .with_gas_estimator(Box::new(FixedGasEstimator(gas_price)));
+
+        let mut tx = TypedTransaction::Legacy(TransactionRequest::pay("vitalik.eth", 100));
+        architect.fill_gas_price(&mut tx).await.unwrap();
+        assert_eq!(tx.gas_price(), Some(gas_price));
+    }
+
+    #[tokio::test]
+    async fn test_send_mev_share() {
+        let relay = MockRelay::serve(vec![json!({ "bundleHash": H256::zero() })]);
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100).gas_price(1),
+        );
+        let probe = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(2), 100).gas_price(1),
+        );
+        let architect = Architect::new_with_relay(
+            mocked_provider(100),
+            LocalWallet::new(&mut thread_rng()),
+            relay.url.clone(),
+        )
+        .await
+        .unwrap()
+        .with_relay_kind(RelayKind::MevShare)
+        .add_transactions(&vec![tx])
+        .await
+        .unwrap()
+        .add_transactions_allow_revert(&vec![probe])
+        .await
+        .unwrap();
+
+        let recipient = Address::from_low_u64_be(7);
+        architect
+            .send_mev_share(RefundConfig {
+                recipient,
+                percent: 90,
+            })
+            .await
+            .unwrap();
+        let requests = relay.requests();
+        assert_eq!(requests[0]["method"], "mev_sendBundle");
+        let bundle = &requests[0]["params"][0];
+        assert_eq!(bundle["inclusion"]["block"], json!(U64::from(101)));
+        // Only the probe may revert.
+        assert_eq!(bundle["body"][0]["canRevert"], false);
+        assert_eq!(bundle["body"][1]["canRevert"], true);
+        assert_eq!(bundle["validity"]["refund"][0]["percent"], 90);
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[tokio::test]
+    async fn test_relay_requests_are_retried() {
+        use std::time::Duration;
+
+        use super::retry::{FailureInjector, RetryPolicy};
+
+        let relay = MockRelay::serve(vec![json!(H256::zero())]);
+        let retry_policy = RetryPolicy::new(3, Duration::from_millis(1))
+            .with_failure_injector(FailureInjector::new(1.0, 0).with_max_failures(2));
+        let mut architect = Architect::new_with_relay(
+            mocked_provider(100),
+            LocalWallet::new(&mut thread_rng()),
+            relay.url.clone(),
+        )
+        .await
+        .unwrap()
+        .with_retry_policy(retry_policy);
+
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100)
+                .gas(21_000)
+                .gas_price(1),
+        );
+        architect.send_private_transaction(tx, None).await.unwrap();
+
+        // Both injected failures were retried and only the successful attempt reached the relay.
+        let failure_injector = architect.retry_policy.failure_injector.clone().unwrap();
+        assert_eq!(failure_injector.lock().unwrap().failures, 2);
+        let requests = relay.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["method"], "eth_sendPrivateTransaction");
+    }
+
+    #[tokio::test]
+    async fn test_only_unreachable_relays_are_retried() {
+        use std::time::Duration;
+
+        use super::retry::RetryPolicy;
+
+        let retry_policy = RetryPolicy::new(3, Duration::from_millis(1));
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(1), 100)
+                .gas(21_000)
+                .gas_price(1),
+        );
+
+        // A rejection is returned after the first request.
+        let relay = MockRelay::respond(vec![
+            json!({ "error": { "code": -32000, "message": "bundle rejected" } }),
+        ]);
+        let mut architect = Architect::new_with_relay(
+            mocked_provider(100),
+            LocalWallet::new(&mut thread_rng()),
+            relay.url.clone(),
+        )
+        .await
+        .unwrap()
+        .with_retry_policy(retry_policy.clone());
+        let result = architect.send_private_transaction(tx.clone(), None).await;
+        assert!(matches!(&result, Err(err @ ArchitectError::Rejected(_)) if !err.is_transient()));
+        assert_eq!(relay.requests().len(), 1);
+
+        // Nothing listens on port 1, so every attempt fails to connect.
+        let mut architect = Architect::new_with_relay(
+            mocked_provider(100),
+            LocalWallet::new(&mut thread_rng()),
+            url::Url::parse("http://127.0.0.1:1").unwrap(),
+        )
+        .await
+        .unwrap()
+        .with_retry_policy(retry_policy);
+        let result = architect.send_private_transaction(tx, None).await;
+        assert!(matches!(&result, Err(err @ ArchitectError::RelayError(_)) if err.is_transient()));
+    }
+
+    /// A `trace_callMany` result with one transaction per entry, each changing the given storage slots.
+    fn state_diff_traces(transactions: &[&[(Address, H256)]]) -> Value {
+        let traces: Vec<Value> = transactions
+            .iter()
+            .map(|slots| {
+                let mut accounts = serde_json::Map::new();
+                for (address, slot) in slots.iter() {
+                    let account = accounts.entry(format!("{:?}", address)).or_insert_with(
+                        || json!({ "balance": "=", "nonce": "=", "code": "=", "storage": {} }),
+                    );
+                    account["storage"][format!("{:?}", slot)] =
+                        json!({ "*": { "from": H256::zero(), "to": H256::from_low_u64_be(1) } });
+                }
+                json!({ "output": "0x", "stateDiff": accounts })
+            })
+            .collect();
+        json!(traces)
+    }
+
+    /// A raw transaction signed by a fresh wallet.
+    fn raw_transaction(to: u64) -> Bytes {
+        let user = LocalWallet::new(&mut thread_rng());
+        let tx = TypedTransaction::Legacy(
+            TransactionRequest::pay(Address::from_low_u64_be(to), 100)
+                .from(user.address())
+                .gas_price(1),
+        );
+        tx.rlp_signed(&user.sign_transaction_sync(&tx).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_bundles_conflict() {
+        let pool = Address::from_low_u64_be(1);
+        let token = Address::from_low_u64_be(2);
+        let reserve = H256::from_low_u64_be(8);
+        let balance = H256::from_low_u64_be(9);
+
+        // Both bundles write the reserve slot of the pool.
+        let ours: &[(Address, H256)] = &[(pool, reserve)];
+        let same_slot: &[(Address, H256)] = &[(token, balance), (pool, reserve)];
+        // Writing another slot of the same contract does not conflict.
+        let disjoint: &[(Address, H256)] = &[(token, balance), (pool, balance)];
+
+        for (theirs, conflict) in [(same_slot, true), (disjoint, false)] {
+            let (provider, mock) = Provider::mocked();
+            // Responses are served last in, first out.
+            mock.push(state_diff_traces(&[theirs])).unwrap();
+            mock.push(state_diff_traces(&[ours])).unwrap();
+            mock.push(U64::from(100)).unwrap();
+            let mut architect = Architect::new(provider, LocalWallet::new(&mut thread_rng()))
+                .await
+                .unwrap();
+            architect.bundle = std::mem::take(&mut architect.bundle)
+                .push_transaction(BundleTransaction::Raw(raw_transaction(1)));
+            let other =
+                BundleRequest::new().push_transaction(BundleTransaction::Raw(raw_transaction(2)));
+
+            assert_eq!(architect.bundles_conflict(&other).await.unwrap(), conflict);
+        }
+    }
+
+    #[test]
+    fn test_estimate_profit() {
+        let gwei = U256::exp10(9);
+        let mut paying = simulated_transaction(100_000);
+        paying.gas_price = gwei * 10;
+        paying.coinbase_diff = gwei * 3_000_000;
+        let mut reverted = simulated_transaction(50_000);
+        reverted.gas_price = gwei * 10;
+
+        let bundle = simulated_bundle(vec![paying, reverted]);
+        assert_eq!(
+            estimate_profit(&bundle),
+            I256::from_raw(gwei * 3_000_000) - I256::from_raw(gwei * 1_500_000)
+        );
+
+        let mut unpaid = simulated_transaction(21_000);
+        unpaid.gas_price = gwei;
+        assert!(estimate_profit(&simulated_bundle(vec![unpaid])).is_negative());
+    }
+
+    #[test]
+    fn test_bundle_summary_revert_classification() {
+        let mut out_of_gas = simulated_transaction(30_000);
+        out_of_gas.error = Some("out of gas".to_string());
+
+        // `require(false, "insufficient output")` reverts with `Error(string)`.
+        let mut require_revert = simulated_transaction(25_000);
+        require_revert.error = Some("execution reverted".to_string());
+        require_revert.revert = Some(format!(
+            "0x08c379a0{}",
+            ethers::utils::hex::encode(ethers::abi::encode(&[ethers::abi::Token::String(
+                "insufficient output".to_string()
+            )]))
+        ));
+
+        let summary = BundleSummary::from(&simulated_bundle(vec![
+            simulated_transaction(21_000),
+            out_of_gas,
+            require_revert,
+        ]));
+
+        assert!(!summary.transactions[0].reverted());
+        assert_eq!(
+            summary.transactions[1].revert_reason,
+            Some(RevertReason::OutOfGas)
+        );
+        assert_eq!(
+            summary.transactions[2].revert_reason,
+            Some(RevertReason::Require("insufficient output".to_string()))
+        );
     }
 }
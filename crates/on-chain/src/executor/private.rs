@@ -0,0 +1,46 @@
+#![warn(missing_docs)]
+//! ## Private
+//!
+//! Request type for `eth_sendPrivateTransaction`, which routes a single transaction to the builders without it entering the public mempool.
+
+use ethers::prelude::*;
+use serde::Serialize;
+
+/// Parameters of an `eth_sendPrivateTransaction` request.
+/// # Fields
+/// * `tx` - Raw signed transaction.
+/// * `max_block_number` - Last block the transaction may be included in, after which it is dropped.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivateTransaction {
+    /// Raw signed transaction.
+    pub tx: Bytes,
+    /// Last block the transaction may be included in, after which it is dropped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_block_number: Option<U64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::*;
+
+    use super::PrivateTransaction;
+
+    #[test]
+    fn test_max_block_number_serialized() {
+        let transaction = PrivateTransaction {
+            tx: Bytes::from(vec![0x02, 0x01]),
+            max_block_number: Some(U64::from(17_000_000)),
+        };
+        let payload = serde_json::to_value(&transaction).unwrap();
+        assert_eq!(payload["tx"], "0x0201");
+        assert_eq!(payload["maxBlockNumber"], "0x1036640");
+
+        let transaction = PrivateTransaction {
+            max_block_number: None,
+            ..transaction
+        };
+        let payload = serde_json::to_value(&transaction).unwrap();
+        assert!(payload.get("maxBlockNumber").is_none());
+    }
+}
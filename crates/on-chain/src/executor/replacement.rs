@@ -0,0 +1,78 @@
+#![warn(missing_docs)]
+//! ## Replacement
+//!
+//! Request type for `eth_sendBundle` with a replacement UUID, which lets a later submission with the same UUID replace or cancel the bundle.
+//! A replacement is only honored by the relay if it targets the same block as the bundle it replaces.
+
+use ethers::prelude::*;
+use ethers_flashbots::BundleTransaction;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Parameters of an `eth_sendBundle` request that carries a replacement UUID.
+/// # Fields
+/// * `txs` - Raw signed transactions of the bundle in order.
+/// * `block_number` - Block the bundle targets.
+/// * `replacement_uuid` - UUID that later submissions use to replace or cancel the bundle.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceableBundle {
+    /// Raw signed transactions of the bundle in order.
+    pub txs: Vec<Bytes>,
+    /// Block the bundle targets.
+    pub block_number: U64,
+    /// UUID that later submissions use to replace or cancel the bundle.
+    pub replacement_uuid: Uuid,
+}
+
+impl ReplaceableBundle {
+    /// Builds a replaceable bundle.
+    /// # Arguments
+    /// * `block` - Block the bundle targets. It must match the block of the bundle being replaced.
+    /// * `transactions` - Transactions of the bundle in order. An empty bundle cancels the one it replaces.
+    /// * `replacement_uuid` - UUID shared with the bundle being replaced.
+    pub fn new(block: U64, transactions: &[BundleTransaction], replacement_uuid: Uuid) -> Self {
+        let txs = transactions
+            .iter()
+            .map(|transaction| match transaction {
+                BundleTransaction::Signed(transaction) => transaction.rlp(),
+                BundleTransaction::Raw(raw) => raw.clone(),
+            })
+            .collect();
+        Self {
+            txs,
+            block_number: block,
+            replacement_uuid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::prelude::*;
+    use ethers_flashbots::BundleTransaction;
+    use uuid::Uuid;
+
+    use super::ReplaceableBundle;
+
+    #[test]
+    fn test_replacement_uuid_serialized() {
+        let uuid = Uuid::from_u128(0x1234);
+        let bundle = ReplaceableBundle::new(
+            U64::from(17_000_000),
+            &[BundleTransaction::Raw(Bytes::from(vec![0x02, 0x01]))],
+            uuid,
+        );
+
+        let payload = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(payload["txs"][0], "0x0201");
+        assert_eq!(payload["blockNumber"], "0x1036640");
+        assert_eq!(payload["replacementUuid"], uuid.to_string());
+
+        // A cancellation is an empty bundle for the same block and UUID.
+        let cancellation = ReplaceableBundle::new(U64::from(17_000_000), &[], uuid);
+        let payload = serde_json::to_value(&cancellation).unwrap();
+        assert_eq!(payload["txs"], serde_json::json!([]));
+        assert_eq!(payload["replacementUuid"], uuid.to_string());
+    }
+}
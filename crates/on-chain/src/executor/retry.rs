@@ -0,0 +1,193 @@
+#![warn(missing_docs)]
+//! ## Retry
+//!
+//! Retries of provider and relay calls that fail transiently, with exponential backoff.
+//! With the `test-utils` feature, a [`FailureInjector`] makes calls fail on purpose to exercise these paths without a flaky network.
+
+#[cfg(feature = "test-utils")]
+use std::sync::{Arc, Mutex};
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "test-utils")]
+use ethers::core::rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::ArchitectError;
+
+/// How often and how patiently a call is retried.
+/// # Fields
+/// * `max_attempts` - Maximum number of attempts, including the first one. (u32)
+/// * `backoff` - Pause before the first retry. It doubles with every further retry. (Duration)
+/// * `failure_injector` - Makes attempts fail on purpose. Only available with the `test-utils` feature. (Option<Arc<Mutex<FailureInjector>>>)
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Pause before the first retry. It doubles with every further retry.
+    pub backoff: Duration,
+    /// Makes attempts fail on purpose.
+    #[cfg(feature = "test-utils")]
+    pub failure_injector: Option<Arc<Mutex<FailureInjector>>>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(100),
+            #[cfg(feature = "test-utils")]
+            failure_injector: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Public constructor function that instantiates a `RetryPolicy`.
+    /// # Arguments
+    /// * `max_attempts` - Maximum number of attempts, including the first one.
+    /// * `backoff` - Pause before the first retry.
+    pub fn new(max_attempts: u32, backoff: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Makes attempts fail on purpose before they reach the network.
+    /// # Arguments
+    /// * `failure_injector` - Decides which attempts fail.
+    #[cfg(feature = "test-utils")]
+    pub fn with_failure_injector(mut self, failure_injector: FailureInjector) -> Self {
+        self.failure_injector = Some(Arc::new(Mutex::new(failure_injector)));
+        self
+    }
+
+    /// Runs a call and retries it while it fails with a transient error and attempts are left.
+    /// # Arguments
+    /// * `call` - Produces the call to attempt.
+    /// # Returns
+    /// * `Result<T, ArchitectError>` - Result of the first successful attempt or the error of the last one.
+    pub async fn run<T, E, F, Fut>(&self, mut call: F) -> Result<T, ArchitectError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        ArchitectError: From<E>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = match self.inject_failure() {
+                Err(err) => Err(err),
+                Ok(()) => call().await.map_err(ArchitectError::from),
+            };
+            match result {
+                Err(err) if err.is_transient() && attempt < self.max_attempts => {
+                    tokio::time::sleep(self.backoff * 2_u32.pow(attempt - 1)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Fails the upcoming attempt if the failure injector decides so.
+    #[cfg(feature = "test-utils")]
+    fn inject_failure(&self) -> Result<(), ArchitectError> {
+        match &self.failure_injector {
+            Some(failure_injector) => failure_injector.lock().unwrap().maybe_fail(),
+            None => Ok(()),
+        }
+    }
+
+    /// Without the `test-utils` feature no failures are injected.
+    #[cfg(not(feature = "test-utils"))]
+    fn inject_failure(&self) -> Result<(), ArchitectError> {
+        Ok(())
+    }
+}
+
+/// Makes provider and relay calls fail transiently on purpose, driven by a seeded random number generator.
+/// # Fields
+/// * `failure_probability` - Probability that an attempt fails. (f64)
+/// * `max_failures` - Number of failures after which attempts stop failing, `None` for no limit. (Option<u32>)
+/// * `failures` - Number of failures injected so far. (u32)
+#[cfg(feature = "test-utils")]
+#[derive(Debug)]
+pub struct FailureInjector {
+    /// Probability that an attempt fails.
+    pub failure_probability: f64,
+    /// Number of failures after which attempts stop failing, `None` for no limit.
+    pub max_failures: Option<u32>,
+    /// Number of failures injected so far.
+    pub failures: u32,
+    /// Random number generator that decides which attempts fail.
+    rng: StdRng,
+}
+
+#[cfg(feature = "test-utils")]
+impl FailureInjector {
+    /// Public constructor function that instantiates a `FailureInjector`.
+    /// # Arguments
+    /// * `failure_probability` - Probability that an attempt fails, between 0 and 1.
+    /// * `seed` - Seed of the random number generator.
+    pub fn new(failure_probability: f64, seed: u64) -> Self {
+        Self {
+            failure_probability: failure_probability.clamp(0.0, 1.0),
+            max_failures: None,
+            failures: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Stops failing attempts after a number of failures.
+    /// # Arguments
+    /// * `max_failures` - Number of failures to inject at most.
+    pub fn with_max_failures(mut self, max_failures: u32) -> Self {
+        self.max_failures = Some(max_failures);
+        self
+    }
+
+    /// Decides whether the upcoming attempt fails.
+    pub fn maybe_fail(&mut self) -> Result<(), ArchitectError> {
+        if self.max_failures.map_or(false, |max| self.failures >= max) {
+            return Ok(());
+        }
+        if self.rng.gen_bool(self.failure_probability) {
+            self.failures += 1;
+            return Err(ArchitectError::InjectedFailure);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "test-utils"))]
+mod tests {
+    use std::time::Duration;
+
+    use super::{FailureInjector, RetryPolicy};
+    use crate::executor::ArchitectError;
+
+    #[tokio::test]
+    async fn test_retry_recovers_from_injected_failures() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1))
+            .with_failure_injector(FailureInjector::new(1.0, 0).with_max_failures(2));
+
+        let mut calls = 0;
+        let result = policy
+            .run(|| {
+                calls += 1;
+                async { Ok::<_, ArchitectError>(42) }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls, 1);
+        let failure_injector = policy.failure_injector.unwrap();
+        assert_eq!(failure_injector.lock().unwrap().failures, 2);
+
+        // Without enough attempts the injected failure surfaces.
+        let policy = RetryPolicy::new(2, Duration::from_millis(1))
+            .with_failure_injector(FailureInjector::new(1.0, 0).with_max_failures(2));
+        let result = policy.run(|| async { Ok::<_, ArchitectError>(42) }).await;
+        assert!(matches!(result, Err(ArchitectError::InjectedFailure)));
+    }
+}
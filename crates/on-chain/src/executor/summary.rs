@@ -0,0 +1,140 @@
+#![warn(missing_docs)]
+//! ## Summary
+//!
+//! Per-transaction summaries of a simulated bundle, including why reverted transactions failed.
+
+use ethers::{
+    abi::{self, ParamType, Token},
+    prelude::*,
+    utils::hex,
+};
+use ethers_flashbots::{SimulatedBundle, SimulatedTransaction};
+
+/// Selector of the `Error(string)` revert emitted by `require` and `revert("...")`.
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Why a simulated transaction reverted.
+/// # Variants
+/// * `OutOfGas` - The transaction ran out of gas.
+/// * `Require` - The transaction reverted with a reason string, e.g., from a failed `require`.
+/// * `CustomError` - The transaction reverted with a custom error. Holds the raw revert data including the selector.
+/// * `Other` - Any other failure reported by the relay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// The transaction ran out of gas.
+    OutOfGas,
+    /// The transaction reverted with a reason string, e.g., from a failed `require`.
+    Require(String),
+    /// The transaction reverted with a custom error. Holds the raw revert data including the selector.
+    CustomError(Bytes),
+    /// Any other failure reported by the relay.
+    Other(String),
+}
+
+impl RevertReason {
+    /// Classify the `error` and `revert` fields that the relay reports for a simulated transaction.
+    /// # Arguments
+    /// * `error` - The execution error reported by the relay, if any.
+    /// * `revert` - The revert data reported by the relay, if any. Either hex encoded or the reason itself.
+    /// # Returns
+    /// * `Option<RevertReason>` - `None` if the transaction succeeded.
+    pub fn classify(error: Option<&str>, revert: Option<&str>) -> Option<Self> {
+        if let Some(error) = error {
+            if error.to_lowercase().contains("out of gas") {
+                return Some(RevertReason::OutOfGas);
+            }
+        }
+        match revert.filter(|revert| !revert.is_empty()) {
+            Some(revert) => Some(Self::decode_revert(revert)),
+            None => error.map(|error| RevertReason::Other(error.to_string())),
+        }
+    }
+
+    /// Decode the revert data of a transaction.
+    fn decode_revert(revert: &str) -> Self {
+        let data = match hex::decode(revert.trim_start_matches("0x")) {
+            Ok(data) if data.len() >= 4 => data,
+            // The relay already decoded the reason for us.
+            _ => return RevertReason::Require(revert.to_string()),
+        };
+        if data[..4] == ERROR_STRING_SELECTOR {
+            if let Ok(tokens) = abi::decode(&[ParamType::String], &data[4..]) {
+                if let Some(Token::String(reason)) = tokens.into_iter().next() {
+                    return RevertReason::Require(reason);
+                }
+            }
+        }
+        RevertReason::CustomError(Bytes::from(data))
+    }
+}
+
+/// Summary of a single simulated transaction.
+/// # Fields
+/// * `hash` - Hash of the transaction. (H256)
+/// * `gas_used` - Gas used by the transaction. (U256)
+/// * `revert_reason` - Why the transaction reverted, `None` if it succeeded. (Option<RevertReason>)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionSummary {
+    /// Hash of the transaction.
+    pub hash: H256,
+    /// Gas used by the transaction.
+    pub gas_used: U256,
+    /// Why the transaction reverted, `None` if it succeeded.
+    pub revert_reason: Option<RevertReason>,
+}
+
+impl TransactionSummary {
+    /// Whether the transaction reverted.
+    pub fn reverted(&self) -> bool {
+        self.revert_reason.is_some()
+    }
+}
+
+impl From<&SimulatedTransaction> for TransactionSummary {
+    fn from(transaction: &SimulatedTransaction) -> Self {
+        Self {
+            hash: transaction.hash,
+            gas_used: transaction.gas_used,
+            revert_reason: RevertReason::classify(
+                transaction.error.as_deref(),
+                transaction.revert.as_deref(),
+            ),
+        }
+    }
+}
+
+/// Summary of a simulated bundle.
+/// # Fields
+/// * `transactions` - Summaries of the bundle transactions in order. (Vec<TransactionSummary>)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSummary {
+    /// Summaries of the bundle transactions in order.
+    pub transactions: Vec<TransactionSummary>,
+}
+
+impl From<&SimulatedBundle> for BundleSummary {
+    fn from(bundle: &SimulatedBundle) -> Self {
+        Self {
+            transactions: bundle
+                .transactions
+                .iter()
+                .map(TransactionSummary::from)
+                .collect(),
+        }
+    }
+}
+
+/// Estimate the net value of a simulated bundle: what it pays the block's coinbase minus what its transactions pay for gas.
+/// # Arguments
+/// * `sim` - Simulation of the bundle.
+/// # Returns
+/// * `I256` - Net value in wei, negative if the gas costs exceed the coinbase payment.
+pub fn estimate_profit(sim: &SimulatedBundle) -> I256 {
+    sim.transactions
+        .iter()
+        .map(|transaction| {
+            I256::from_raw(transaction.coinbase_diff)
+                - I256::from_raw(transaction.gas_used * transaction.gas_price)
+        })
+        .fold(I256::zero(), |profit, net| profit + net)
+}
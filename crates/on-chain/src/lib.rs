@@ -1,2 +1,2 @@
-// pub mod executor; TODO Work on executor next
+pub mod executor;
 pub mod monitor;
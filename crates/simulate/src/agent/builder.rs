@@ -0,0 +1,198 @@
+#![warn(missing_docs)]
+#![warn(unsafe_code)]
+//! A fluent builder for configuring agents before they are activated.
+
+use revm::primitives::{B160, U256};
+
+use super::{
+    simple_arbitrageur::SimpleArbitrageur, user::User, AgentType, NotActive, SimulationEventFilter,
+};
+
+/// The kind of agent that an [`AgentBuilder`] produces.
+enum AgentKind {
+    /// Produces a [`User`].
+    User,
+    /// Produces a [`SimpleArbitrageur`].
+    SimpleArbitrageur,
+}
+
+/// Builds a ready-to-activate [`AgentType<NotActive>`] by chaining configuration.
+/// # Fields
+/// * `kind` - The kind of agent that is being built.
+/// * `name` - Name of the agent.
+/// * `event_filters` - The filters for the events that the agent is interested in.
+/// * `auto_approvals` - The `(token, spender)` pairs that the agent approves upon activation.
+/// * `gas_limit` - The gas limit of the transactions the agent sends, unlimited if unset.
+/// * `gas_price` - The gas price of the transactions the agent sends, free if unset.
+/// * `priority_fee` - The priority fee per gas of the transactions the agent sends, legacy transactions if unset.
+pub struct AgentBuilder {
+    /// The kind of agent that is being built.
+    kind: AgentKind,
+    /// Name of the agent.
+    name: String,
+    /// The filters for the events that the agent is interested in.
+    event_filters: Vec<SimulationEventFilter>,
+    /// The `(token, spender)` pairs that the agent approves upon activation.
+    auto_approvals: Vec<(B160, B160)>,
+    /// The gas limit of the transactions the agent sends, unlimited if unset.
+    gas_limit: Option<u64>,
+    /// The gas price of the transactions the agent sends, free if unset.
+    gas_price: Option<U256>,
+    /// The priority fee per gas of the transactions the agent sends, legacy transactions if unset.
+    priority_fee: Option<U256>,
+}
+
+impl AgentBuilder {
+    /// Starts building a [`User`] with the given name.
+    pub fn user<S: Into<String>>(name: S) -> Self {
+        Self::new(AgentKind::User, name.into())
+    }
+
+    /// Starts building a [`SimpleArbitrageur`] with the given name.
+    pub fn simple_arbitrageur<S: Into<String>>(name: S) -> Self {
+        Self::new(AgentKind::SimpleArbitrageur, name.into())
+    }
+
+    fn new(kind: AgentKind, name: String) -> Self {
+        Self {
+            kind,
+            name,
+            event_filters: vec![],
+            auto_approvals: vec![],
+            gas_limit: None,
+            gas_price: None,
+            priority_fee: None,
+        }
+    }
+
+    /// Adds a single [`SimulationEventFilter`] to the agent.
+    pub fn event_filter(mut self, event_filter: SimulationEventFilter) -> Self {
+        self.event_filters.push(event_filter);
+        self
+    }
+
+    /// Adds several [`SimulationEventFilter`]s to the agent.
+    pub fn event_filters(mut self, event_filters: Vec<SimulationEventFilter>) -> Self {
+        self.event_filters.extend(event_filters);
+        self
+    }
+
+    /// Has the agent approve `spender` for the maximum amount of `token` upon activation.
+    pub fn auto_approval(mut self, token: B160, spender: B160) -> Self {
+        self.auto_approvals.push((token, spender));
+        self
+    }
+
+    /// Sets the gas limit of the transactions the agent sends.
+    pub fn gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = Some(gas_limit);
+        self
+    }
+
+    /// Sets the gas price of the transactions the agent sends, which is debited from its balance.
+    pub fn gas_price(mut self, gas_price: U256) -> Self {
+        self.gas_price = Some(gas_price);
+        self
+    }
+
+    /// Makes the agent send EIP-1559 transactions with the given priority fee per gas. The gas price becomes the maximum fee per gas.
+    pub fn priority_fee(mut self, priority_fee: U256) -> Self {
+        self.priority_fee = Some(priority_fee);
+        self
+    }
+
+    /// Finishes the configuration and returns the agent ready to be activated by the `SimulationManager`.
+    pub fn build(self) -> AgentType<NotActive> {
+        let mut agent = match self.kind {
+            AgentKind::User => AgentType::User(
+                User::new(self.name, Some(self.event_filters))
+                    .with_auto_approvals(self.auto_approvals),
+            ),
+            AgentKind::SimpleArbitrageur => AgentType::SimpleArbitrageur(
+                SimpleArbitrageur::new(self.name, self.event_filters)
+                    .with_auto_approvals(self.auto_approvals),
+            ),
+        };
+        let transact_settings = match &mut agent {
+            AgentType::User(user) => &mut user.transact_settings,
+            AgentType::SimpleArbitrageur(simple_arbitrageur) => {
+                &mut simple_arbitrageur.transact_settings
+            }
+            AgentType::PriceFeed(price_feed) => &mut price_feed.transact_settings,
+        };
+        if let Some(gas_limit) = self.gas_limit {
+            transact_settings.gas_limit = gas_limit;
+        }
+        if let Some(gas_price) = self.gas_price {
+            transact_settings.gas_price = gas_price;
+        }
+        if let Some(priority_fee) = self.priority_fee {
+            transact_settings.gas_priority_fee = Some(priority_fee);
+        }
+        agent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bindings::{arbiter_token, writer};
+    use revm::primitives::B160;
+
+    use super::AgentBuilder;
+    use crate::{
+        agent::{create_filter, AgentType, Identifiable},
+        contract::SimulationContract,
+        manager::SimulationManager,
+    };
+
+    #[test]
+    fn build_configured_arbitrageur() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+
+        // Deploy a contract to filter on and a token to approve.
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let writer = writer.deploy(
+            &mut manager.environment,
+            manager.agents.get("admin").unwrap(),
+            (),
+        );
+        let arbiter_token = SimulationContract::new(
+            arbiter_token::ARBITERTOKEN_ABI.clone(),
+            arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+        );
+        let arbiter_token = arbiter_token.deploy(
+            &mut manager.environment,
+            manager.agents.get("admin").unwrap(),
+            ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+        );
+
+        // Build and activate a fully configured arbitrageur.
+        let spender = B160::from_low_u64_be(3);
+        let arbitrageur = AgentBuilder::simple_arbitrageur("arbitrageur")
+            .event_filter(create_filter(&writer, "WasWritten"))
+            .event_filters(vec![create_filter(&arbiter_token, "Approval")])
+            .auto_approval(arbiter_token.address, spender)
+            .gas_limit(500_000)
+            .build();
+        manager.activate_agent(arbitrageur, B160::from_low_u64_be(2))?;
+
+        // Check that every setting survived activation.
+        let arbitrageur = match manager.agents.get("arbitrageur").unwrap() {
+            AgentType::SimpleArbitrageur(arbitrageur) => arbitrageur,
+            _ => panic!("Expected a SimpleArbitrageur."),
+        };
+        assert_eq!(arbitrageur.name(), "arbitrageur");
+        assert_eq!(arbitrageur.event_filters.len(), 2);
+        assert_eq!(arbitrageur.event_filters[0].address, writer.address);
+        assert_eq!(arbitrageur.event_filters[1].address, arbiter_token.address);
+        assert_eq!(
+            arbitrageur.auto_approvals,
+            vec![(arbiter_token.address, spender)]
+        );
+        assert_eq!(arbitrageur.transact_settings.gas_limit, 500_000);
+        Ok(())
+    }
+}
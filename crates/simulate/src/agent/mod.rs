@@ -6,7 +6,9 @@
 //! An abstract representation of an agent on the EVM, to be used in simulations.
 //! Some examples of agents are market makers or arbitrageurs.
 //! All agents must implement the [`Agent`] traits and be included in the [`AgentType`] enum.
+//! Agents can be configured directly through their constructors or with the [`builder::AgentBuilder`].
 use std::{
+    any::Any,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
     thread,
@@ -14,15 +16,18 @@ use std::{
 
 use bytes::Bytes;
 use crossbeam_channel::Receiver;
-use ethers::{prelude::BaseContract, types::H256};
+use ethers::{abi::RawLog, contract::EthLogDecode, prelude::BaseContract, types::H256};
 use revm::primitives::{AccountInfo, Address, ExecutionResult, Log, TransactTo, TxEnv, B160, U256};
 
-use self::{simple_arbitrageur::SimpleArbitrageur, user::User};
+use self::{price_feed::PriceFeed, simple_arbitrageur::SimpleArbitrageur, user::User};
 use crate::{
     contract::{IsDeployed, SimulationContract},
-    environment::SimulationEnvironment,
+    environment::{SimulationEnvironment, SimulationReceipt},
+    manager::ManagerError,
 };
 
+pub mod builder;
+pub mod price_feed;
 pub mod simple_arbitrageur;
 pub mod user;
 
@@ -61,7 +66,7 @@ impl AgentStatus for NotActive {
     type Address = ();
     type AccountInfo = ();
     type EventReceiver = ();
-    type TransactSettings = ();
+    type TransactSettings = TransactSettings;
 }
 
 impl AgentStatus for IsActive {
@@ -79,12 +84,14 @@ pub trait Identifiable {
 
 /// An agent is an entity that can interact with the simulation environment.
 /// Agents can be various entities such as users, market makers, arbitrageurs, etc.
-/// The [`User`] and [`SimpleArbitrageur`] agents are currently implemented.
+/// The [`User`], [`SimpleArbitrageur`] and [`PriceFeed`] agents are currently implemented.
 pub enum AgentType<AgentState: AgentStatus> {
     /// A [`User`] is the most basic agent that can interact with the simulation environment.
     User(User<AgentState>),
     /// A [`SimpleArbitrageur`] is an agent that can perform arbitrage between two pools.
     SimpleArbitrageur(SimpleArbitrageur<AgentState>),
+    /// A [`PriceFeed`] is an agent that moves the price of an exchange along a price path.
+    PriceFeed(PriceFeed<AgentState>),
 }
 
 impl AgentType<IsActive> {
@@ -93,6 +100,16 @@ impl AgentType<IsActive> {
         match self {
             AgentType::User(inner) => inner,
             AgentType::SimpleArbitrageur(inner) => inner,
+            AgentType::PriceFeed(inner) => inner,
+        }
+    }
+
+    /// Retrieves the inner agent as `&dyn Any` so that it can be downcast to its concrete type.
+    pub fn as_any(&self) -> &dyn Any {
+        match self {
+            AgentType::User(inner) => inner,
+            AgentType::SimpleArbitrageur(inner) => inner,
+            AgentType::PriceFeed(inner) => inner,
         }
     }
 }
@@ -103,6 +120,7 @@ impl AgentType<NotActive> {
         match self {
             AgentType::User(inner) => inner,
             AgentType::SimpleArbitrageur(inner) => inner,
+            AgentType::PriceFeed(inner) => inner,
         }
     }
 }
@@ -137,12 +155,42 @@ impl Agent for AgentType<IsActive> {
     }
 }
 
+/// The gas limit that priced transactions fall back to when no finite gas limit is set, i.e., the gas limit of a mainnet block.
+pub const PRICED_GAS_LIMIT: u64 = 30_000_000;
+
 /// Describes the gas settings for a transaction.
+/// Agents are configured with them before activation and keep them while active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TransactSettings {
     /// Gas limit for the transaction for a simulation.
     pub gas_limit: u64,
     /// Gas limit for the transaction for a simulation.
     pub gas_price: U256,
+    /// Priority fee per gas of EIP-1559 transactions. With a priority fee the gas price is the maximum fee per gas and the sender pays `min(gas_price, base_fee + priority_fee)`, without one the sender pays the gas price in full.
+    pub gas_priority_fee: Option<U256>,
+}
+
+impl Default for TransactSettings {
+    /// Unconstrained gas that is free of charge.
+    fn default() -> Self {
+        Self {
+            gas_limit: u64::MAX,
+            gas_price: U256::ZERO,
+            gas_priority_fee: None,
+        }
+    }
+}
+
+impl TransactSettings {
+    /// Sets the gas price. A nonzero gas price clamps an unlimited gas limit to [`PRICED_GAS_LIMIT`], since the EVM rejects a sender whose balance cannot cover the gas limit times the gas price.
+    /// # Arguments
+    /// * `gas_price` - The gas price in wei.
+    pub fn set_gas_price(&mut self, gas_price: U256) {
+        self.gas_price = gas_price;
+        if gas_price > U256::ZERO && self.gas_limit == u64::MAX {
+            self.gas_limit = PRICED_GAS_LIMIT;
+        }
+    }
 }
 
 /// Basic traits that every `Agent` must implement in order to properly interact with an EVM.
@@ -157,15 +205,29 @@ pub trait Agent: Identifiable {
     fn event_filters(&self) -> Vec<SimulationEventFilter>;
 
     /// Used to allow agents to make a generic call a specific smart contract.
+    /// Fails with [`ManagerError::Rejected`] if the EVM rejects the call, e.g., because its gas price is below the base fee.
     fn call_contract(
         &self,
         simulation_environment: &mut SimulationEnvironment,
         contract: &SimulationContract<IsDeployed>,
         call_data: Bytes,
         value: U256,
-    ) -> ExecutionResult {
+    ) -> Result<ExecutionResult, ManagerError> {
         let tx = self.build_call_transaction(contract.address, call_data, value);
-        simulation_environment.execute(tx)
+        Ok(simulation_environment.execute(tx)?)
+    }
+
+    /// Sends a transaction from the agent and returns its [`SimulationReceipt`], so the agent can react to the logs its own transaction produced.
+    /// Fails with [`ManagerError::Rejected`] if the EVM rejects the transaction.
+    fn send_transaction(
+        &self,
+        simulation_environment: &mut SimulationEnvironment,
+        to: B160,
+        call_data: Bytes,
+        value: U256,
+    ) -> Result<SimulationReceipt, ManagerError> {
+        let tx = self.build_call_transaction(to, call_data, value);
+        Ok(simulation_environment.execute_with_receipt(tx)?)
     }
 
     /// A constructor to build a `TxEnv` for an agent (uses agent data like `address` and `TransactSettings`).
@@ -179,7 +241,7 @@ pub trait Agent: Identifiable {
             caller: self.address(),
             gas_limit: self.transact_settings().gas_limit,
             gas_price: self.transact_settings().gas_price,
-            gas_priority_fee: None,
+            gas_priority_fee: self.transact_settings().gas_priority_fee,
             transact_to: TransactTo::Call(receiver_address),
             value,
             data: call_data,
@@ -268,9 +330,12 @@ pub fn filter_events(event_filters: Vec<SimulationEventFilter>, logs: Vec<Log>)
 
     for log in logs {
         for event_filter in event_filters.iter() {
-            if event_filter.address == log.address && event_filter.topic == log.topics[0].into()
             // TODO: Needs to not just be log.topics[0]
-            {
+            let topic0_matches = log
+                .topics
+                .first()
+                .map_or(false, |topic| event_filter.topic == (*topic).into());
+            if event_filter.address == log.address && topic0_matches {
                 events.push(log.clone());
                 break;
             }
@@ -280,18 +345,38 @@ pub fn filter_events(event_filters: Vec<SimulationEventFilter>, logs: Vec<Log>)
     events
 }
 
+/// Decodes the logs of one event type, e.g., a `PriceChangeFilter` generated in the `bindings` crate, and skips every other log.
+/// Decoding into the combined event enum of a contract, e.g., `IPortfolioEvents`, keeps every event of the contract.
+/// # Arguments
+/// * `logs` - The logs to decode, e.g., the logs an agent received or [`SimulationManager::log_history`](crate::manager::SimulationManager::log_history).
+/// # Returns
+/// * `Vec<E>` - The decoded events in the order of the logs.
+pub fn decode_events<E: EthLogDecode>(logs: &[Log]) -> Vec<E> {
+    logs.iter()
+        .filter_map(|log| {
+            let raw_log = RawLog {
+                topics: log.topics.iter().map(|topic| H256::from(topic.0)).collect(),
+                data: log.data.to_vec(),
+            };
+            E::decode_log(&raw_log).ok()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::error::Error;
 
-    use bindings::{arbiter_token, writer};
+    use bindings::{arbiter_token, liquid_exchange, writer};
+    use ethers::types::U256;
     use revm::primitives::{ruint::Uint, B160};
 
     use crate::{
         agent::{create_filter, user::User, Agent, AgentType},
         contract::SimulationContract,
         manager::SimulationManager,
+        utils::recast_address,
     };
 
     #[test]
@@ -333,7 +418,7 @@ mod tests {
             &writer,
             call_data,
             Uint::ZERO,
-        );
+        )?;
         // Test that the alice doesn't filter out these logs.
         let unfiltered_events = alice.read_logs()?;
         let filtered_events =
@@ -398,7 +483,7 @@ mod tests {
             &writer,
             call_data,
             Uint::ZERO,
-        );
+        )?;
         // Test that the alice doesn't filter out these logs.
         let unfiltered_events = alice.read_logs()?;
         let filtered_events =
@@ -410,4 +495,108 @@ mod tests {
         assert_eq!(filtered_events, vec![]);
         Ok(())
     }
+
+    #[test]
+    fn decode_events_skips_other_events() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+        let arbiter_token = SimulationContract::new(
+            arbiter_token::ARBITERTOKEN_ABI.clone(),
+            arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+        );
+        let args = ("Token X".to_string(), "TKNX".to_string(), 18_u8);
+        let token_x = arbiter_token.deploy(&mut manager.environment, admin, args);
+        let args = ("Token Y".to_string(), "TKNY".to_string(), 18_u8);
+        let token_y = arbiter_token.deploy(&mut manager.environment, admin, args);
+        let liquid_exchange = SimulationContract::new(
+            liquid_exchange::LIQUIDEXCHANGE_ABI.clone(),
+            liquid_exchange::LIQUIDEXCHANGE_BYTECODE.clone(),
+        )
+        .deploy(
+            &mut manager.environment,
+            admin,
+            (
+                recast_address(token_x.address),
+                recast_address(token_y.address),
+                U256::exp10(18),
+            ),
+        );
+
+        // The mint emits a `Transfer`, which is not a `PriceChange`.
+        let args = (recast_address(liquid_exchange.address), U256::exp10(18));
+        let call_data = token_y.encode_function("mint", args)?;
+        let mint = admin.send_transaction(
+            &mut manager.environment,
+            token_y.address,
+            call_data,
+            Uint::ZERO,
+        )?;
+        let call_data = liquid_exchange.encode_function("setPrice", U256::exp10(18) * 2)?;
+        let set_price = admin.send_transaction(
+            &mut manager.environment,
+            liquid_exchange.address,
+            call_data,
+            Uint::ZERO,
+        )?;
+
+        let logs = [mint.logs, set_price.logs].concat();
+        assert_eq!(logs.len(), 2);
+        let events = super::decode_events::<liquid_exchange::PriceChangeFilter>(&logs);
+        assert_eq!(
+            events,
+            vec![liquid_exchange::PriceChangeFilter {
+                price: U256::exp10(18) * 2
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn decode_events_into_contract_event_enum() {
+        use bindings::i_portfolio::{IPortfolioEvents, SwapFilter};
+        use ethers::{
+            abi::{encode, Token},
+            contract::EthEvent,
+            types::{Address, H256, I256},
+        };
+        use revm::primitives::{Log, B256};
+
+        let swap = SwapFilter {
+            pool_id: 7,
+            price: U256::exp10(18),
+            token_in: Address::from_low_u64_be(0xa),
+            input: U256::from(100),
+            token_out: Address::from_low_u64_be(0xb),
+            output: U256::from(99),
+            fee_amount_dec: U256::from(1),
+            invariant_wad: I256::from(-5_i64),
+        };
+        let topics = [
+            SwapFilter::signature(),
+            H256::from_low_u64_be(swap.pool_id),
+            H256::from(swap.token_in),
+            H256::from(swap.token_out),
+        ];
+        let data = encode(&[
+            Token::Uint(swap.price),
+            Token::Uint(swap.input),
+            Token::Uint(swap.output),
+            Token::Uint(swap.fee_amount_dec),
+            Token::Int(swap.invariant_wad.into_raw()),
+        ]);
+        let swap_log = Log {
+            address: B160::from_low_u64_be(0x9),
+            topics: topics.iter().map(|topic| B256::from(topic.0)).collect(),
+            data: data.into(),
+        };
+        // A log of another contract.
+        let other_log = Log {
+            address: B160::from_low_u64_be(0x9),
+            topics: vec![B256::from(H256::from_low_u64_be(1).0)],
+            data: Default::default(),
+        };
+
+        let events = super::decode_events::<IPortfolioEvents>(&[other_log, swap_log]);
+        assert_eq!(events, vec![IPortfolioEvents::SwapFilter(swap)]);
+    }
 }
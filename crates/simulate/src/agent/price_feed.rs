@@ -0,0 +1,294 @@
+#![warn(missing_docs)]
+#![warn(unsafe_code)]
+//! Describes an agent that drives the price of an exchange along an external price path.
+
+use std::error::Error;
+
+use bindings::liquid_exchange::LIQUIDEXCHANGE_ABI;
+use bytes::Bytes;
+use crossbeam_channel::Receiver;
+use ethers::prelude::BaseContract;
+use revm::primitives::{Address, Log, B160, U256};
+
+use super::{AgentStatus, Identifiable, IsActive, NotActive};
+use crate::{
+    agent::{Agent, SimulationEventFilter, TransactSettings},
+    exchange::MarketImpact,
+    historic::import_price_from_csv,
+    stochastic::price_process::PriceProcess,
+    utils::float_to_wad,
+};
+
+/// A price feed is an agent that pushes the next price of a price path into a `LiquidExchange` every round of `SimulationManager::run_agents`, so arbitrageurs have a moving price to trade against.
+/// The exchange only accepts prices from its admin, so the price feed has to deploy it.
+/// With a [`MarketImpact`], swaps on the exchange pull the next price of the path toward the price they traded at.
+pub struct PriceFeed<AgentState: AgentStatus> {
+    /// Name of the agent.
+    pub name: String,
+    /// Public address of the simulation manager.
+    pub address: AgentState::Address,
+    /// [`revm::primitives`] account of the simulation manager.
+    pub account_info: AgentState::AccountInfo,
+    /// Contains the default transaction options for revm such as gas limit and gas price.
+    pub transact_settings: AgentState::TransactSettings,
+    /// The [`crossbeam_channel::Receiver`] for the events are sent down from [`SimulationEnvironment`]'s dispatch.
+    pub event_receiver: AgentState::EventReceiver,
+    /// The filter for the events that the agent is interested in.
+    pub event_filters: Vec<SimulationEventFilter>,
+    /// The `LiquidExchange` whose price the [`PriceFeed`] sets.
+    pub exchange: B160,
+    /// The price path, one price per round.
+    pub prices: Vec<f64>,
+    /// The index of the next price to push.
+    pub next: usize,
+    /// Couples the path to the exchange, so swaps on the exchange nudge the next price. `None` leaves the path as it is.
+    pub market_impact: Option<MarketImpact>,
+    /// The last price pushed into the exchange, i.e., the price swaps trade at until the next push.
+    pub last_price: Option<f64>,
+}
+
+impl<AgentState: AgentStatus> Identifiable for PriceFeed<AgentState> {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl Agent for PriceFeed<IsActive> {
+    fn address(&self) -> Address {
+        self.address
+    }
+    fn transact_settings(&self) -> &TransactSettings {
+        &self.transact_settings
+    }
+    fn receiver(&self) -> Receiver<Vec<Log>> {
+        self.event_receiver.clone()
+    }
+    fn event_filters(&self) -> Vec<SimulationEventFilter> {
+        self.event_filters.clone()
+    }
+}
+
+impl PriceFeed<NotActive> {
+    /// Creates a new [`PriceFeed`] that pushes the given prices into an exchange, one per round.
+    pub fn new<S: Into<String>>(name: S, exchange: B160, prices: Vec<f64>) -> PriceFeed<NotActive> {
+        PriceFeed::<NotActive> {
+            name: name.into(),
+            address: (),
+            account_info: (),
+            transact_settings: TransactSettings::default(),
+            event_receiver: (),
+            event_filters: vec![],
+            exchange,
+            prices,
+            next: 0,
+            market_impact: None,
+            last_price: None,
+        }
+    }
+
+    /// Creates a new [`PriceFeed`] that follows a sample of a [`PriceProcess`], e.g., a geometric Brownian motion.
+    pub fn from_price_process<S: Into<String>>(
+        name: S,
+        exchange: B160,
+        price_process: &PriceProcess,
+    ) -> PriceFeed<NotActive> {
+        let (_, prices) = price_process.generate_price_path();
+        Self::new(name, exchange, prices)
+    }
+
+    /// Creates a new [`PriceFeed`] that follows the prices of a CSV file with a single column of prices.
+    pub fn from_csv<S: Into<String>>(
+        name: S,
+        exchange: B160,
+        file_path: &str,
+    ) -> Result<PriceFeed<NotActive>, Box<dyn Error>> {
+        Ok(Self::new(name, exchange, import_price_from_csv(file_path)?))
+    }
+
+    /// Sets the gas limit of the transactions the [`PriceFeed`] sends. Unlimited by default.
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.transact_settings.gas_limit = gas_limit;
+        self
+    }
+
+    /// Sets the gas price of the transactions the [`PriceFeed`] sends. Free by default.
+    /// An unlimited gas limit is clamped to [`PRICED_GAS_LIMIT`](crate::agent::PRICED_GAS_LIMIT).
+    pub fn with_gas_price(mut self, gas_price: U256) -> Self {
+        self.transact_settings.set_gas_price(gas_price);
+        self
+    }
+
+    /// Couples the price path to the exchange, so a swap on the exchange nudges the next price toward the price the swap traded at.
+    pub fn with_market_impact(mut self, market_impact: MarketImpact) -> Self {
+        self.market_impact = Some(market_impact);
+        self
+    }
+}
+
+impl PriceFeed<IsActive> {
+    /// Takes the next price of the path and builds the `setPrice` call that pushes it into the exchange.
+    /// With a [`MarketImpact`], the next price is first nudged toward the last pushed price if the exchange saw a swap since the last update.
+    /// Returns `None` once the path is exhausted.
+    pub fn next_price_update(&mut self) -> Option<(B160, Bytes)> {
+        let mut price = *self.prices.get(self.next)?;
+        if let Some(market_impact) = self.market_impact {
+            if let (true, Some(last_price)) = (self.saw_swap(), self.last_price) {
+                price = market_impact.nudge(price, last_price);
+                self.prices[self.next] = price;
+            }
+        }
+        self.next += 1;
+        self.last_price = Some(price);
+        let price: ethers::types::U256 = float_to_wad(price).into();
+        let call_data = BaseContract::from(LIQUIDEXCHANGE_ABI.clone())
+            .encode("setPrice", price)
+            .unwrap(); // The `LiquidExchange` ABI always has `setPrice`.
+        Some((self.exchange, call_data.0))
+    }
+
+    /// Drains the events received since the last call and checks whether any of them is a `Swap` on the exchange.
+    fn saw_swap(&self) -> bool {
+        let swap = LIQUIDEXCHANGE_ABI.event("Swap").unwrap().signature(); // The `LiquidExchange` ABI always has `Swap`.
+        self.event_receiver
+            .try_iter()
+            .flatten()
+            .filter(|log| log.address == self.exchange)
+            .fold(false, |seen, log| {
+                seen || log.topics.first().map(|topic| topic.0) == Some(swap.0)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bindings::{arbiter_token, liquid_exchange};
+    use ethers::prelude::U256;
+    use revm::primitives::B160;
+
+    use super::PriceFeed;
+    use crate::{
+        agent::AgentType, contract::SimulationContract, exchange::MarketImpact,
+        manager::SimulationManager, utils::recast_address,
+    };
+
+    #[test]
+    fn price_feed_pushes_one_price_per_round() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let arbiter_token = SimulationContract::new(
+            arbiter_token::ARBITERTOKEN_ABI.clone(),
+            arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+        );
+        let mut tokens = vec![];
+        for symbol in ["TKNX", "TKNY"] {
+            tokens.push(arbiter_token.deploy(
+                &mut manager.environment,
+                manager.agents.get("admin").unwrap(),
+                (symbol.to_string(), symbol.to_string(), 18_u8),
+            ));
+        }
+
+        // The exchange address is known once the feed deployed it, so the feed starts out pointing nowhere.
+        let price_feed = PriceFeed::new("feed", B160::zero(), vec![1.5, 2.0]);
+        manager.activate_agent(AgentType::PriceFeed(price_feed), B160::from_low_u64_be(2))?;
+        let exchange = SimulationContract::new(
+            liquid_exchange::LIQUIDEXCHANGE_ABI.clone(),
+            liquid_exchange::LIQUIDEXCHANGE_BYTECODE.clone(),
+        )
+        .deploy(
+            &mut manager.environment,
+            manager.agents.get("feed").unwrap(),
+            (
+                recast_address(tokens[0].address),
+                recast_address(tokens[1].address),
+                U256::exp10(18),
+            ),
+        );
+        match manager.agents.get_mut("feed").unwrap() {
+            AgentType::PriceFeed(price_feed) => price_feed.exchange = exchange.address,
+            _ => panic!(),
+        }
+
+        let wad = U256::exp10(18);
+        for expected in [wad * 3 / 2, wad * 2, wad * 2] {
+            manager.run_agents(|_| None)?;
+            let call_data = exchange.encode_function("price", ())?;
+            let price: U256 = manager.call("admin", exchange.address, call_data)?;
+            assert_eq!(price, expected);
+        }
+        // The path is exhausted after two rounds, so the third round is empty.
+        assert!(manager.run_agents(|_| None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn swap_nudges_the_next_price() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let arbiter_token = SimulationContract::new(
+            arbiter_token::ARBITERTOKEN_ABI.clone(),
+            arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+        );
+        let mut tokens = vec![];
+        for symbol in ["TKNX", "TKNY"] {
+            tokens.push(arbiter_token.deploy(
+                &mut manager.environment,
+                manager.agents.get("admin").unwrap(),
+                (symbol.to_string(), symbol.to_string(), 18_u8),
+            ));
+        }
+
+        let price_feed = PriceFeed::new("feed", B160::zero(), vec![1.0, 2.0, 2.0])
+            .with_market_impact(MarketImpact::new(0.5));
+        manager.activate_agent(AgentType::PriceFeed(price_feed), B160::from_low_u64_be(2))?;
+        let exchange = SimulationContract::new(
+            liquid_exchange::LIQUIDEXCHANGE_ABI.clone(),
+            liquid_exchange::LIQUIDEXCHANGE_BYTECODE.clone(),
+        )
+        .deploy(
+            &mut manager.environment,
+            manager.agents.get("feed").unwrap(),
+            (
+                recast_address(tokens[0].address),
+                recast_address(tokens[1].address),
+                U256::exp10(18),
+            ),
+        );
+        match manager.agents.get_mut("feed").unwrap() {
+            AgentType::PriceFeed(price_feed) => price_feed.exchange = exchange.address,
+            _ => panic!(),
+        }
+
+        // The admin holds token X and the exchange holds token Y to pay out.
+        let wad = U256::exp10(18);
+        let zero = revm::primitives::U256::ZERO;
+        let call_data =
+            tokens[0].encode_function("mint", (recast_address(B160::from_low_u64_be(1)), wad))?;
+        manager.call_with_value("admin", tokens[0].address, call_data, zero)?;
+        let call_data =
+            tokens[1].encode_function("mint", (recast_address(exchange.address), U256::MAX))?;
+        manager.call_with_value("admin", tokens[1].address, call_data, zero)?;
+        let call_data =
+            tokens[0].encode_function("approve", (recast_address(exchange.address), U256::MAX))?;
+        manager.call_with_value("admin", tokens[0].address, call_data, zero)?;
+
+        let price = |manager: &mut SimulationManager| -> Result<U256, Box<dyn Error>> {
+            let call_data = exchange.encode_function("price", ())?;
+            Ok(manager.call("admin", exchange.address, call_data)?)
+        };
+        manager.run_agents(|_| None)?;
+        assert_eq!(price(&mut manager)?, wad);
+
+        // A swap at a price of 1 pulls the next price of 2 halfway back.
+        let call_data =
+            exchange.encode_function("swap", (recast_address(tokens[0].address), wad))?;
+        manager.call_with_value("admin", exchange.address, call_data, zero)?;
+        manager.run_agents(|_| None)?;
+        assert_eq!(price(&mut manager)?, wad * 3 / 2);
+
+        // Without another swap the path continues unchanged.
+        manager.run_agents(|_| None)?;
+        assert_eq!(price(&mut manager)?, wad * 2);
+        Ok(())
+    }
+}
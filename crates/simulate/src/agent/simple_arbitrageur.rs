@@ -8,10 +8,51 @@ use std::{
 };
 
 use crossbeam_channel::Receiver;
-use revm::primitives::{Address, Log, U256};
+use revm::primitives::{Address, Log, B160, U256};
 
 use super::{AgentStatus, Identifiable, IsActive, NotActive};
-use crate::agent::{filter_events, Agent, SimulationEventFilter, TransactSettings};
+use crate::{
+    agent::{filter_events, Agent, SimulationEventFilter, TransactSettings},
+    stochastic::noise::NoiseModel,
+};
+
+/// The default amount of gas a [`SimpleArbitrageur`] expects an arbitrage to use.
+pub const DEFAULT_ARBITRAGE_GAS: u64 = 150_000;
+
+/// Computes the break-even spread in basis points, i.e., the spread at which the profit of an arbitrage exactly covers its gas cost.
+/// The `price` is assumed to be quoted in the same 18 decimal numeraire that gas is paid in (e.g. ETH).
+/// # Arguments
+/// * `gas_estimate` - Amount of gas the arbitrage is expected to use. (u64)
+/// * `gas_price` - Gas price paid for the arbitrage in wei. (U256)
+/// * `trade_size` - Size of the arbitrage in units of the traded asset. (f64)
+/// * `price` - Price of the traded asset in units of the numeraire. (f64)
+/// # Returns
+/// * `f64` - The break-even spread in basis points.
+pub fn min_profitable_spread(
+    gas_estimate: u64,
+    gas_price: U256,
+    trade_size: f64,
+    price: f64,
+) -> f64 {
+    let gas_price = u128::try_from(gas_price).unwrap_or(u128::MAX) as f64;
+    let gas_cost = gas_estimate as f64 * gas_price / 1e18;
+    gas_cost / (trade_size * price) * 10_000.0
+}
+
+/// Checks whether the spread between two WAD prices exceeds the break-even spread given by [`min_profitable_spread`].
+fn is_profitable(prices: [U256; 2], gas_estimate: u64, gas_price: U256, trade_size: f64) -> bool {
+    let wad_to_float = |x: U256| u128::try_from(x).unwrap_or(u128::MAX) as f64 / 1e18;
+    let (low, high) = if prices[0] < prices[1] {
+        (wad_to_float(prices[0]), wad_to_float(prices[1]))
+    } else {
+        (wad_to_float(prices[1]), wad_to_float(prices[0]))
+    };
+    if low == 0.0 {
+        return false;
+    }
+    let spread = (high - low) / low * 10_000.0;
+    spread > min_profitable_spread(gas_estimate, gas_price, trade_size, low)
+}
 
 /// A user is an agent that can interact with the simulation environment generically.
 pub struct SimpleArbitrageur<AgentState: AgentStatus> {
@@ -27,8 +68,16 @@ pub struct SimpleArbitrageur<AgentState: AgentStatus> {
     pub event_receiver: AgentState::EventReceiver,
     /// The filter for the events that the agent is interested in.
     pub event_filters: Vec<SimulationEventFilter>,
+    /// The `(token, spender)` pairs that the agent approves for the maximum amount upon activation.
+    pub auto_approvals: Vec<(B160, B160)>,
     /// Storage of prices of the two pools the [`SimpleArbitrageur`] tracks.
     pub prices: Arc<Mutex<[U256; 2]>>,
+    /// The amount of gas the [`SimpleArbitrageur`] expects an arbitrage to use.
+    pub gas_estimate: u64,
+    /// The size of an arbitrage in units of the traded asset.
+    pub trade_size: f64,
+    /// Optional noise on the decisions of the [`SimpleArbitrageur`]. Without it the agent decides deterministically.
+    pub noise: Option<NoiseModel>,
 }
 
 impl<AgentState: AgentStatus> Identifiable for SimpleArbitrageur<AgentState> {
@@ -62,20 +111,82 @@ impl SimpleArbitrageur<NotActive> {
             name: name.into(),
             address: (),
             account_info: (),
-            transact_settings: (),
+            transact_settings: TransactSettings::default(),
             event_receiver: (),
             event_filters,
             prices: Arc::new(Mutex::new([U256::MAX, U256::MAX])), /* Default to MAX value as a placeholder. */
+            gas_estimate: DEFAULT_ARBITRAGE_GAS,
+            trade_size: 1.0,
+            auto_approvals: vec![],
+            noise: None,
         }
     }
+
+    /// Sets the `(token, spender)` pairs that the [`SimpleArbitrageur`] will approve for the maximum amount when it is activated.
+    pub fn with_auto_approvals(mut self, auto_approvals: Vec<(B160, B160)>) -> Self {
+        self.auto_approvals = auto_approvals;
+        self
+    }
+
+    /// Sets the gas limit of the transactions the [`SimpleArbitrageur`] sends. Unlimited by default.
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.transact_settings.gas_limit = gas_limit;
+        self
+    }
+
+    /// Sets the gas price of the transactions the [`SimpleArbitrageur`] sends, which it also weighs against the spread of an arbitrage. Free by default.
+    /// The gas used times the gas price is debited from the agent's balance, so the agent needs a balance that covers its gas limit. An unlimited gas limit is clamped to [`PRICED_GAS_LIMIT`](crate::agent::PRICED_GAS_LIMIT).
+    pub fn with_gas_price(mut self, gas_price: U256) -> Self {
+        self.transact_settings.set_gas_price(gas_price);
+        self
+    }
+
+    /// Sets a [`NoiseModel`] that perturbs the prices the [`SimpleArbitrageur`] perceives and makes it skip some arbitrages.
+    pub fn with_noise(mut self, noise: NoiseModel) -> Self {
+        self.noise = Some(noise);
+        self
+    }
 }
 
 impl SimpleArbitrageur<IsActive> {
+    /// Checks whether the spread between two prices is wide enough to cover the gas cost of an arbitrage.
+    /// Uses the gas price of the [`SimpleArbitrageur`] along with its `gas_estimate` and `trade_size`.
+    pub fn should_arbitrage(&self, prices: [U256; 2]) -> bool {
+        is_profitable(
+            prices,
+            self.gas_estimate,
+            self.transact_settings.gas_price,
+            self.trade_size,
+        )
+    }
+
+    /// Checks whether to arbitrage like [`SimpleArbitrageur::should_arbitrage`] but through the agent's [`NoiseModel`], if it has one.
+    /// The agent may skip the arbitrage outright and otherwise judges noisy perceptions of the prices.
+    pub fn noisy_should_arbitrage(&mut self, prices: [U256; 2]) -> bool {
+        let noise = match self.noise.as_mut() {
+            Some(noise) => noise,
+            None => return self.should_arbitrage(prices),
+        };
+        if noise.skips_action() {
+            return false;
+        }
+        let perceive = |noise: &mut NoiseModel, price: U256| {
+            let price = u128::try_from(price).unwrap_or(u128::MAX) as f64;
+            U256::from(noise.perceive_price(price).max(0.0) as u128)
+        };
+        let perceived_prices = [perceive(noise, prices[0]), perceive(noise, prices[1])];
+        self.should_arbitrage(perceived_prices)
+    }
+
     /// A basic implementation that will detect price discprepencies from events emitted from pools.
+    /// Price discrepancies that do not cover the gas cost of an arbitrage are skipped.
     /// Currently implemented and tested only against the `liquid_exchange`.
     pub fn detect_arbitrage(&self) -> JoinHandle<()> {
         let receiver = self.receiver();
         let event_filters = self.event_filters();
+        let gas_estimate = self.gas_estimate;
+        let gas_price = self.transact_settings.gas_price;
+        let trade_size = self.trade_size;
 
         let prices = Arc::clone(&self.prices);
 
@@ -118,15 +229,16 @@ impl SimpleArbitrageur<IsActive> {
                     // look to see if this gives an arbitrage event
                     // First filter out if one of the prices is MAX as this is the default state.
                     if prices[0] != U256::MAX && prices[1] != U256::MAX {
-                        let price_difference = prices[0].overflowing_sub(prices[1]);
-                        println!("Price difference = {:#?}", price_difference);
-                        if price_difference.1 {
+                        if !is_profitable(*prices, gas_estimate, gas_price, trade_size) {
+                            println!("Spread does not cover the gas cost of an arbitrage.");
+                            continue;
+                        }
+                        if prices[0] < prices[1] {
                             println!("Arbitrage with price_0 < price_1");
-                            break;
-                        } else if price_difference.1 && price_difference.0 != U256::ZERO {
+                        } else {
                             println!("Arbitrage with price_0 > price_1");
-                            break;
                         }
+                        break;
                     }
                 }
             }
@@ -144,11 +256,12 @@ mod tests {
     use ethers::prelude::U256;
     use revm::primitives::B160;
 
-    use super::SimpleArbitrageur;
+    use super::{min_profitable_spread, SimpleArbitrageur};
     use crate::{
         agent::{create_filter, filter_events, Agent, AgentType},
         contract::SimulationContract,
         manager::SimulationManager,
+        stochastic::noise::NoiseModel,
         utils::recast_address,
     };
 
@@ -239,7 +352,7 @@ mod tests {
             &liquid_exchange_xy0,
             call_data,
             U256::zero().into(),
-        );
+        )?;
         // Test that the arbitrageur doesn't filter out these logs.
         let unfiltered_events = arbitrageur.read_logs()?;
         let filtered_events = filter_events(arbitrageur.event_filters(), unfiltered_events.clone());
@@ -257,7 +370,7 @@ mod tests {
             &liquid_exchange_xy1,
             call_data,
             U256::zero().into(),
-        );
+        )?;
         // Test that the arbitrageur doesn't filter out these logs.
         let unfiltered_events = arbitrageur.read_logs()?;
         let filtered_events = filter_events(arbitrageur.event_filters(), unfiltered_events.clone());
@@ -281,7 +394,7 @@ mod tests {
             &token_x,
             call_data,
             U256::zero().into(),
-        );
+        )?;
         // Test that the arbitrageur does filter out these logs.
         let unfiltered_events = arbitrageur.read_logs()?;
         let filtered_events = filter_events(arbitrageur.event_filters(), unfiltered_events.clone());
@@ -395,7 +508,7 @@ mod tests {
             &liquid_exchange_xy0,
             call_data,
             U256::zero().into(),
-        );
+        )?;
 
         // Make a price change to the second exchange.
         let new_price1 = wad.checked_mul(U256::from(69420)).unwrap();
@@ -405,7 +518,7 @@ mod tests {
             &liquid_exchange_xy1,
             call_data,
             U256::zero().into(),
-        );
+        )?;
 
         arbitrage_detection_handle.join().unwrap(); // Block progress until all the events have been recorded
         let prices = Arc::clone(&base_arbitrageur.prices);
@@ -422,4 +535,61 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn gas_aware_arbitrage_threshold() -> Result<(), Box<dyn Error>> {
+        let wad: U256 = U256::from(10_i64.pow(18));
+        let gwei = U256::from(1_000_000_000_u64);
+
+        // The break-even spread widens as the gas price rises.
+        let cheap = min_profitable_spread(150_000, gwei.into(), 1.0, 1000.0);
+        let expensive = min_profitable_spread(150_000, (gwei * 100).into(), 1.0, 1000.0);
+        assert!(expensive > cheap);
+
+        let mut manager = SimulationManager::default();
+        let arbitrageur =
+            AgentType::SimpleArbitrageur(SimpleArbitrageur::new("arbitrageur", vec![]));
+        manager.activate_agent(arbitrageur, B160::from_low_u64_be(2))?;
+        let arbitrageur = match manager.agents.get_mut("arbitrageur").unwrap() {
+            AgentType::SimpleArbitrageur(arbitrageur) => arbitrageur,
+            _ => panic!(),
+        };
+
+        // A marginal 5 bps spread is worth taking at a low gas price.
+        let prices = [(wad * 10_000).into(), (wad * 10_005).into()];
+        arbitrageur.transact_settings.gas_price = gwei.into();
+        assert!(arbitrageur.should_arbitrage(prices));
+
+        // At a high enough gas price the agent skips the marginal arbitrage.
+        arbitrageur.transact_settings.gas_price = (gwei * 100_000).into();
+        assert!(!arbitrageur.should_arbitrage(prices));
+        Ok(())
+    }
+
+    #[test]
+    fn noisy_decisions_are_reproducible() -> Result<(), Box<dyn Error>> {
+        let wad: U256 = U256::from(10_i64.pow(18));
+        let prices = [(wad * 10_000).into(), (wad * 10_005).into()];
+
+        let decisions = |seed: u64| -> Result<Vec<bool>, Box<dyn Error>> {
+            let mut manager = SimulationManager::default();
+            let arbitrageur = SimpleArbitrageur::new("arbitrageur", vec![])
+                .with_noise(NoiseModel::new(0.001, 0.1, seed));
+            manager.activate_agent(
+                AgentType::SimpleArbitrageur(arbitrageur),
+                B160::from_low_u64_be(2),
+            )?;
+            let arbitrageur = match manager.agents.get_mut("arbitrageur").unwrap() {
+                AgentType::SimpleArbitrageur(arbitrageur) => arbitrageur,
+                _ => panic!(),
+            };
+            Ok((0..100)
+                .map(|_| arbitrageur.noisy_should_arbitrage(prices))
+                .collect())
+        };
+
+        assert_eq!(decisions(7)?, decisions(7)?);
+        assert_ne!(decisions(7)?, decisions(8)?);
+        Ok(())
+    }
 }
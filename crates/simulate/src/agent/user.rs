@@ -3,7 +3,7 @@
 //! Describes the most basic type of user agent.
 
 use crossbeam_channel::Receiver;
-use revm::primitives::{Address, Log};
+use revm::primitives::{Address, Log, B160, U256};
 
 use super::{AgentStatus, Identifiable, IsActive, NotActive};
 use crate::agent::{Agent, SimulationEventFilter, TransactSettings};
@@ -22,6 +22,8 @@ pub struct User<AgentState: AgentStatus> {
     pub event_receiver: AgentState::EventReceiver,
     /// The filter for the events that the agent is interested in.
     pub event_filters: Vec<SimulationEventFilter>,
+    /// The `(token, spender)` pairs that the agent approves for the maximum amount upon activation.
+    pub auto_approvals: Vec<(B160, B160)>,
 }
 
 impl<AgentState: AgentStatus> Identifiable for User<AgentState> {
@@ -55,9 +57,35 @@ impl User<NotActive> {
             name: name.into(),
             address: (),
             account_info: (),
-            transact_settings: (),
+            transact_settings: TransactSettings::default(),
             event_receiver: (),
             event_filters: event_filters.unwrap_or_default(),
+            auto_approvals: vec![],
         }
     }
+
+    /// Sets the `(token, spender)` pairs that the [`User`] will approve for the maximum amount when it is activated.
+    pub fn with_auto_approvals(mut self, auto_approvals: Vec<(B160, B160)>) -> Self {
+        self.auto_approvals = auto_approvals;
+        self
+    }
+
+    /// Sets the gas limit of the transactions the [`User`] sends, e.g., to test out-of-gas behavior. Unlimited by default.
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.transact_settings.gas_limit = gas_limit;
+        self
+    }
+
+    /// Sets the gas price of the transactions the [`User`] sends. Free by default.
+    /// The gas used times the gas price is debited from the agent's balance, so the agent needs a balance that covers its gas limit. An unlimited gas limit is clamped to [`PRICED_GAS_LIMIT`](crate::agent::PRICED_GAS_LIMIT).
+    pub fn with_gas_price(mut self, gas_price: U256) -> Self {
+        self.transact_settings.set_gas_price(gas_price);
+        self
+    }
+
+    /// Makes the [`User`] send EIP-1559 transactions that tip the given priority fee per gas on top of the base fee. The gas price becomes the maximum fee per gas.
+    pub fn with_priority_fee(mut self, priority_fee: U256) -> Self {
+        self.transact_settings.gas_priority_fee = Some(priority_fee);
+        self
+    }
 }
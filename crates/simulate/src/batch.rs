@@ -0,0 +1,117 @@
+#![warn(missing_docs)]
+//! Runs several simulations side by side, e.g., to compare them at the same point in time.
+
+use crate::manager::{ManagerError, SimulationManager};
+
+/// What a simulation does after a step.
+/// # Variants
+/// * `Continue` - The simulation keeps running.
+/// * `Terminate` - The simulation is finished and is not stepped again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// The simulation keeps running.
+    Continue,
+    /// The simulation is finished and is not stepped again.
+    Terminate,
+}
+
+/// The work a simulation does within one block.
+pub type Step = Box<dyn FnMut(&mut SimulationManager) -> Result<StepOutcome, ManagerError>>;
+
+/// A simulation managed by a [`BatchRunner`].
+/// # Fields
+/// * `manager` - The manager of the simulation.
+/// * `step` - The work the simulation does within one block.
+/// * `terminated` - Whether the simulation has finished.
+pub struct BatchSimulation {
+    /// The manager of the simulation.
+    pub manager: SimulationManager,
+    /// The work the simulation does within one block.
+    step: Step,
+    /// Whether the simulation has finished.
+    terminated: bool,
+}
+
+impl BatchSimulation {
+    /// Whether the simulation has finished and is no longer stepped.
+    pub fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
+
+/// Runs several simulations side by side.
+/// # Fields
+/// * `simulations` - The managed simulations.
+#[derive(Default)]
+pub struct BatchRunner {
+    /// The managed simulations.
+    pub simulations: Vec<BatchSimulation>,
+}
+
+impl BatchRunner {
+    /// Constructor function for an empty [`BatchRunner`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a simulation to the batch.
+    /// # Arguments
+    /// * `manager` - The manager of the simulation.
+    /// * `step` - The work the simulation does within one block. It runs before the simulation advances to the next block.
+    pub fn add_simulation<F>(&mut self, manager: SimulationManager, step: F)
+    where
+        F: FnMut(&mut SimulationManager) -> Result<StepOutcome, ManagerError> + 'static,
+    {
+        self.simulations.push(BatchSimulation {
+            manager,
+            step: Box::new(step),
+            terminated: false,
+        });
+    }
+
+    /// Steps every simulation until it reaches the target block, so that all of them can be compared at the same point in time.
+    /// A simulation that terminates early stops at the block it terminated in and is skipped from then on.
+    /// Simulations that are already at or past the target block are left as they are.
+    /// # Arguments
+    /// * `block` - The block number to advance the simulations to.
+    pub fn advance_all_to(&mut self, block: u64) -> Result<(), ManagerError> {
+        for simulation in self.simulations.iter_mut() {
+            while !simulation.terminated && simulation.manager.block_number() < block {
+                if (simulation.step)(&mut simulation.manager)? == StepOutcome::Terminate {
+                    simulation.terminated = true;
+                }
+                simulation.manager.advance_block(1);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BatchRunner, StepOutcome};
+    use crate::manager::{ManagerError, SimulationManager};
+
+    #[test]
+    fn advance_all_to_common_block() -> Result<(), ManagerError> {
+        let mut batch_runner = BatchRunner::new();
+        batch_runner.add_simulation(SimulationManager::default(), |_| Ok(StepOutcome::Continue));
+        batch_runner.add_simulation(SimulationManager::default(), |_| Ok(StepOutcome::Continue));
+        // This simulation finishes during block 3.
+        batch_runner.add_simulation(SimulationManager::default(), |manager| {
+            if manager.block_number() == 3 {
+                Ok(StepOutcome::Terminate)
+            } else {
+                Ok(StepOutcome::Continue)
+            }
+        });
+
+        batch_runner.advance_all_to(10)?;
+
+        assert_eq!(batch_runner.simulations[0].manager.block_number(), 10);
+        assert_eq!(batch_runner.simulations[1].manager.block_number(), 10);
+        assert!(batch_runner.simulations[2].is_terminated());
+        assert_eq!(batch_runner.simulations[2].manager.block_number(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+#![warn(missing_docs)]
+//! Audit log of the cheatcodes a simulation used, i.e., every way the simulated state deviated from organic execution.
+
+use bytes::Bytes;
+use revm::primitives::{B160, U256};
+
+/// A cheatcode along with the arguments it was invoked with.
+/// # Variants
+/// * `SetBalance` - The balance of an account was overwritten.
+/// * `SetStorage` - A storage slot of an account was overwritten.
+/// * `SetCode` - The runtime bytecode of an account was overwritten.
+/// * `Impersonate` - A transaction was sent on behalf of an address without its signature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cheatcode {
+    /// The balance of an account was overwritten.
+    SetBalance {
+        /// The address of the account.
+        address: B160,
+        /// The new balance.
+        balance: U256,
+    },
+    /// A storage slot of an account was overwritten.
+    SetStorage {
+        /// The address of the account.
+        address: B160,
+        /// The storage slot.
+        slot: U256,
+        /// The new value of the slot.
+        value: U256,
+    },
+    /// The runtime bytecode of an account was overwritten.
+    SetCode {
+        /// The address of the account.
+        address: B160,
+        /// The new runtime bytecode.
+        code: Bytes,
+    },
+    /// A transaction was sent on behalf of an address without its signature.
+    Impersonate {
+        /// The impersonated address.
+        address: B160,
+        /// The address the transaction was sent to.
+        to: B160,
+        /// The calldata of the transaction.
+        call_data: Bytes,
+        /// The value of the transaction.
+        value: U256,
+    },
+}
+
+/// An entry of the cheatcode audit log.
+/// # Fields
+/// * `block_number` - The block the cheatcode was used in.
+/// * `transactions_executed` - The number of transactions executed before the cheatcode was used, which orders it among them.
+/// * `cheatcode` - The cheatcode and its arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatcodeRecord {
+    /// The block the cheatcode was used in.
+    pub block_number: u64,
+    /// The number of transactions executed before the cheatcode was used, which orders it among them.
+    pub transactions_executed: u64,
+    /// The cheatcode and its arguments.
+    pub cheatcode: Cheatcode,
+}
@@ -0,0 +1,211 @@
+#![warn(missing_docs)]
+#![warn(unsafe_code)]
+//! A Uniswap-V3-style concentrated liquidity pool that quotes swaps across initialized ticks.
+//! Prices are quoted as units of token 1 per unit of token 0.
+
+use std::collections::BTreeMap;
+
+/// Base of the tick spacing, i.e., each tick moves the price by 0.01%.
+const TICK_BASE: f64 = 1.0001;
+
+/// Returns the square root price at a tick.
+/// # Arguments
+/// * `tick` - The tick. (i32)
+/// # Returns
+/// * `f64` - The square root of `1.0001^tick`.
+pub fn sqrt_price_at_tick(tick: i32) -> f64 {
+    TICK_BASE.powf(tick as f64 / 2.0)
+}
+
+/// Returns the tick that contains a square root price.
+/// # Arguments
+/// * `sqrt_price` - The square root price. (f64)
+/// # Returns
+/// * `i32` - The largest tick whose price is at most `sqrt_price^2`.
+pub fn tick_at_sqrt_price(sqrt_price: f64) -> i32 {
+    (2.0 * sqrt_price.ln() / TICK_BASE.ln()).floor() as i32
+}
+
+/// A concentrated liquidity pool where liquidity is only active between the ticks of each position.
+/// # Fields
+/// * `sqrt_price` - Square root of the current price. (f64)
+/// * `tick` - Tick that contains the current price. (i32)
+/// * `liquidity` - Liquidity that is active at the current price. (f64)
+/// * `liquidity_net` - Change of the active liquidity when the price crosses each initialized tick upwards. (BTreeMap<i32, f64>)
+/// * `fee` - Fee taken from the input amount, e.g., `0.003` for 30 bps. (f64)
+#[derive(Debug, Clone)]
+pub struct ConcentratedLiquidityPool {
+    /// Square root of the current price.
+    pub sqrt_price: f64,
+    /// Tick that contains the current price.
+    pub tick: i32,
+    /// Liquidity that is active at the current price.
+    pub liquidity: f64,
+    /// Change of the active liquidity when the price crosses each initialized tick upwards.
+    pub liquidity_net: BTreeMap<i32, f64>,
+    /// Fee taken from the input amount, e.g., `0.003` for 30 bps.
+    pub fee: f64,
+}
+
+impl ConcentratedLiquidityPool {
+    /// Constructor function for an empty pool at a price.
+    /// # Arguments
+    /// * `price` - Initial price of token 0 in units of token 1. (f64)
+    /// * `fee` - Fee taken from the input amount. (f64)
+    pub fn new(price: f64, fee: f64) -> Self {
+        Self {
+            sqrt_price: price.sqrt(),
+            tick: tick_at_sqrt_price(price.sqrt()),
+            liquidity: 0.0,
+            liquidity_net: BTreeMap::new(),
+            fee,
+        }
+    }
+
+    /// Adds a position that provides liquidity between two ticks.
+    /// # Arguments
+    /// * `lower_tick` - Tick where the position starts. (i32)
+    /// * `upper_tick` - Tick where the position ends. (i32)
+    /// * `liquidity` - Liquidity of the position. (f64)
+    pub fn add_liquidity(&mut self, lower_tick: i32, upper_tick: i32, liquidity: f64) {
+        assert!(
+            lower_tick < upper_tick,
+            "The lower tick must be below the upper tick."
+        );
+        *self.liquidity_net.entry(lower_tick).or_insert(0.0) += liquidity;
+        *self.liquidity_net.entry(upper_tick).or_insert(0.0) -= liquidity;
+        if lower_tick <= self.tick && self.tick < upper_tick {
+            self.liquidity += liquidity;
+        }
+    }
+
+    /// Returns the current price of token 0 in units of token 1.
+    pub fn price(&self) -> f64 {
+        self.sqrt_price * self.sqrt_price
+    }
+
+    /// Quotes an exact input swap without changing the pool.
+    /// The swap walks through the initialized ticks and uses the liquidity that is active in each range it crosses.
+    /// # Arguments
+    /// * `zero_for_one` - Whether token 0 is swapped for token 1 (moving the price down) or the other way around.
+    /// * `amount_in` - Amount of the input token. (f64)
+    /// # Returns
+    /// * `f64` - Amount of the output token.
+    pub fn quote_swap(&self, zero_for_one: bool, amount_in: f64) -> f64 {
+        let mut pool = self.clone();
+        pool.swap(zero_for_one, amount_in)
+    }
+
+    /// Executes an exact input swap and moves the pool price.
+    /// # Arguments
+    /// * `zero_for_one` - Whether token 0 is swapped for token 1 (moving the price down) or the other way around.
+    /// * `amount_in` - Amount of the input token. (f64)
+    /// # Returns
+    /// * `f64` - Amount of the output token.
+    pub fn swap(&mut self, zero_for_one: bool, amount_in: f64) -> f64 {
+        let mut remaining = amount_in * (1.0 - self.fee);
+        let mut amount_out = 0.0;
+
+        while remaining > 0.0 {
+            let next_tick = if zero_for_one {
+                self.liquidity_net
+                    .range(..=self.tick)
+                    .next_back()
+                    .map(|(tick, _)| *tick)
+            } else {
+                self.liquidity_net
+                    .range(self.tick + 1..)
+                    .next()
+                    .map(|(tick, _)| *tick)
+            };
+            let target = next_tick.map(sqrt_price_at_tick);
+
+            if self.liquidity > 0.0 {
+                let liquidity = self.liquidity;
+                let sqrt_price = self.sqrt_price;
+                // The input needed to move the price all the way to the next initialized tick.
+                let amount_to_target = target.map(|target| {
+                    if zero_for_one {
+                        liquidity * (1.0 / target - 1.0 / sqrt_price)
+                    } else {
+                        liquidity * (target - sqrt_price)
+                    }
+                });
+                match (target, amount_to_target) {
+                    (Some(target), Some(amount_to_target)) if remaining >= amount_to_target => {
+                        amount_out += if zero_for_one {
+                            liquidity * (sqrt_price - target)
+                        } else {
+                            liquidity * (1.0 / sqrt_price - 1.0 / target)
+                        };
+                        remaining -= amount_to_target;
+                        self.sqrt_price = target;
+                    }
+                    _ => {
+                        // The rest of the swap stays within the current range.
+                        let new_sqrt_price = if zero_for_one {
+                            liquidity * sqrt_price / (liquidity + remaining * sqrt_price)
+                        } else {
+                            sqrt_price + remaining / liquidity
+                        };
+                        amount_out += if zero_for_one {
+                            liquidity * (sqrt_price - new_sqrt_price)
+                        } else {
+                            liquidity * (1.0 / sqrt_price - 1.0 / new_sqrt_price)
+                        };
+                        self.sqrt_price = new_sqrt_price;
+                        self.tick = tick_at_sqrt_price(new_sqrt_price);
+                        break;
+                    }
+                }
+            }
+
+            // Cross the next initialized tick or stop if there is no liquidity left in this direction.
+            match next_tick {
+                Some(tick) => {
+                    self.sqrt_price = sqrt_price_at_tick(tick);
+                    let liquidity_net = self.liquidity_net[&tick];
+                    if zero_for_one {
+                        self.liquidity -= liquidity_net;
+                        self.tick = tick - 1;
+                    } else {
+                        self.liquidity += liquidity_net;
+                        self.tick = tick;
+                    }
+                }
+                None => break,
+            }
+        }
+        amount_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sqrt_price_at_tick, ConcentratedLiquidityPool};
+
+    #[test]
+    fn swap_crosses_tick_boundary() {
+        let mut pool = ConcentratedLiquidityPool::new(1.0, 0.0);
+        pool.add_liquidity(-100, 100, 1_000.0);
+        pool.add_liquidity(-200, 200, 1_000.0);
+        assert_eq!(pool.liquidity, 2_000.0);
+
+        // Move the price up to tick 100 with both positions active and then 2 more units with only the wider one.
+        let sqrt_price_100 = sqrt_price_at_tick(100);
+        let amount_to_tick = 2_000.0 * (sqrt_price_100 - 1.0);
+        let amount_in = amount_to_tick + 2.0;
+        let expected = 2_000.0 * (1.0 - 1.0 / sqrt_price_100)
+            + 1_000.0 * (1.0 / sqrt_price_100 - 1.0 / (sqrt_price_100 + 2.0 / 1_000.0));
+
+        let amount_out = pool.quote_swap(false, amount_in);
+        assert!((amount_out - expected).abs() < 1e-9);
+
+        // Quoting leaves the pool untouched while swapping crosses the tick.
+        assert_eq!(pool.liquidity, 2_000.0);
+        pool.swap(false, amount_in);
+        assert!(pool.tick >= 100 && pool.tick < 200);
+        assert_eq!(pool.liquidity, 1_000.0);
+        assert!(pool.sqrt_price > sqrt_price_100 && pool.sqrt_price < sqrt_price_at_tick(200));
+    }
+}
@@ -13,6 +13,8 @@ use revm::primitives::{ExecutionResult, Output, TransactTo, TxEnv, B160, B256, U
 use crate::{
     agent::{AgentType, IsActive},
     environment::SimulationEnvironment,
+    inspector::CreationTracker,
+    manager::ManagerError,
 };
 
 #[derive(Debug, Clone)]
@@ -84,12 +86,85 @@ impl SimulationContract<NotDeployed> {
     /// * `constructor_arguments` - The constructor arguments for the contract.
     /// # Returns
     /// * `SimulationContract<IsDeployed>` - The deployed contract.
+    /// # Panics
+    /// * If the deployment fails, see [`SimulationContract::try_deploy`].
     pub fn deploy<T: Tokenize>(
         &self,
         simulation_environment: &mut SimulationEnvironment,
         deployer: &AgentType<IsActive>,
         constructor_arguments: T,
     ) -> SimulationContract<IsDeployed> {
+        match self.try_deploy(simulation_environment, deployer, constructor_arguments) {
+            Ok(contract) => contract,
+            Err(err) => panic!("The deployment failed: {}", err),
+        }
+    }
+
+    /// Deploy a contract like [`SimulationContract::deploy`], but return an error instead of panicking if the deployment fails.
+    /// # Arguments
+    /// * `simulation_environment` - The [`SimulationEnvironment`] to deploy the contract to.
+    /// * `deployer` - The [`AgentType`] that will deploy the contract.
+    /// * `constructor_arguments` - The constructor arguments for the contract.
+    /// # Returns
+    /// * `Ok(SimulationContract<IsDeployed>)` - The deployed contract.
+    /// * `Err(ManagerError)` - [`ManagerError::Revert`] or [`ManagerError::Halt`] if the constructor failed, [`ManagerError::Rejected`] if the EVM rejected the deployment, e.g., because the deployer cannot pay for its gas.
+    pub fn try_deploy<T: Tokenize>(
+        &self,
+        simulation_environment: &mut SimulationEnvironment,
+        deployer: &AgentType<IsActive>,
+        constructor_arguments: T,
+    ) -> Result<SimulationContract<IsDeployed>, ManagerError> {
+        let (deploy_txenv, tokenized_args) =
+            self.deploy_transaction(deployer, constructor_arguments);
+        let execution_result = simulation_environment
+            .execute(deploy_txenv)
+            .map_err(|err| {
+                ManagerError::Rejected(format!("The deployment was rejected: {:?}", err))
+            })?;
+        self.deployed(execution_result, tokenized_args)
+    }
+
+    /// Deploy a contract like [`SimulationContract::deploy`] and also return the address of every contract created during the deployment.
+    /// This captures contracts that the constructor itself deploys through `CREATE` or `CREATE2` (e.g., factories).
+    /// # Arguments
+    /// * `simulation_environment` - The [`SimulationEnvironment`] to deploy the contract to.
+    /// * `deployer` - The [`AgentType`] that will deploy the contract.
+    /// * `constructor_arguments` - The constructor arguments for the contract.
+    /// # Returns
+    /// * `Ok((SimulationContract<IsDeployed>, Vec<B160>))` - The deployed contract and the addresses of all created contracts, starting with the deployed contract itself.
+    /// * `Err(ManagerError)` - Why the deployment failed, as for [`SimulationContract::try_deploy`].
+    pub fn deploy_with_created_addresses<T: Tokenize>(
+        &self,
+        simulation_environment: &mut SimulationEnvironment,
+        deployer: &AgentType<IsActive>,
+        constructor_arguments: T,
+    ) -> Result<(SimulationContract<IsDeployed>, Vec<B160>), ManagerError> {
+        let (deploy_txenv, tokenized_args) =
+            self.deploy_transaction(deployer, constructor_arguments);
+        let mut creation_tracker = CreationTracker::default();
+        let execution_result = simulation_environment
+            .execute_with_inspector(deploy_txenv, &mut creation_tracker)
+            .map_err(|err| {
+                ManagerError::Rejected(format!("The deployment was rejected: {:?}", err))
+            })?;
+        let contract = self.deployed(execution_result, tokenized_args)?;
+
+        let mut created = vec![contract.address];
+        created.extend(
+            creation_tracker
+                .created
+                .into_iter()
+                .filter(|address| *address != contract.address),
+        );
+        Ok((contract, created))
+    }
+
+    /// Builds the transaction that deploys the contract along with the tokenized constructor arguments.
+    fn deploy_transaction<T: Tokenize>(
+        &self,
+        deployer: &AgentType<IsActive>,
+        constructor_arguments: T,
+    ) -> (TxEnv, Vec<Token>) {
         // Append constructor args (if available) to generate the deploy bytecode.
         let tokenized_args = constructor_arguments.into_tokens();
         let bytecode = match self.base_contract.abi().constructor.clone() {
@@ -101,7 +176,6 @@ impl SimulationContract<NotDeployed> {
             None => Bytes::from(self.bytecode.clone()),
         };
 
-        // Take the execution result and extract the contract address.
         let deploy_txenv = TxEnv {
             caller: deployer.inner().address(),
             gas_limit: deployer.inner().transact_settings().gas_limit,
@@ -114,23 +188,45 @@ impl SimulationContract<NotDeployed> {
             nonce: None,
             access_list: Vec::new(),
         };
-        let execution_result = simulation_environment.execute(deploy_txenv);
-        let output = match execution_result {
-            ExecutionResult::Success { output, .. } => output,
-            ExecutionResult::Revert { output, .. } => panic!("Failed due to revert: {:?}", output),
-            ExecutionResult::Halt { reason, .. } => panic!("Failed due to halt: {:?}", reason),
-        };
-        let address = match output {
-            Output::Create(_, address) => address.unwrap(),
-            _ => panic!("failed"),
+        (deploy_txenv, tokenized_args)
+    }
+
+    /// Take the execution result of a deployment and extract the contract address.
+    /// # Arguments
+    /// * `execution_result` - The result of the deployment transaction.
+    /// * `tokenized_args` - The constructor arguments of the deployment.
+    /// # Returns
+    /// * `Ok(SimulationContract<IsDeployed>)` - The deployed contract.
+    /// * `Err(ManagerError)` - [`ManagerError::Revert`] or [`ManagerError::Halt`] if the constructor failed.
+    fn deployed(
+        &self,
+        execution_result: ExecutionResult,
+        tokenized_args: Vec<Token>,
+    ) -> Result<SimulationContract<IsDeployed>, ManagerError> {
+        let address = match execution_result {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => address,
+            ExecutionResult::Success { .. } => {
+                return Err(ManagerError::Other(
+                    "The deployment succeeded without creating a contract.".to_string(),
+                ))
+            }
+            ExecutionResult::Revert { output, gas_used } => {
+                return Err(ManagerError::Revert { output, gas_used })
+            }
+            ExecutionResult::Halt { reason, gas_used } => {
+                return Err(ManagerError::Halt { reason, gas_used })
+            }
         };
 
-        SimulationContract {
+        Ok(SimulationContract {
             bytecode: (),
             address,
             base_contract: self.base_contract.clone(),
             constructor_arguments: tokenized_args,
-        }
+        })
     }
 }
 
@@ -186,3 +282,46 @@ impl SimulationContract<IsDeployed> {
             .decode_event(function_name, log_topics, log_data.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::{abi::Contract, utils::get_contract_address};
+
+    use super::SimulationContract;
+    use crate::{manager::SimulationManager, utils::recast_address};
+
+    #[test]
+    fn deploy_captures_created_addresses() {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // A factory whose constructor deploys an empty child contract.
+        let factory = SimulationContract::new(
+            Contract::default(),
+            vec![
+                0x64, 0x60, 0x00, 0x60, 0x00, 0xf3, // PUSH5 child init code
+                0x60, 0x00, // PUSH1 0
+                0x52, // MSTORE
+                0x60, 0x05, // PUSH1 5
+                0x60, 0x1b, // PUSH1 27
+                0x60, 0x00, // PUSH1 0
+                0xf0, // CREATE
+                0x50, // POP
+                0x60, 0x00, // PUSH1 0
+                0x60, 0x00, // PUSH1 0
+                0xf3, // RETURN
+            ]
+            .into(),
+        );
+        let (factory, created) = factory
+            .deploy_with_created_addresses(&mut manager.environment, admin, ())
+            .unwrap();
+
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0], factory.address);
+        assert_eq!(
+            recast_address(created[1]),
+            get_contract_address(recast_address(factory.address), 1)
+        );
+    }
+}
@@ -1,60 +1,637 @@
 #![warn(missing_docs)]
 //! The environment that constitutes a simulation is handled here.
 
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
 use crossbeam_channel::Sender;
 use revm::{
-    db::{CacheDB, EmptyDB},
-    primitives::{ExecutionResult, Log, TxEnv},
-    EVM,
+    db::CacheDB,
+    primitives::{EVMError, ExecutionResult, Log, ResultAndState, SpecId, TxEnv, B160, U256},
+    DatabaseCommit, Inspector, EVM,
+};
+
+use crate::{
+    agent::{filter_events, SimulationEventFilter},
+    fork::{self, ForkDB},
+    trace::TraceTransaction,
 };
 
+/// The number of seconds between two consecutive blocks.
+pub const BLOCK_TIME: u64 = 12;
+
+/// A log emitted in the simulation along with its position within its block.
+/// Logs are numbered in transaction-then-emission order and the numbering restarts with every new block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedLog {
+    /// The number of the block the log was emitted in.
+    pub block_number: U256,
+    /// The index of the transaction that emitted the log within its block.
+    pub transaction_index: u64,
+    /// The index of the log within its block.
+    pub log_index: u64,
+    /// The log itself.
+    pub log: Log,
+}
+
+/// The outcome of a transaction bundled together, so an agent can react to the logs of its own transaction without listening on its event channel.
+/// # Fields
+/// * `success` - Whether the transaction succeeded.
+/// * `gas_used` - The gas used by the transaction.
+/// * `output` - The return data, or the revert data if the transaction reverted. Empty if it halted.
+/// * `logs` - The logs emitted by the transaction. Empty unless it succeeded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationReceipt {
+    /// Whether the transaction succeeded.
+    pub success: bool,
+    /// The gas used by the transaction.
+    pub gas_used: u64,
+    /// The return data, or the revert data if the transaction reverted. Empty if it halted.
+    pub output: Bytes,
+    /// The logs emitted by the transaction. Empty unless it succeeded.
+    pub logs: Vec<Log>,
+}
+
+impl From<ExecutionResult> for SimulationReceipt {
+    fn from(execution_result: ExecutionResult) -> Self {
+        let success = execution_result.is_success();
+        let gas_used = execution_result.gas_used();
+        let logs = execution_result.logs();
+        let output = match execution_result {
+            ExecutionResult::Success { output, .. } => output.into_data(),
+            ExecutionResult::Revert { output, .. } => output,
+            ExecutionResult::Halt { .. } => Bytes::new(),
+        };
+        Self {
+            success,
+            gas_used,
+            output,
+            logs,
+        }
+    }
+}
+
 /// The simulation environment that houses the execution environment and event logs.
+/// The EVM runs with revm's latest spec unless it is pinned to another one. The latest spec includes Cancun, so contracts can use EIP-1153 transient storage (`TSTORE`/`TLOAD`), which revm clears after every transaction.
 /// # Fields
 /// * `evm` - The EVM that is used for the simulation.
-/// * `event_senders` - The senders on the event channel that is used to send events to the agents and simulation manager.
+/// * `event_senders` - The senders on the event channels that are used to send events to the agents along with the agents' event filters, keyed by agent name.
+/// * `log_history` - Every log emitted in the simulation so far.
+/// * `transaction_history` - Every transaction executed in the simulation so far along with its receipt.
+/// * `transaction_index` - The index of the next transaction within the current block.
+/// * `log_index` - The index of the next log within the current block.
+/// * `transactions_executed` - The number of transactions executed so far.
+/// * `gas_executed` - The gas used by all transactions executed so far.
+/// * `execution_time` - The wall-clock time spent executing transactions so far.
+/// * `last_access` - The number of transactions executed when each account was last loaded by a transaction.
 pub struct SimulationEnvironment {
     /// The EVM that is used for the simulation.
-    pub(crate) evm: EVM<CacheDB<EmptyDB>>,
-    /// The sender on the event channel that is used to send events to the agents and simulation manager.
-    pub(crate) event_senders: Vec<Sender<Vec<Log>>>,
+    pub(crate) evm: EVM<CacheDB<ForkDB>>,
+    /// The senders on the event channels that are used to send events to the agents along with the agents' event filters, keyed by agent name.
+    pub(crate) event_senders: HashMap<String, (Sender<Vec<Log>>, Vec<SimulationEventFilter>)>,
+    /// Every log emitted in the simulation so far.
+    pub(crate) log_history: Vec<IndexedLog>,
+    /// Every transaction executed in the simulation so far along with its receipt.
+    pub(crate) transaction_history: Vec<TraceTransaction>,
+    /// The block that the transaction and log indices are counted in.
+    indexed_block: U256,
+    /// The index of the next transaction within the current block.
+    transaction_index: u64,
+    /// The index of the next log within the current block.
+    log_index: u64,
+    /// The number of transactions executed so far.
+    pub(crate) transactions_executed: u64,
+    /// The gas used by all transactions executed so far.
+    pub(crate) gas_executed: u64,
+    /// The wall-clock time spent executing transactions so far.
+    pub(crate) execution_time: Duration,
+    /// The number of transactions executed when each account was last loaded by a transaction.
+    pub(crate) last_access: HashMap<B160, u64>,
 }
 
 impl SimulationEnvironment {
     pub(crate) fn new() -> Self {
+        Self::new_with_spec(SpecId::LATEST)
+    }
+    /// Creates an environment whose EVM is pinned to a specific spec so that its gas schedule does not change along with revm's latest spec.
+    /// # Arguments
+    /// * `spec_id` - The spec (hardfork) that the EVM executes transactions with.
+    pub(crate) fn new_with_spec(spec_id: SpecId) -> Self {
+        Self::new_with_db(spec_id, ForkDB::default())
+    }
+    /// Creates an environment that forks the state of a block from a node, e.g., to run agents against live pools.
+    /// Accounts and storage are fetched from the node as transactions first read them and are cached locally from then on, so local writes never reach the node.
+    /// The environment continues with the block after the forked one.
+    /// # Arguments
+    /// * `rpc_url` - The URL of the node. Forking older blocks requires an archive node.
+    /// * `block` - The number of the block to fork.
+    /// # Returns
+    /// * `Result<SimulationEnvironment, String>` - The environment, or the reason the node could not be reached.
+    pub fn fork(rpc_url: &str, block: u64) -> Result<Self, String> {
+        let (db, fork_block) = ForkDB::new(rpc_url, block)?;
+        let mut environment = Self::new_with_db(SpecId::LATEST, db);
+        environment.evm.env.block = fork::block_env(&fork_block);
+        environment.advance_block(1);
+        environment.indexed_block = environment.evm.env.block.number;
+        Ok(environment)
+    }
+    /// Sets the chain id that contracts read as `block.chainid` and that EIP-155 signed transactions are checked against.
+    /// # Arguments
+    /// * `chain_id` - The chain id, e.g., 1 for mainnet.
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.evm.env.cfg.chain_id = U256::from(chain_id);
+        self
+    }
+    /// Creates an environment on top of a database.
+    /// # Arguments
+    /// * `spec_id` - The spec (hardfork) that the EVM executes transactions with.
+    /// * `fork_db` - The database underneath the cache.
+    fn new_with_db(spec_id: SpecId, fork_db: ForkDB) -> Self {
         let mut evm = EVM::new();
-        let db = CacheDB::new(EmptyDB {});
+        evm.env.cfg.spec_id = spec_id;
+        let db = CacheDB::new(fork_db);
         evm.env.cfg.limit_contract_code_size = Some(0x100000); // This is a large contract size limit, beware!
         evm.database(db);
-        let event_senders = vec![];
-        Self { evm, event_senders }
+        let event_senders = HashMap::new();
+        let indexed_block = evm.env.block.number;
+        Self {
+            evm,
+            event_senders,
+            log_history: vec![],
+            transaction_history: vec![],
+            indexed_block,
+            transaction_index: 0,
+            log_index: 0,
+            transactions_executed: 0,
+            gas_executed: 0,
+            execution_time: Duration::ZERO,
+            last_access: HashMap::new(),
+        }
+    }
+    /// Execute a transaction in the execution environment and bundle its outcome into a [`SimulationReceipt`].
+    /// # Arguments
+    /// * `tx` - The transaction environment that is used to execute the transaction.
+    /// # Returns
+    /// * `Ok(SimulationReceipt)` - The success, gas used, output and logs of the transaction.
+    /// * `Err(EVMError)` - The reason the EVM rejected the transaction.
+    pub(crate) fn execute_with_receipt(
+        &mut self,
+        tx: TxEnv,
+    ) -> Result<SimulationReceipt, EVMError<Infallible>> {
+        Ok(self.execute(tx)?.into())
     }
     /// Execute a transaction in the execution environment.
+    /// The block environment is left untouched so all transactions executed within one block observe the same `block.number` and `block.timestamp`.
+    /// The EVM rejects a transaction instead of executing it, e.g., if it carries a nonce that does not match the sender's account nonce or its gas price is below the base fee.
     /// # Arguments
     /// * `tx` - The transaction environment that is used to execute the transaction.
     /// # Returns
-    /// * `ExecutionResult` - The execution result of the transaction.
-    pub(crate) fn execute(&mut self, tx: TxEnv) -> ExecutionResult {
+    /// * `Ok(ExecutionResult)` - The execution result of the transaction.
+    /// * `Err(EVMError)` - The reason the EVM rejected the transaction.
+    pub(crate) fn execute(&mut self, tx: TxEnv) -> Result<ExecutionResult, EVMError<Infallible>> {
         self.evm.env.tx = tx;
 
-        let execution_result = match self.evm.transact_commit() {
-            Ok(val) => val,
-            // URGENT: change this to a custom error
-            Err(_) => panic!("failed"),
-        };
+        let start = Instant::now();
+        let result_and_state = self.evm.transact()?;
+        let execution_result = self.commit(result_and_state);
+        self.record_throughput(&execution_result, start.elapsed());
+        self.transaction_history
+            .push(TraceTransaction::new(&self.evm.env.tx, &execution_result));
+        self.record_logs(execution_result.logs());
         self.echo_logs(execution_result.logs());
 
-        execution_result
+        Ok(execution_result)
+    }
+    /// Execute a transaction in the execution environment and discard its state changes, e.g., to read a price without bumping the caller's nonce.
+    /// The transaction is not recorded in the history and its logs are not sent to the agents.
+    /// # Arguments
+    /// * `tx` - The transaction environment that is used to execute the transaction.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The execution result of the transaction.
+    /// * `Err(EVMError)` - The reason the EVM rejected the transaction.
+    pub(crate) fn execute_static(
+        &mut self,
+        tx: TxEnv,
+    ) -> Result<ExecutionResult, EVMError<Infallible>> {
+        self.evm.env.tx = tx;
+        // `transact` leaves the database untouched, the changed accounts are only applied by `commit`.
+        Ok(self.evm.transact()?.result)
+    }
+    /// Execute a transaction in the execution environment while an [`Inspector`] observes the EVM, e.g., a [`CallTracer`](crate::inspector::CallTracer) to find out why a transaction reverted.
+    /// Pass the inspector by `&mut` to read what it recorded afterwards.
+    /// # Arguments
+    /// * `tx` - The transaction environment that is used to execute the transaction.
+    /// * `inspector` - The inspector that observes the execution.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The execution result of the transaction.
+    /// * `Err(EVMError)` - The reason the EVM rejected the transaction.
+    pub fn execute_with_inspector<INSP: Inspector<CacheDB<ForkDB>>>(
+        &mut self,
+        tx: TxEnv,
+        inspector: INSP,
+    ) -> Result<ExecutionResult, EVMError<Infallible>> {
+        self.evm.env.tx = tx;
+
+        let start = Instant::now();
+        let result_and_state = self.evm.inspect(inspector)?;
+        let execution_result = self.commit(result_and_state);
+        self.record_throughput(&execution_result, start.elapsed());
+        self.transaction_history
+            .push(TraceTransaction::new(&self.evm.env.tx, &execution_result));
+        self.record_logs(execution_result.logs());
+        self.echo_logs(execution_result.logs());
+
+        Ok(execution_result)
+    }
+    /// Commit the state changes of a transaction to the database and note every account the transaction loaded as accessed.
+    /// # Arguments
+    /// * `result_and_state` - The result of the transaction and the accounts it loaded.
+    fn commit(&mut self, result_and_state: ResultAndState) -> ExecutionResult {
+        let ResultAndState { result, state } = result_and_state;
+        let tick = self.transactions_executed + 1;
+        for address in state.keys() {
+            self.last_access.insert(*address, tick);
+        }
+        self.evm.db().unwrap().commit(state); // The environment always has a database.
+        result
+    }
+    /// Move the environment forward by a number of blocks, incrementing `block.number` by one and `block.timestamp` by [`BLOCK_TIME`] per block.
+    /// # Arguments
+    /// * `blocks` - The number of blocks to move forward.
+    pub fn advance_block(&mut self, blocks: u64) {
+        self.evm.env.block.number += U256::from(blocks);
+        self.evm.env.block.timestamp += U256::from(blocks * BLOCK_TIME);
+    }
+    /// Set the `block.timestamp` that transactions observe from now on, e.g., to jump past a vesting cliff without advancing the block number.
+    /// Later calls to [`SimulationEnvironment::advance_block`] continue from the new timestamp.
+    /// # Arguments
+    /// * `timestamp` - The new timestamp in seconds.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.evm.env.block.timestamp = U256::from(timestamp);
+    }
+    /// Set the `block.basefee` that transactions pay from now on, e.g., to study how a fee spike eats into arbitrage profits.
+    /// Transactions whose gas price is below the base fee are rejected. EIP-1559 transactions pay `min(gas_price, base_fee + priority_fee)` per gas, legacy transactions pay their gas price.
+    /// # Arguments
+    /// * `base_fee` - The base fee per gas in wei.
+    pub fn set_base_fee(&mut self, base_fee: U256) {
+        self.evm.env.block.basefee = base_fee;
+    }
+    /// Add an executed transaction to the throughput counters.
+    /// # Arguments
+    /// * `execution_result` - The execution result of the transaction.
+    /// * `elapsed` - The wall-clock time it took to execute the transaction.
+    fn record_throughput(&mut self, execution_result: &ExecutionResult, elapsed: Duration) {
+        self.transactions_executed += 1;
+        self.gas_executed += execution_result.gas_used();
+        self.execution_time += elapsed;
     }
-    /// Echo the logs to the event channel.
+    /// Record the logs of a transaction in the log history, numbering them within the current block.
+    /// # Arguments
+    /// * `logs` - The logs that are to be recorded.
+    fn record_logs(&mut self, logs: Vec<Log>) {
+        let block_number = self.evm.env.block.number;
+        if block_number != self.indexed_block {
+            self.indexed_block = block_number;
+            self.transaction_index = 0;
+            self.log_index = 0;
+        }
+        for log in logs {
+            self.log_history.push(IndexedLog {
+                block_number,
+                transaction_index: self.transaction_index,
+                log_index: self.log_index,
+                log,
+            });
+            self.log_index += 1;
+        }
+        self.transaction_index += 1;
+    }
+    /// Echo the logs to the event channels, each filtered by the event filters of its agent.
+    /// Every agent receives one batch per transaction, which is empty if none of the logs match its filters.
     /// # Arguments
     /// * `logs` - The logs that are to be echoed.
     fn echo_logs(&mut self, logs: Vec<Log>) {
-        for event_sender in self.event_senders.iter() {
-            event_sender.send(logs.clone()).unwrap();
+        for (event_sender, event_filters) in self.event_senders.values() {
+            event_sender
+                .send(filter_events(event_filters.clone(), logs.clone()))
+                .unwrap();
+        }
+    }
+    /// Register the sender on an agent's event channel.
+    /// # Arguments
+    /// * `name` - The name of the agent.
+    /// * `sender` - The sender on the agent's event channel.
+    /// * `event_filters` - The filters that a log has to match to be sent to the agent. Without filters the agent receives every log.
+    pub(crate) fn add_sender(
+        &mut self,
+        name: String,
+        sender: Sender<Vec<Log>>,
+        event_filters: Vec<SimulationEventFilter>,
+    ) {
+        self.event_senders.insert(name, (sender, event_filters));
+    }
+    /// Unregister the sender on an agent's event channel so that the agent stops receiving events.
+    /// # Arguments
+    /// * `name` - The name of the agent.
+    pub(crate) fn remove_sender(&mut self, name: &str) {
+        self.event_senders.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bindings::writer;
+    use bytes::Bytes;
+    use revm::primitives::{ExecutionResult, SpecId, B160, U256};
+
+    use super::BLOCK_TIME;
+    use crate::{
+        agent::{create_filter, user::User, Agent, AgentType},
+        contract::{NotDeployed, SimulationContract},
+        manager::SimulationManager,
+        tests::runtime_contract,
+    };
+
+    /// Runtime code that writes 1 to transient slot 0 when called with calldata and then returns transient slot 0.
+    fn transient_storage_runtime() -> SimulationContract<NotDeployed> {
+        runtime_contract(&[
+            0x36, // CALLDATASIZE
+            0x60, 0x10, // PUSH1 0x10
+            0x57, // JUMPI
+            0x5b, // JUMPDEST
+            0x60, 0x00, // PUSH1 0
+            0x5c, // TLOAD
+            0x60, 0x00, // PUSH1 0
+            0x52, // MSTORE
+            0x60, 0x20, // PUSH1 32
+            0x60, 0x00, // PUSH1 0
+            0xf3, // RETURN
+            0x5b, // JUMPDEST
+            0x60, 0x01, // PUSH1 1
+            0x60, 0x00, // PUSH1 0
+            0x5d, // TSTORE
+            0x60, 0x04, // PUSH1 0x04
+            0x56, // JUMP
+        ])
+    }
+
+    #[test]
+    fn transient_storage_cleared_between_transactions() -> Result<(), Box<dyn Error>> {
+        for mut manager in [
+            SimulationManager::default(),
+            SimulationManager::with_pinned_gas(SpecId::CANCUN),
+        ] {
+            let admin = manager.agents.get("admin").unwrap();
+            let transient = transient_storage_runtime().deploy(&mut manager.environment, admin, ());
+
+            // The transaction that writes transient storage reads its own write.
+            let execution_result = admin.call_contract(
+                &mut manager.environment,
+                &transient,
+                Bytes::from_static(&[0x01]),
+                U256::ZERO,
+            )?;
+            let value = manager.unpack_execution(execution_result)?;
+            let mut one = vec![0_u8; 32];
+            one[31] = 1;
+            assert_eq!(value, Bytes::from(one));
+
+            // A subsequent transaction finds it cleared.
+            let execution_result = admin.call_contract(
+                &mut manager.environment,
+                &transient,
+                Bytes::new(),
+                U256::ZERO,
+            )?;
+            let value = manager.unpack_execution(execution_result)?;
+            assert_eq!(value, Bytes::from(vec![0_u8; 32]));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn transient_storage_needs_cancun() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::with_pinned_gas(SpecId::SHANGHAI);
+        let admin = manager.agents.get("admin").unwrap();
+        let transient = transient_storage_runtime().deploy(&mut manager.environment, admin, ());
+
+        // Before Cancun, TSTORE is not an opcode.
+        let execution_result = admin.call_contract(
+            &mut manager.environment,
+            &transient,
+            Bytes::from_static(&[0x01]),
+            U256::ZERO,
+        )?;
+        assert!(matches!(execution_result, ExecutionResult::Halt { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn transactions_in_a_block_share_timestamp() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Returns `block.timestamp`.
+        let clock = runtime_contract(&[
+            0x42, // TIMESTAMP
+            0x60, 0x00, // PUSH1 0
+            0x52, // MSTORE
+            0x60, 0x20, // PUSH1 32
+            0x60, 0x00, // PUSH1 0
+            0xf3, // RETURN
+        ]);
+        let clock = clock.deploy(&mut manager.environment, admin, ());
+        let read = |output: Bytes| -> Result<U256, Box<dyn Error>> {
+            Ok(U256::from_be_bytes::<32>(output.as_ref().try_into()?))
+        };
+
+        // Each step executes two transactions and then moves to the next block.
+        let mut steps = Vec::new();
+        manager.run_steps(2, |manager| {
+            let mut outputs = Vec::new();
+            for _ in 0..2 {
+                let admin = manager.agents.get("admin").unwrap();
+                let execution_result = admin.call_contract(
+                    &mut manager.environment,
+                    &clock,
+                    Bytes::new(),
+                    U256::ZERO,
+                )?;
+                outputs.push(manager.unpack_execution(execution_result)?);
+            }
+            steps.push(outputs);
+            manager.advance_block(1);
+            Ok(())
+        })?;
+        let first_block = read(steps[0][0].clone())?;
+        assert_eq!(read(steps[0][1].clone())?, first_block);
+        let second_block = read(steps[1][0].clone())?;
+        assert_eq!(read(steps[1][1].clone())?, second_block);
+        assert_eq!(second_block, first_block + U256::from(BLOCK_TIME));
+
+        // Transactions of a block built from the mempool observe the timestamp of that block.
+        let timestamp = manager.environment.evm.env.block.timestamp;
+        for _ in 0..2 {
+            manager.submit_transaction("admin", clock.address, Bytes::new(), U256::ZERO)?;
         }
-        // self.event_sender.send(logs).unwrap();
+        let execution_results = manager.build_block()?;
+        assert_eq!(execution_results.len(), 2);
+        for execution_result in execution_results {
+            assert_eq!(
+                read(manager.unpack_execution(execution_result)?)?,
+                timestamp
+            );
+        }
+        assert_eq!(
+            manager.environment.evm.env.block.timestamp,
+            timestamp + U256::from(BLOCK_TIME)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn contract_observes_chain_id() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::with_chain_id(31_337);
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Returns `block.chainid`.
+        let chain_id = runtime_contract(&[
+            0x46, // CHAINID
+            0x60, 0x00, // PUSH1 0
+            0x52, // MSTORE
+            0x60, 0x20, // PUSH1 32
+            0x60, 0x00, // PUSH1 0
+            0xf3, // RETURN
+        ]);
+        let chain_id = chain_id.deploy(&mut manager.environment, admin, ());
+
+        let execution_result = admin.call_contract(
+            &mut manager.environment,
+            &chain_id,
+            Bytes::new(),
+            U256::ZERO,
+        )?;
+        let output = manager.unpack_execution(execution_result)?;
+        assert_eq!(
+            U256::from_be_bytes::<32>(output.as_ref().try_into()?),
+            U256::from(31_337)
+        );
+        assert_eq!(manager.chain_id(), 31_337);
+        Ok(())
     }
-    pub(crate) fn add_sender(&mut self, sender: Sender<Vec<Log>>) {
-        self.event_senders.push(sender);
+
+    #[test]
+    fn contract_observes_advanced_block_and_timestamp() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Returns `block.timestamp`.
+        let clock = runtime_contract(&[
+            0x42, // TIMESTAMP
+            0x60, 0x00, // PUSH1 0
+            0x52, // MSTORE
+            0x60, 0x20, // PUSH1 32
+            0x60, 0x00, // PUSH1 0
+            0xf3, // RETURN
+        ]);
+        let clock = clock.deploy(&mut manager.environment, admin, ());
+
+        let block_number = manager.block_number();
+        manager.advance_block(5);
+        assert_eq!(manager.block_number(), block_number + 5);
+
+        manager.set_timestamp(1_700_000_000);
+        let admin = manager.agents.get("admin").unwrap();
+        let execution_result =
+            admin.call_contract(&mut manager.environment, &clock, Bytes::new(), U256::ZERO)?;
+        let timestamp = manager.unpack_execution(execution_result)?;
+        assert_eq!(
+            U256::from_be_bytes::<32>(timestamp.as_ref().try_into()?),
+            U256::from(1_700_000_000)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn receipt_carries_own_logs() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let writer = writer.deploy(&mut manager.environment, admin, ());
+
+        let call_data = writer.encode_function("echoString", "receipt".to_string())?;
+        let receipt = admin.send_transaction(
+            &mut manager.environment,
+            writer.address,
+            call_data,
+            U256::ZERO,
+        )?;
+        assert!(receipt.success);
+        assert!(receipt.gas_used > 0);
+        assert_eq!(receipt.logs.len(), 1);
+        assert_eq!(receipt.logs[0].address, writer.address);
+        let echoed: String = writer.decode_output("echoString", receipt.output)?;
+        assert_eq!(echoed, "receipt");
+        Ok(())
+    }
+
+    #[test]
+    fn log_indices_are_contiguous_within_a_block() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let writer = writer.deploy(&mut manager.environment, admin, ());
+
+        // Two transactions that each emit a log.
+        for message in ["first", "second"] {
+            let call_data = writer.encode_function("echoString", message.to_string())?;
+            admin.call_contract(&mut manager.environment, &writer, call_data, U256::ZERO)?;
+        }
+
+        let logs = manager.log_history();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].log_index, 0);
+        assert_eq!(logs[1].log_index, 1);
+        assert_eq!(logs[1].transaction_index, logs[0].transaction_index + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn agents_only_receive_logs_matching_their_filters() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Two contracts that emit the same event.
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let watched = writer.deploy(&mut manager.environment, admin, ());
+        let unrelated = writer.deploy(&mut manager.environment, admin, ());
+
+        let alice = User::new("alice", Some(vec![create_filter(&watched, "WasWritten")]));
+        manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2))?;
+        let alice = manager.agents.get("alice").unwrap().receiver();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // The unrelated contract's log does not reach alice, who still gets an empty batch for the transaction.
+        let call_data = unrelated.encode_function("echoString", "unrelated".to_string())?;
+        admin.call_contract(&mut manager.environment, &unrelated, call_data, U256::ZERO)?;
+        assert!(alice.recv()?.is_empty());
+
+        let call_data = watched.encode_function("echoString", "watched".to_string())?;
+        admin.call_contract(&mut manager.environment, &watched, call_data, U256::ZERO)?;
+        let logs = alice.recv()?;
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].address, watched.address);
+        Ok(())
     }
 }
@@ -21,6 +21,111 @@ pub trait Cfmm: Exchange {
     fn remove_liquidity(&self, token: &str, amount: f64);
 }
 
+/// Couples an external (off-chain) price feed to an exchange so that large trades propagate their price impact.
+/// Without the coupling, a trade that moves the on-chain price leaves the external price fixed and creates an arbitrage that never closes.
+/// A [`PriceFeed`](crate::agent::price_feed::PriceFeed) applies it with `with_market_impact`.
+/// # Fields
+/// * `elasticity` - Fraction of the gap between the external and the post-trade exchange price that is closed on each update, between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarketImpact {
+    /// Fraction of the gap between the external and the post-trade exchange price that is closed on each update, between 0 and 1.
+    pub elasticity: f64,
+}
+
+impl MarketImpact {
+    /// Constructor function for a market impact coupling. The elasticity is clamped to `[0, 1]`.
+    /// # Arguments
+    /// * `elasticity` - Fraction of the price gap that is closed on each update. Zero leaves the external price fixed, one snaps it to the exchange price.
+    pub fn new(elasticity: f64) -> Self {
+        Self {
+            elasticity: elasticity.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Nudges the external price toward the exchange price after a trade.
+    /// # Arguments
+    /// * `external_price` - The current price of the external feed. (f64)
+    /// * `exchange_price` - The price on the exchange after the trade. (f64)
+    /// # Returns
+    /// * `f64` - The updated external price.
+    pub fn nudge(&self, external_price: f64, exchange_price: f64) -> f64 {
+        external_price + self.elasticity * (exchange_price - external_price)
+    }
+}
+
+/// A time-weighted average price oracle in the style of Uniswap V2, which accumulates the price over time as blocks advance.
+/// The price set during a block holds until the next block, so a price that is only briefly moved within a block barely affects the average.
+/// # Fields
+/// * `price` - The current price, which accrues into the cumulative price until the next block.
+/// * `observations` - Timestamps and the cumulative price (the sum of price times elapsed seconds) at each of them, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwapOracle {
+    /// The current price, which accrues into the cumulative price until the next block.
+    pub price: f64,
+    /// Timestamps and the cumulative price (the sum of price times elapsed seconds) at each of them, oldest first.
+    pub observations: Vec<(u64, f64)>,
+}
+
+impl TwapOracle {
+    /// Constructor function for a TWAP oracle that starts observing at a timestamp.
+    /// # Arguments
+    /// * `price` - The initial price. (f64)
+    /// * `timestamp` - The timestamp of the first observation. (u64)
+    pub fn new(price: f64, timestamp: u64) -> Self {
+        Self {
+            price,
+            observations: vec![(timestamp, 0.0)],
+        }
+    }
+
+    /// Sets the current price. It accrues from the last observation onward.
+    /// # Arguments
+    /// * `price` - The new price. (f64)
+    pub fn update_price(&mut self, price: f64) {
+        self.price = price;
+    }
+
+    /// Accrues the current price up to a timestamp and records an observation there.
+    /// # Arguments
+    /// * `timestamp` - The timestamp of the new block. Timestamps at or before the last observation are ignored. (u64)
+    pub fn accumulate(&mut self, timestamp: u64) {
+        let (last_timestamp, cumulative) = *self.observations.last().unwrap(); // There is always a first observation.
+        if timestamp <= last_timestamp {
+            return;
+        }
+        let cumulative = cumulative + self.price * (timestamp - last_timestamp) as f64;
+        self.observations.push((timestamp, cumulative));
+    }
+
+    /// Returns the time-weighted average price over a period that ends at the last observation.
+    /// # Arguments
+    /// * `period` - Length of the period in seconds. (u64)
+    /// # Returns
+    /// * `Option<f64>` - The average price, or `None` if the period is zero or reaches back before the first observation.
+    pub fn consult(&self, period: u64) -> Option<f64> {
+        let (now, cumulative_now) = *self.observations.last().unwrap(); // There is always a first observation.
+        let start = now.checked_sub(period)?;
+        if period == 0 || start < self.observations[0].0 {
+            return None;
+        }
+        // The price is constant between observations, so the cumulative price is linear in between.
+        let after = self
+            .observations
+            .iter()
+            .position(|(timestamp, _)| *timestamp >= start)?;
+        let (after_timestamp, after_cumulative) = self.observations[after];
+        let cumulative_start = if after_timestamp == start {
+            after_cumulative
+        } else {
+            let (before_timestamp, before_cumulative) = self.observations[after - 1];
+            let segment_price = (after_cumulative - before_cumulative)
+                / (after_timestamp - before_timestamp) as f64;
+            before_cumulative + segment_price * (start - before_timestamp) as f64
+        };
+        Some((cumulative_now - cumulative_start) / period as f64)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::error::Error;
@@ -29,6 +134,7 @@ mod tests {
     use ethers::prelude::U256;
     use revm::primitives::{ruint::Uint, B160};
 
+    use super::MarketImpact;
     use crate::{
         agent::{user::User, Agent, AgentType},
         contract::SimulationContract,
@@ -89,17 +195,17 @@ mod tests {
         let mint_amount = wad.checked_mul(U256::from(20)).unwrap(); // in wei units
         let args = (recast_address(alice.address()), mint_amount);
         let call_data = token_x.encode_function("mint", args)?;
-        admin.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0));
+        admin.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0))?;
 
         // Mint max token_y to the liquid_exchange contract.
         let args = (recast_address(liquid_exchange_xy.address), U256::MAX);
         let call_data = token_y.encode_function("mint", args)?;
-        admin.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0));
+        admin.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0))?;
 
         // Have alice's approval for token_x to be spent by the liquid_exchange.
         let args = (recast_address(liquid_exchange_xy.address), U256::MAX);
         let call_data = token_x.encode_function("approve", args)?;
-        alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0));
+        alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0))?;
 
         // Have alice call the swap function to trade token_x for token_y.
         let swap_amount = mint_amount / 2;
@@ -110,12 +216,12 @@ mod tests {
             &liquid_exchange_xy,
             call_data,
             Uint::from(0),
-        );
+        )?;
 
         // Let alice check they spent the right amount of token_x
         let call_data = token_x.encode_function("balanceOf", recast_address(user_address))?;
         let execution_result =
-            alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0)); // Call the 'balanceOf' function.
+            alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0))?; // Call the 'balanceOf' function.
         let value = manager.unpack_execution(execution_result)?;
         let response: U256 = token_x.decode_output("balanceOf", value)?;
         println!("alice has {} token_x after swap", response);
@@ -124,7 +230,7 @@ mod tests {
         // Let alice check they received the right amount of token_y
         let call_data = token_y.encode_function("balanceOf", recast_address(user_address))?;
         let execution_result =
-            alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0)); // Call the 'balanceOf' function.
+            alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0))?; // Call the 'balanceOf' function.
         let value = manager.unpack_execution(execution_result)?;
         let response: U256 = token_y.decode_output("balanceOf", value)?;
         println!("alice has {} token_y after swap", response);
@@ -185,17 +291,17 @@ mod tests {
         let mint_amount = wad.checked_mul(U256::from(20)).unwrap(); // in wei units
         let args = (recast_address(alice.address()), mint_amount);
         let call_data = token_y.encode_function("mint", args)?;
-        admin.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0));
+        admin.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0))?;
 
         // Mint max token_x to the liquid_exchange contract.
         let args = (recast_address(liquid_exchange_xy.address), U256::MAX);
         let call_data = token_x.encode_function("mint", args)?;
-        admin.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0));
+        admin.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0))?;
 
         // Have alice's approval for token_y to be spent by the liquid_exchange.
         let args = (recast_address(liquid_exchange_xy.address), U256::MAX);
         let call_data = token_y.encode_function("approve", args)?;
-        alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0));
+        alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0))?;
 
         // Have alice call the swap function to trade token_y for token_x.
         let swap_amount = mint_amount / 2;
@@ -206,12 +312,12 @@ mod tests {
             &liquid_exchange_xy,
             call_data,
             Uint::from(0),
-        );
+        )?;
 
         // Let alice check they spent the right amount of token_y
         let call_data = token_y.encode_function("balanceOf", recast_address(user_address))?;
         let execution_result =
-            alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0)); // Call the 'balanceOf' function.
+            alice.call_contract(&mut manager.environment, &token_y, call_data, Uint::from(0))?; // Call the 'balanceOf' function.
         let value = manager.unpack_execution(execution_result)?;
         let response: U256 = token_y.decode_output("balanceOf", value)?;
         println!("alice has {} token_y after swap", response);
@@ -220,7 +326,7 @@ mod tests {
         // Let alice check they received the right amount of token_x
         let call_data = token_x.encode_function("balanceOf", recast_address(user_address))?;
         let execution_result =
-            alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0)); // Call the 'balanceOf' function.
+            alice.call_contract(&mut manager.environment, &token_x, call_data, Uint::from(0))?; // Call the 'balanceOf' function.
         let value = manager.unpack_execution(execution_result)?;
         let response: U256 = token_x.decode_output("balanceOf", value)?;
         println!("alice has {} token_x after swap", response);
@@ -300,7 +406,7 @@ mod tests {
                 &liquid_exchange_xy,
                 call_data,
                 Uint::from(0),
-            );
+            )?;
             // Check that the price is set correctly
             let call_data = liquid_exchange_xy.encode_function("price", ())?;
             let execution_result = admin.call_contract(
@@ -308,7 +414,7 @@ mod tests {
                 &liquid_exchange_xy,
                 call_data,
                 Uint::from(0),
-            );
+            )?;
             let value = manager.unpack_execution(execution_result)?;
             let response: U256 = liquid_exchange_xy.decode_output("price", value)?;
             println!("Price from the exchange: {}", response);
@@ -316,4 +422,26 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn market_impact_moves_external_price() {
+        // A constant product pool with a price of 1000 Y per X.
+        let (reserve_x, reserve_y) = (1_000.0_f64, 1_000_000.0_f64);
+        let external_price = reserve_y / reserve_x;
+
+        // A large trade of 100 X into the pool pushes the pool price down.
+        let reserve_x_after = reserve_x + 100.0;
+        let reserve_y_after = reserve_x * reserve_y / reserve_x_after;
+        let pool_price = reserve_y_after / reserve_x_after;
+
+        let coupled_price = MarketImpact::new(0.5).nudge(external_price, pool_price);
+        assert!(coupled_price < external_price && coupled_price > pool_price);
+        assert!((coupled_price - (external_price + pool_price) / 2.0).abs() < 1e-9);
+
+        // Without elasticity the external price does not move.
+        assert_eq!(
+            MarketImpact::new(0.0).nudge(external_price, pool_price),
+            external_price
+        );
+    }
 }
@@ -0,0 +1,340 @@
+#![warn(missing_docs)]
+//! Fetches the context of a historical block from a node so that one of its transactions can be reproduced locally.
+//! The state at the start of the block is assembled from `prestateTracer` traces, so the node needs to serve the `debug` namespace for that block, e.g., an archive node.
+//! Alternatively, [`ForkDB`] lets the whole simulation run on top of the state of a block, fetching accounts and storage from the node as they are first read.
+
+use std::{collections::BTreeMap, convert::Infallible, sync::Mutex};
+
+use ethers::{
+    providers::{Http, Middleware, Provider},
+    types::{
+        Address, Block, BlockId, BlockNumber, Bytes as EthersBytes, Transaction, H256,
+        U256 as EthersU256,
+    },
+};
+use revm::{
+    primitives::{
+        keccak256, AccountInfo, BlockEnv, Bytecode, CreateScheme, TransactTo, TxEnv, B160, B256,
+        KECCAK_EMPTY, U256,
+    },
+    DatabaseRef,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::runtime::Runtime;
+
+use crate::{
+    state::{recast_ethers_u256, AccountDump, StateDump},
+    utils::recast_address,
+};
+
+/// The database underneath the cache of the simulation.
+/// Without a remote it is empty, like revm's `EmptyDB`. Forked, it reads the state of a block from a node, and the cache on top of it keeps every value once it was fetched.
+/// Reads block on an internal runtime, so a forked simulation must not run inside of another async runtime.
+/// A read that fails on the node panics, since the EVM cannot continue without the value.
+/// The addresses of fetched accounts are recorded, so that the manager can drop clean ones from the cache and let them be fetched again later.
+#[derive(Debug, Default)]
+pub struct ForkDB {
+    /// The node and block that state is read from, `None` for an empty database.
+    remote: Option<Remote>,
+    /// The accounts fetched from the node since they were last taken with [`ForkDB::take_fetched`].
+    fetched: Mutex<Vec<B160>>,
+}
+
+/// The node and block a [`ForkDB`] reads state from.
+#[derive(Debug)]
+struct Remote {
+    /// The node to read state from.
+    provider: Provider<Http>,
+    /// The block whose state is read.
+    block: BlockId,
+    /// The runtime that drives the requests to the node.
+    runtime: Runtime,
+}
+
+impl ForkDB {
+    /// Creates a database that reads the state of a block from a node.
+    /// # Arguments
+    /// * `rpc_url` - The URL of the node. Reading the state of older blocks requires an archive node.
+    /// * `block` - The number of the block whose state is read, i.e., the state after the block.
+    /// # Returns
+    /// * `Result<(ForkDB, Block<Transaction>), String>` - The database along with the block, or the reason the node could not be reached.
+    pub(crate) fn new(rpc_url: &str, block: u64) -> Result<(Self, Block<Transaction>), String> {
+        let provider = Provider::<Http>::try_from(rpc_url).map_err(|err| err.to_string())?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|err| err.to_string())?;
+        let fork_block = runtime
+            .block_on(provider.get_block_with_txs(block))
+            .map_err(|err| err.to_string())?
+            .ok_or_else(|| format!("The node does not know block {}.", block))?;
+        let remote = Remote {
+            provider,
+            block: BlockId::from(block),
+            runtime,
+        };
+        Ok((
+            Self {
+                remote: Some(remote),
+                fetched: Mutex::default(),
+            },
+            fork_block,
+        ))
+    }
+
+    /// Whether the database reads state from a node, so an account dropped from the cache above it is fetched again when it is read.
+    pub(crate) fn is_forked(&self) -> bool {
+        self.remote.is_some()
+    }
+
+    /// Takes the addresses of the accounts fetched from the node since the last call.
+    pub(crate) fn take_fetched(&self) -> Vec<B160> {
+        std::mem::take(&mut *self.fetched.lock().unwrap())
+    }
+
+    /// Notes that an account was fetched from the node.
+    /// # Arguments
+    /// * `address` - The address of the account.
+    pub(crate) fn record_fetched(&self, address: B160) {
+        self.fetched.lock().unwrap().push(address);
+    }
+
+    /// Creates a forked database whose node is never reached, for tests that only read accounts already in the cache above it.
+    #[cfg(test)]
+    pub(crate) fn unreachable() -> Self {
+        let remote = Remote {
+            provider: Provider::<Http>::try_from("http://127.0.0.1:1").unwrap(),
+            block: BlockId::from(0),
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap(),
+        };
+        Self {
+            remote: Some(remote),
+            fetched: Mutex::default(),
+        }
+    }
+}
+
+impl DatabaseRef for ForkDB {
+    type Error = Infallible;
+
+    fn basic(&self, address: B160) -> Result<Option<AccountInfo>, Self::Error> {
+        let remote = match &self.remote {
+            Some(remote) => remote,
+            None => return Ok(None),
+        };
+        self.record_fetched(address);
+        let address = recast_address(address);
+        let (balance, nonce, code) = remote
+            .runtime
+            .block_on(async {
+                tokio::try_join!(
+                    remote.provider.get_balance(address, Some(remote.block)),
+                    remote
+                        .provider
+                        .get_transaction_count(address, Some(remote.block)),
+                    remote.provider.get_code(address, Some(remote.block)),
+                )
+            })
+            .unwrap_or_else(|err| panic!("Failed to fetch account {:?}: {}", address, err));
+        let (code_hash, code) = if code.0.is_empty() {
+            (KECCAK_EMPTY, None)
+        } else {
+            (keccak256(&code.0), Some(Bytecode::new_raw(code.0)))
+        };
+        Ok(Some(AccountInfo {
+            balance: recast_ethers_u256(balance),
+            nonce: nonce.as_u64(),
+            code_hash,
+            code,
+        }))
+    }
+
+    fn code_by_hash(&self, _code_hash: B256) -> Result<Bytecode, Self::Error> {
+        // The code of every fetched account is cached along with it, so it is never looked up by hash.
+        Ok(Bytecode::new())
+    }
+
+    fn storage(&self, address: B160, index: U256) -> Result<U256, Self::Error> {
+        let remote = match &self.remote {
+            Some(remote) => remote,
+            None => return Ok(U256::ZERO),
+        };
+        let address = recast_address(address);
+        let slot = H256::from(index.to_be_bytes::<32>());
+        let value = remote
+            .runtime
+            .block_on(
+                remote
+                    .provider
+                    .get_storage_at(address, slot, Some(remote.block)),
+            )
+            .unwrap_or_else(|err| {
+                panic!("Failed to fetch slot {:?} of {:?}: {}", slot, address, err)
+            });
+        Ok(U256::from_be_bytes(value.0))
+    }
+
+    fn block_hash(&self, number: U256) -> Result<B256, Self::Error> {
+        let remote = match &self.remote {
+            Some(remote) => remote,
+            // The same placeholder hash as revm's `EmptyDB`.
+            None => return Ok(keccak256(&number.to_be_bytes::<32>())),
+        };
+        let number = u64::try_from(number).unwrap_or(u64::MAX);
+        let block = remote
+            .runtime
+            .block_on(remote.provider.get_block(number))
+            .unwrap_or_else(|err| panic!("Failed to fetch block {}: {}", number, err));
+        Ok(block
+            .and_then(|block| block.hash)
+            .map(|hash| B256::from(hash.0))
+            .unwrap_or_default())
+    }
+}
+
+/// An account as the `prestateTracer` reports it.
+#[derive(Debug, Deserialize)]
+struct PrestateAccount {
+    #[serde(default)]
+    balance: EthersU256,
+    #[serde(default)]
+    nonce: u64,
+    #[serde(default)]
+    code: EthersBytes,
+    #[serde(default)]
+    storage: BTreeMap<H256, H256>,
+}
+
+/// Assembles the state at the start of a block for every account and storage slot that some transactions touch.
+/// The prestate of each transaction is fetched in block order and the first value seen for an account or slot wins, since later transactions may observe values that earlier ones changed.
+/// # Arguments
+/// * `provider` - The node to fetch the traces from.
+/// * `block` - The number of the block.
+/// * `preceding` - The transactions of the block that run before the target, in block order.
+/// * `target` - The transaction to reproduce. It is traced as a call against the state before the block.
+/// # Returns
+/// * `Result<StateDump, String>` - The state at the start of the block or the reason it could not be fetched.
+pub(crate) async fn block_start_state<M: Middleware>(
+    provider: &M,
+    block: u64,
+    preceding: &[Transaction],
+    target: &Transaction,
+) -> Result<StateDump, String> {
+    let tracer = json!({ "tracer": "prestateTracer" });
+    let mut prestates = Vec::with_capacity(preceding.len() + 1);
+    for transaction in preceding {
+        let prestate: BTreeMap<Address, PrestateAccount> = provider
+            .provider()
+            .request("debug_traceTransaction", (transaction.hash, &tracer))
+            .await
+            .map_err(|err| err.to_string())?;
+        prestates.push(prestate);
+    }
+    let call = json!({
+        "from": target.from,
+        "to": target.to,
+        "gas": target.gas,
+        "value": target.value,
+        "data": target.input,
+    });
+    let parent = BlockNumber::Number(block.saturating_sub(1).into());
+    let prestate: BTreeMap<Address, PrestateAccount> = provider
+        .provider()
+        .request("debug_traceCall", (call, parent, &tracer))
+        .await
+        .map_err(|err| err.to_string())?;
+    prestates.push(prestate);
+
+    let mut state = StateDump::default();
+    for prestate in prestates {
+        for (address, account) in prestate {
+            let storage = account.storage.iter().map(|(slot, value)| {
+                (
+                    EthersU256::from_big_endian(slot.as_bytes()),
+                    EthersU256::from_big_endian(value.as_bytes()),
+                )
+            });
+            match state.accounts.get_mut(&address) {
+                Some(known) => {
+                    for (slot, value) in storage {
+                        known.storage.entry(slot).or_insert(value);
+                    }
+                }
+                None => {
+                    state.accounts.insert(
+                        address,
+                        AccountDump {
+                            balance: account.balance,
+                            nonce: account.nonce,
+                            code: account.code.clone(),
+                            storage: storage.collect(),
+                        },
+                    );
+                }
+            }
+        }
+    }
+    Ok(state)
+}
+
+/// Builds the block environment of a historical block.
+/// # Arguments
+/// * `block` - The block fetched from the node.
+pub(crate) fn block_env(block: &Block<Transaction>) -> BlockEnv {
+    BlockEnv {
+        number: U256::from(block.number.unwrap_or_default().as_u64()),
+        coinbase: B160::from(block.author.unwrap_or_default().0),
+        timestamp: recast_ethers_u256(block.timestamp),
+        difficulty: recast_ethers_u256(block.difficulty),
+        prevrandao: block.mix_hash.map(|mix_hash| B256::from(mix_hash.0)),
+        basefee: recast_ethers_u256(block.base_fee_per_gas.unwrap_or_default()),
+        gas_limit: recast_ethers_u256(block.gas_limit),
+    }
+}
+
+/// Builds the transaction environment of a historical transaction.
+/// The chain id is not checked, so transactions of any chain can be reproduced.
+/// # Arguments
+/// * `transaction` - The signed transaction with its sender recovered.
+pub(crate) fn tx_env(transaction: &Transaction) -> TxEnv {
+    let (gas_price, gas_priority_fee) = match transaction.max_fee_per_gas {
+        Some(max_fee_per_gas) => (max_fee_per_gas, transaction.max_priority_fee_per_gas),
+        None => (transaction.gas_price.unwrap_or_default(), None),
+    };
+    TxEnv {
+        caller: B160::from(transaction.from.0),
+        gas_limit: transaction.gas.as_u64(),
+        gas_price: recast_ethers_u256(gas_price),
+        gas_priority_fee: gas_priority_fee.map(recast_ethers_u256),
+        transact_to: match transaction.to {
+            Some(to) => TransactTo::Call(B160::from(to.0)),
+            None => TransactTo::Create(CreateScheme::Create),
+        },
+        value: recast_ethers_u256(transaction.value),
+        data: transaction.input.0.clone(),
+        chain_id: None,
+        nonce: Some(transaction.nonce.as_u64()),
+        access_list: transaction
+            .access_list
+            .as_ref()
+            .map(|access_list| {
+                access_list
+                    .0
+                    .iter()
+                    .map(|item| {
+                        (
+                            B160::from(item.address.0),
+                            item.storage_keys
+                                .iter()
+                                .map(|key| U256::from_be_bytes(key.0))
+                                .collect(),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
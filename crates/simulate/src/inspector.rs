@@ -0,0 +1,515 @@
+#![warn(missing_docs)]
+//! Inspectors that observe the EVM while it executes a transaction in the [`SimulationEnvironment`](crate::environment::SimulationEnvironment).
+
+use std::collections::HashMap;
+
+use bytes::Bytes;
+use ethers::abi::{self, ParamType, Token};
+use revm::{
+    interpreter::{opcode, CallInputs, CreateInputs, Gas, InstructionResult, Interpreter},
+    primitives::{B160, U256},
+    Database, EVMData, Inspector,
+};
+
+/// Selector of the `Error(string)` revert emitted by `require` and `revert("...")`.
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Highest address of the precompiles up to Shanghai, i.e., `ecrecover` (0x01) through `blake2f` (0x09).
+const LAST_PRECOMPILE: u8 = 0x09;
+
+/// Records the address of every contract created during a transaction, including contracts created by other contracts through `CREATE` or `CREATE2`.
+/// # Fields
+/// * `created` - Addresses of the created contracts in the order their creation finished.
+#[derive(Debug, Default)]
+pub struct CreationTracker {
+    /// Addresses of the created contracts in the order their creation finished.
+    pub created: Vec<B160>,
+}
+
+impl<DB: Database> Inspector<DB> for CreationTracker {
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<B160>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<B160>, Gas, Bytes) {
+        if let (InstructionResult::Return | InstructionResult::Stop, Some(address)) = (ret, address)
+        {
+            self.created.push(address);
+        }
+        (ret, address, remaining_gas, out)
+    }
+}
+
+/// The data a call returned, captured by a [`ReturnDataTracker`].
+/// # Fields
+/// * `depth` - Depth of the call, where the transaction's own call is at depth 0.
+/// * `address` - Address of the contract that was called.
+/// * `success` - Whether the call succeeded.
+/// * `output` - The return data, or the revert data if the call reverted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReturnData {
+    /// Depth of the call, where the transaction's own call is at depth 0.
+    pub depth: u64,
+    /// Address of the contract that was called.
+    pub address: B160,
+    /// Whether the call succeeded.
+    pub success: bool,
+    /// The return data, or the revert data if the call reverted.
+    pub output: Bytes,
+}
+
+impl ReturnData {
+    /// Decodes the reason string of a call that reverted with `Error(string)`.
+    pub fn revert_reason(&self) -> Option<String> {
+        if self.success {
+            return None;
+        }
+        decode_revert_reason(&self.output)
+    }
+}
+
+/// Decodes the reason string of revert data that is ABI-encoded as `Error(string)`, as emitted by `require` and `revert("...")`.
+/// # Arguments
+/// * `output` - The revert data.
+/// # Returns
+/// * `Option<String>` - The reason, or `None` if the revert data is not an `Error(string)`.
+pub fn decode_revert_reason(output: &[u8]) -> Option<String> {
+    if output.len() < 4 || output[..4] != ERROR_STRING_SELECTOR {
+        return None;
+    }
+    match abi::decode(&[ParamType::String], &output[4..]) {
+        Ok(tokens) => match tokens.into_iter().next() {
+            Some(Token::String(reason)) => Some(reason),
+            _ => None,
+        },
+        Err(_) => None,
+    }
+}
+
+/// Records the return data of every call in a transaction, so the revert of an inner call is not lost when an outer call masks it.
+/// # Fields
+/// * `calls` - The return data of each call in the order the calls finished, i.e., inner calls before the calls that made them.
+#[derive(Debug, Default)]
+pub struct ReturnDataTracker {
+    /// The return data of each call in the order the calls finished, i.e., inner calls before the calls that made them.
+    pub calls: Vec<ReturnData>,
+}
+
+impl ReturnDataTracker {
+    /// Returns the reason string of the innermost call that reverted with `Error(string)`.
+    pub fn innermost_revert_reason(&self) -> Option<String> {
+        self.calls.iter().find_map(ReturnData::revert_reason)
+    }
+}
+
+impl<DB: Database> Inspector<DB> for ReturnDataTracker {
+    fn call_end(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+        _is_static: bool,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.calls.push(ReturnData {
+            depth: data.journaled_state.depth(),
+            address: inputs.contract,
+            success: matches!(
+                ret,
+                InstructionResult::Return
+                    | InstructionResult::Stop
+                    | InstructionResult::SelfDestruct
+            ),
+            output: out.clone(),
+        });
+        (ret, remaining_gas, out)
+    }
+}
+
+/// A call frame captured by a [`CallTracer`].
+/// # Fields
+/// * `depth` - Depth of the call, where the transaction's own call is at depth 0.
+/// * `caller` - Address that made the call.
+/// * `address` - Address of the contract that was called.
+/// * `input` - The calldata of the call.
+/// * `gas_limit` - Gas the call was given.
+/// * `gas_used` - Gas the call used, including the calls it made.
+/// * `result` - How the call ended, e.g., `Return`, `Revert` or `OutOfGas`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallFrame {
+    /// Depth of the call, where the transaction's own call is at depth 0.
+    pub depth: u64,
+    /// Address that made the call.
+    pub caller: B160,
+    /// Address of the contract that was called.
+    pub address: B160,
+    /// The calldata of the call.
+    pub input: Bytes,
+    /// Gas the call was given.
+    pub gas_limit: u64,
+    /// Gas the call used, including the calls it made.
+    pub gas_used: u64,
+    /// How the call ended, e.g., `Return`, `Revert` or `OutOfGas`.
+    pub result: InstructionResult,
+}
+
+/// A storage read or write captured by a [`CallTracer`].
+/// # Fields
+/// * `address` - Address of the contract whose storage was accessed.
+/// * `slot` - The storage slot.
+/// * `value` - The value read by `SLOAD` or written by `SSTORE`.
+/// * `write` - Whether the access was an `SSTORE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageAccess {
+    /// Address of the contract whose storage was accessed.
+    pub address: B160,
+    /// The storage slot.
+    pub slot: U256,
+    /// The value read by `SLOAD` or written by `SSTORE`.
+    pub value: U256,
+    /// Whether the access was an `SSTORE`.
+    pub write: bool,
+}
+
+/// Traces the call frames of a transaction along with their gas and every `SLOAD` and `SSTORE`, e.g., to find out which call of an agent's transaction reverted and what state it saw.
+/// # Fields
+/// * `calls` - The call frames in the order the calls started, i.e., callers before the calls they made.
+/// * `storage` - The storage accesses in execution order.
+/// * `open_calls` - Indices into `calls` of the calls that have not ended yet.
+/// * `pending_load` - The `SLOAD` whose value is read once the instruction has run.
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    /// The call frames in the order the calls started, i.e., callers before the calls they made.
+    pub calls: Vec<CallFrame>,
+    /// The storage accesses in execution order.
+    pub storage: Vec<StorageAccess>,
+    /// Indices into `calls` of the calls that have not ended yet.
+    open_calls: Vec<usize>,
+    /// The `SLOAD` whose value is read once the instruction has run.
+    pending_load: Option<(B160, U256)>,
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn step(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+    ) -> InstructionResult {
+        let address = interp.contract.address;
+        match interp.current_opcode() {
+            opcode::SLOAD => {
+                if let Ok(slot) = interp.stack.peek(0) {
+                    self.pending_load = Some((address, slot));
+                }
+            }
+            opcode::SSTORE => {
+                if let (Ok(slot), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                    self.storage.push(StorageAccess {
+                        address,
+                        slot,
+                        value,
+                        write: true,
+                    });
+                }
+            }
+            _ => {}
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        _data: &mut EVMData<'_, DB>,
+        _is_static: bool,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        if let Some((address, slot)) = self.pending_load.take() {
+            if let Ok(value) = interp.stack.peek(0) {
+                self.storage.push(StorageAccess {
+                    address,
+                    slot,
+                    value,
+                    write: false,
+                });
+            }
+        }
+        eval
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+        _is_static: bool,
+    ) -> (InstructionResult, Gas, Bytes) {
+        self.open_calls.push(self.calls.len());
+        self.calls.push(CallFrame {
+            depth: data.journaled_state.depth(),
+            caller: inputs.context.caller,
+            address: inputs.contract,
+            input: inputs.input.clone(),
+            gas_limit: inputs.gas_limit,
+            gas_used: 0,
+            result: InstructionResult::Continue,
+        });
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+        _is_static: bool,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if let Some(index) = self.open_calls.pop() {
+            let frame = &mut self.calls[index];
+            frame.gas_used = inputs.gas_limit.saturating_sub(remaining_gas.remaining());
+            frame.result = ret;
+        }
+        (ret, remaining_gas, out)
+    }
+}
+
+/// Gas used and number of calls of a single precompile, captured by a [`PrecompileGasProfiler`].
+/// # Fields
+/// * `calls` - Number of times the precompile was called.
+/// * `gas_used` - Gas used by all calls of the precompile.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrecompileGas {
+    /// Number of times the precompile was called.
+    pub calls: u64,
+    /// Gas used by all calls of the precompile.
+    pub gas_used: u64,
+}
+
+/// Breaks out the gas used by precompiles (e.g., `ecrecover`, `modexp` or the pairing check) by precompile address, since it can dominate signature-heavy or cryptography-heavy transactions.
+/// # Fields
+/// * `precompiles` - The gas used and number of calls keyed by precompile address.
+#[derive(Debug, Default)]
+pub struct PrecompileGasProfiler {
+    /// The gas used and number of calls keyed by precompile address.
+    pub precompiles: HashMap<B160, PrecompileGas>,
+}
+
+impl PrecompileGasProfiler {
+    /// Returns the gas used by all precompile calls.
+    pub fn total_gas_used(&self) -> u64 {
+        self.precompiles.values().map(|gas| gas.gas_used).sum()
+    }
+}
+
+/// Whether an address belongs to a precompile.
+pub fn is_precompile(address: B160) -> bool {
+    let bytes = address.as_bytes();
+    bytes[..19].iter().all(|byte| *byte == 0) && (1..=LAST_PRECOMPILE).contains(&bytes[19])
+}
+
+impl<DB: Database> Inspector<DB> for PrecompileGasProfiler {
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+        _is_static: bool,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if is_precompile(inputs.contract) {
+            let precompile = self.precompiles.entry(inputs.contract).or_default();
+            precompile.calls += 1;
+            // A precompile runs with its own gas meter, starting at the gas limit of the call.
+            precompile.gas_used += inputs.gas_limit.saturating_sub(remaining_gas.remaining());
+        }
+        (ret, remaining_gas, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use revm::{
+        interpreter::InstructionResult,
+        primitives::{B160, U256},
+    };
+
+    use super::{is_precompile, CallTracer};
+    use crate::{manager::SimulationManager, tests::runtime_contract};
+
+    /// Runtime code that calls `callee` and then either reverts or stops, discarding whatever the callee returned.
+    fn caller_runtime(callee: B160, revert: bool) -> Vec<u8> {
+        let mut runtime = vec![
+            0x60, 0x00, // PUSH1 0 (retSize)
+            0x60, 0x00, // PUSH1 0 (retOffset)
+            0x60, 0x00, // PUSH1 0 (argsSize)
+            0x60, 0x00, // PUSH1 0 (argsOffset)
+            0x60, 0x00, // PUSH1 0 (value)
+            0x73, // PUSH20 callee
+        ];
+        runtime.extend_from_slice(callee.as_bytes());
+        runtime.extend_from_slice(&[
+            0x5a, // GAS
+            0xf1, // CALL
+            0x50, // POP
+        ]);
+        if revert {
+            runtime.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0xfd]); // REVERT(0, 0)
+        } else {
+            runtime.push(0x00); // STOP
+        }
+        runtime
+    }
+
+    #[test]
+    fn innermost_revert_reason_is_captured() {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // The innermost contract reverts with `Error("inner")`.
+        let inner = runtime_contract(&[
+            0x63, 0x08, 0xc3, 0x79, 0xa0, // PUSH4 selector
+            0x60, 0xe0, // PUSH1 224
+            0x1b, // SHL
+            0x60, 0x00, // PUSH1 0
+            0x52, // MSTORE
+            0x60, 0x20, // PUSH1 32 (offset of the string)
+            0x60, 0x04, // PUSH1 4
+            0x52, // MSTORE
+            0x60, 0x05, // PUSH1 5 (length of the string)
+            0x60, 0x24, // PUSH1 36
+            0x52, // MSTORE
+            0x64, 0x69, 0x6e, 0x6e, 0x65, 0x72, // PUSH5 "inner"
+            0x60, 0xd8, // PUSH1 216
+            0x1b, // SHL
+            0x60, 0x44, // PUSH1 68
+            0x52, // MSTORE
+            0x60, 0x64, // PUSH1 100
+            0x60, 0x00, // PUSH1 0
+            0xfd, // REVERT
+        ])
+        .deploy(&mut manager.environment, admin, ());
+        // The middle contract masks the inner revert with an empty one and the outer contract swallows that.
+        let middle = runtime_contract(&caller_runtime(inner.address, true)).deploy(
+            &mut manager.environment,
+            admin,
+            (),
+        );
+        let outer = runtime_contract(&caller_runtime(middle.address, false)).deploy(
+            &mut manager.environment,
+            admin,
+            (),
+        );
+
+        let (execution_result, return_data) = manager
+            .call_with_return_data("admin", outer.address, Bytes::new())
+            .unwrap();
+        assert!(execution_result.is_success());
+
+        assert_eq!(return_data.calls.len(), 3);
+        assert_eq!(return_data.calls[0].address, inner.address);
+        assert_eq!(return_data.calls[0].depth, 2);
+        assert_eq!(return_data.calls[1].address, middle.address);
+        assert!(return_data.calls[1].output.is_empty());
+        assert!(return_data.calls[2].success);
+        assert_eq!(
+            return_data.innermost_revert_reason(),
+            Some("inner".to_string())
+        );
+    }
+
+    #[test]
+    fn call_tracer_records_frames_and_storage() {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Increments slot 0 and then reverts.
+        let counter = runtime_contract(&[
+            0x60, 0x00, // PUSH1 0
+            0x54, // SLOAD
+            0x60, 0x01, // PUSH1 1
+            0x01, // ADD
+            0x60, 0x00, // PUSH1 0
+            0x55, // SSTORE
+            0x60, 0x00, // PUSH1 0
+            0x80, // DUP1
+            0xfd, // REVERT
+        ])
+        .deploy(&mut manager.environment, admin, ());
+        let caller = runtime_contract(&caller_runtime(counter.address, false)).deploy(
+            &mut manager.environment,
+            admin,
+            (),
+        );
+
+        let mut tracer = CallTracer::default();
+        let execution_result = manager
+            .call_with_inspector("admin", caller.address, Bytes::new(), &mut tracer)
+            .unwrap();
+        assert!(execution_result.is_success());
+
+        assert_eq!(tracer.calls.len(), 2);
+        assert_eq!(tracer.calls[0].address, caller.address);
+        assert_eq!(tracer.calls[0].result, InstructionResult::Stop);
+        assert_eq!(tracer.calls[1].depth, 1);
+        assert_eq!(tracer.calls[1].caller, caller.address);
+        assert_eq!(tracer.calls[1].result, InstructionResult::Revert);
+        assert!(tracer.calls[0].gas_used > tracer.calls[1].gas_used);
+
+        assert_eq!(tracer.storage.len(), 2);
+        assert!(!tracer.storage[0].write);
+        assert_eq!(tracer.storage[0].value, U256::ZERO);
+        assert!(tracer.storage[1].write);
+        assert_eq!(tracer.storage[1].value, U256::from(1));
+    }
+
+    #[test]
+    fn precompile_gas_is_attributed_to_ecrecover() {
+        let mut manager = SimulationManager::default();
+        let admin = manager.agents.get("admin").unwrap();
+
+        // Calls `ecrecover` five times in a loop with an all-zero signature.
+        let looper = runtime_contract(&[
+            0x60, 0x05, // PUSH1 5 (counter)
+            0x5b, // JUMPDEST
+            0x60, 0x00, // PUSH1 0 (retSize)
+            0x60, 0x00, // PUSH1 0 (retOffset)
+            0x60, 0x80, // PUSH1 128 (argsSize)
+            0x60, 0x00, // PUSH1 0 (argsOffset)
+            0x60, 0x01, // PUSH1 1 (ecrecover)
+            0x5a, // GAS
+            0xfa, // STATICCALL
+            0x50, // POP
+            0x60, 0x01, // PUSH1 1
+            0x90, // SWAP1
+            0x03, // SUB
+            0x80, // DUP1
+            0x60, 0x02, // PUSH1 2 (JUMPDEST)
+            0x57, // JUMPI
+            0x00, // STOP
+        ])
+        .deploy(&mut manager.environment, admin, ());
+
+        let (execution_result, profile) = manager
+            .call_with_precompile_profile("admin", looper.address, Bytes::new())
+            .unwrap();
+        assert!(execution_result.is_success());
+
+        let ecrecover = B160::from_low_u64_be(1);
+        assert!(is_precompile(ecrecover));
+        assert!(!is_precompile(looper.address));
+        assert_eq!(profile.precompiles.len(), 1);
+        // `ecrecover` costs a flat 3000 gas per call.
+        assert_eq!(profile.precompiles[&ecrecover].calls, 5);
+        assert_eq!(profile.precompiles[&ecrecover].gas_used, 5 * 3000);
+        assert_eq!(profile.total_gas_used(), 5 * 3000);
+    }
+}
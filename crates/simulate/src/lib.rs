@@ -2,12 +2,21 @@
 //! Lib crate for describing simulations.
 
 pub mod agent;
+pub mod batch;
+pub mod cheatcode;
+pub mod concentrated_liquidity;
 pub mod contract;
 pub mod environment;
 pub mod exchange;
+pub mod fork;
 pub mod historic;
+pub mod inspector;
 pub mod manager;
+pub mod mempool;
+pub mod proof;
+pub mod state;
 pub mod stochastic;
+pub mod trace;
 pub mod utils;
 
 #[cfg(test)]
@@ -15,16 +24,38 @@ mod tests {
     use std::{error::Error, thread};
 
     use bindings::{arbiter_token, writer};
-    use ethers::prelude::{H256, U256};
+    use ethers::{
+        abi::Contract,
+        prelude::{H256, U256},
+    };
     use revm::primitives::{ruint::Uint, B160};
 
     use crate::{
         agent::{user::User, Agent, AgentType},
-        contract::SimulationContract,
+        contract::{NotDeployed, SimulationContract},
         manager::SimulationManager,
         utils::recast_address,
     };
 
+    /// Builds a [`SimulationContract`] with an empty ABI from raw runtime bytecode.
+    /// The runtime bytecode is prefixed with init code that copies it into memory and returns it on deployment.
+    /// Panics if the runtime bytecode is longer than 255 bytes, since the init code pushes its length as a single byte.
+    pub(crate) fn runtime_contract(runtime: &[u8]) -> SimulationContract<NotDeployed> {
+        let length =
+            u8::try_from(runtime.len()).expect("The init code only copies up to 255 bytes.");
+        let mut bytecode = vec![
+            0x60, length, // PUSH1 length
+            0x60, 0x0c, // PUSH1 offset of the runtime code
+            0x60, 0x00, // PUSH1 0
+            0x39, // CODECOPY
+            0x60, length, // PUSH1 length
+            0x60, 0x00, // PUSH1 0
+            0xf3, // RETURN
+        ];
+        bytecode.extend_from_slice(runtime);
+        SimulationContract::new(Contract::default(), bytecode.into())
+    }
+
     #[test]
     /// Test that the writer contract can echo a string.
     /// The writer contract takes in no constructor args.
@@ -51,7 +82,7 @@ mod tests {
             &writer,
             call_data,
             Uint::from(0),
-        );
+        )?;
         let value = manager.unpack_execution(execution_result)?;
 
         let response: String = writer.decode_output("echoString", value)?;
@@ -100,7 +131,7 @@ mod tests {
             &arbiter_token,
             call_data,
             Uint::ZERO,
-        );
+        )?;
         let value = manager.unpack_execution(execution_result)?;
 
         let response: String = arbiter_token.decode_output("name", value)?;
@@ -120,7 +151,7 @@ mod tests {
             &arbiter_token,
             call_data,
             Uint::ZERO,
-        );
+        )?;
         println!("Mint execution result: {:#?}", execution_result);
 
         let call_data = arbiter_token.encode_function(
@@ -134,7 +165,7 @@ mod tests {
             &arbiter_token,
             call_data,
             Uint::from(0),
-        );
+        )?;
         let value = manager.unpack_execution(execution_result)?;
 
         let response: U256 = arbiter_token.decode_output("balanceOf", value)?;
@@ -164,7 +195,7 @@ mod tests {
         let call_data = writer.encode_function("echoString", input_arguments)?;
 
         // Call the 'echoString' function.
-        let _execution_result = admin.call_contract(environment, &writer, call_data, Uint::ZERO);
+        let _execution_result = admin.call_contract(environment, &writer, call_data, Uint::ZERO)?;
 
         // Read logs twice since the first time is just the contract creation which gives no log.
         let _logs = admin.read_logs()?;
@@ -308,7 +339,7 @@ mod tests {
 
         // Call the 'echoString' function.
         let _execution_result =
-            admin.call_contract(&mut manager.environment, &writer, call_data, Uint::ZERO);
+            admin.call_contract(&mut manager.environment, &writer, call_data, Uint::ZERO)?;
 
         // Generate calldata for the 'echoString' function again.
         let test_string = "Hello, world! again...";
@@ -316,7 +347,7 @@ mod tests {
         let call_data = writer.encode_function("echoString", input_arguments)?;
         // Call the `echoString` function again.
         let _execution_result =
-            admin.call_contract(&mut manager.environment, &writer, call_data, Uint::ZERO);
+            admin.call_contract(&mut manager.environment, &writer, call_data, Uint::ZERO)?;
 
         if handle.join().is_err() {
             panic!("Thread panicked!");
@@ -3,33 +3,99 @@
 //! Managers are responsible for adding agents, running agents, deploying contracts, calling contracts, and reading logs.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
+    convert::Infallible,
     error::Error,
     fmt::{Display, Formatter, Result as FmtResult},
+    fs,
+    future::Future,
+    mem::size_of,
+    path::Path,
+    thread,
+    time::Duration,
 };
 
+use bindings::ierc20::IERC20_ABI;
 use bytes::Bytes;
 use crossbeam_channel::unbounded;
-use revm::primitives::{AccountInfo, Address, ExecutionResult, Log, Output, B160, U256};
+use ethers::{
+    abi::{self, AbiType, ParamType, Token, Tokenizable, Tokenize},
+    contract::AbiError,
+    prelude::{BaseContract, LocalWallet, Signature, Signer, H256},
+    providers::Middleware,
+    types::{Filter, Transaction, ValueOrArray},
+    utils::rlp,
+};
+use rand::{rngs::StdRng, SeedableRng};
+use revm::{
+    db::{AccountState, CacheDB, DbAccount},
+    primitives::{
+        keccak256, AccountInfo, Address, BlockEnv, Bytecode, CreateScheme, EVMError,
+        ExecutionResult, Halt, InvalidTransaction, Log, Output, SpecId, TransactTo, TxEnv, B160,
+        B256, KECCAK_EMPTY, U256,
+    },
+    DatabaseRef, Inspector,
+};
 
 use crate::{
     agent::{
-        simple_arbitrageur::SimpleArbitrageur, user::User, AgentType, IsActive, NotActive,
-        TransactSettings,
+        filter_events, price_feed::PriceFeed, simple_arbitrageur::SimpleArbitrageur, user::User,
+        Agent, AgentType, IsActive, NotActive, TransactSettings,
+    },
+    cheatcode::{Cheatcode, CheatcodeRecord},
+    contract::{IsDeployed, NotDeployed, SimulationContract},
+    environment::{IndexedLog, SimulationEnvironment},
+    exchange::TwapOracle,
+    fork::{self, ForkDB},
+    inspector::{decode_revert_reason, PrecompileGasProfiler, ReturnDataTracker},
+    mempool::{Mempool, OrderingPolicy, RejectedTx},
+    proof::StateProof,
+    state::{
+        recast_ethers_u256, recast_u256, AgentDump, AgentKind, BlockDump, ConfigDump,
+        PriceFeedDump, SimulationSave, StateDump, StateFormat,
     },
-    environment::SimulationEnvironment,
+    trace::{TraceFile, TraceReceipt},
+    utils::{permit_digest, recast_address},
 };
 
 #[derive(Debug)]
 /// Error type for the simulation manager.
-/// # Fields
-/// * `message` - Error message.
-/// * `output` - Byte output of the error.
-pub struct ManagerError {
-    /// Error message.
-    pub message: String,
-    /// Byte output of the error.
-    pub output: Option<Bytes>,
+/// # Variants
+/// * `DuplicateAddress` - An agent with the address is already active.
+/// * `DuplicateName` - An agent with the name is already active.
+/// * `AgentNotFound` - No active agent has the name.
+/// * `Halt` - The call halted, e.g., because it ran out of gas.
+/// * `Revert` - The call reverted.
+/// * `Abi` - Calldata could not be encoded or an output could not be decoded.
+/// * `Rejected` - The EVM rejected the transaction before executing it, e.g., for a nonce collision.
+/// * `Other` - Any other failure, described by its message.
+pub enum ManagerError {
+    /// An agent with the address is already active.
+    DuplicateAddress,
+    /// An agent with the name is already active.
+    DuplicateName,
+    /// No active agent has the name.
+    AgentNotFound(String),
+    /// The call halted, e.g., because it ran out of gas.
+    Halt {
+        /// Why the call halted.
+        reason: Halt,
+        /// The gas used by the call.
+        gas_used: u64,
+    },
+    /// The call reverted.
+    Revert {
+        /// The revert data.
+        output: Bytes,
+        /// The gas used by the call.
+        gas_used: u64,
+    },
+    /// Calldata could not be encoded or an output could not be decoded.
+    Abi(String),
+    /// The EVM rejected the transaction before executing it, e.g., for a nonce collision.
+    Rejected(String),
+    /// Any other failure, described by its message.
+    Other(String),
 }
 
 impl Error for ManagerError {}
@@ -37,19 +103,172 @@ impl Error for ManagerError {}
 impl Display for ManagerError {
     /// Display the error message.
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{}", self.message)
+        match self {
+            ManagerError::DuplicateAddress => write!(
+                f,
+                "Agent with that address already exists in the simulation environment."
+            ),
+            ManagerError::DuplicateName => write!(
+                f,
+                "Agent with that name already exists in the simulation environment."
+            ),
+            ManagerError::AgentNotFound(name) => write!(
+                f,
+                "Agent with name {} does not exist in the simulation environment.",
+                name
+            ),
+            ManagerError::Halt { reason, gas_used } => write!(
+                f,
+                "This call halted for {:#?} and used {} gas.",
+                reason, gas_used
+            ),
+            ManagerError::Revert { output, gas_used } => {
+                write!(f, "{} (used {} gas)", describe_revert(output), gas_used)
+            }
+            ManagerError::Abi(message) => write!(f, "ABI error: {}", message),
+            ManagerError::Rejected(message) | ManagerError::Other(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl From<AbiError> for ManagerError {
+    fn from(err: AbiError) -> Self {
+        ManagerError::Abi(err.to_string())
+    }
+}
+
+impl From<EVMError<Infallible>> for ManagerError {
+    fn from(err: EVMError<Infallible>) -> Self {
+        ManagerError::Rejected(format!("The transaction was rejected: {:?}", err))
     }
 }
 
+/// Saved info and storage of a subset of accounts, taken with [`SimulationManager::snapshot_accounts`].
+/// # Fields
+/// * `accounts` - The saved accounts. `None` if an account did not exist when the snapshot was taken.
+#[derive(Debug, Clone)]
+pub struct PartialSnapshot {
+    /// The saved accounts. `None` if an account did not exist when the snapshot was taken.
+    accounts: Vec<(B160, Option<DbAccount>)>,
+}
+
+/// Identifies a snapshot of the full chain state, taken with [`SimulationManager::snapshot`]. Identifiers are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SnapshotId(u64);
+
+/// Saved chain state of the simulation, i.e., the database and the block environment.
+/// # Fields
+/// * `accounts` - The info and storage of every account in the database.
+/// * `contracts` - The bytecode in the database, keyed by code hash.
+/// * `block_hashes` - The block hashes in the database.
+/// * `block` - The block environment.
+#[derive(Debug, Clone)]
+struct ChainSnapshot {
+    /// The info and storage of every account in the database.
+    accounts: HashMap<B160, DbAccount>,
+    /// The bytecode in the database, keyed by code hash.
+    contracts: HashMap<B256, Bytecode>,
+    /// The block hashes in the database.
+    block_hashes: HashMap<U256, B256>,
+    /// The block environment.
+    block: BlockEnv,
+}
+
+/// The output of a successful call along with the gas it used, returned by [`SimulationManager::unpack_execution_with_gas`].
+/// # Fields
+/// * `output` - The raw bytes of the output.
+/// * `gas_used` - The gas used by the call, after refunds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionOutput {
+    /// The raw bytes of the output.
+    pub output: Bytes,
+    /// The gas used by the call, after refunds.
+    pub gas_used: u64,
+}
+
+/// Summary of the work the simulation engine has done so far.
+/// # Fields
+/// * `transactions` - The number of transactions executed.
+/// * `gas_used` - The gas used by all executed transactions.
+/// * `execution_time` - The wall-clock time spent executing transactions.
+/// * `transactions_per_second` - Executed transactions per second of execution time.
+/// * `gas_per_second` - Gas used per second of execution time.
+/// * `idle_agents` - Agents that never sent a transaction, sorted by name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    /// The number of transactions executed.
+    pub transactions: u64,
+    /// The gas used by all executed transactions.
+    pub gas_used: u64,
+    /// The wall-clock time spent executing transactions.
+    pub execution_time: Duration,
+    /// Executed transactions per second of execution time.
+    pub transactions_per_second: f64,
+    /// Gas used per second of execution time.
+    pub gas_per_second: f64,
+    /// Agents that never sent a transaction, sorted by name.
+    pub idle_agents: Vec<IdleAgent>,
+}
+
+/// An agent that never sent a transaction, which often means that its event filters are misconfigured.
+/// # Fields
+/// * `name` - Name of the agent.
+/// * `last_observed_event` - The last log that passed the agent's event filters, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleAgent {
+    /// Name of the agent.
+    pub name: String,
+    /// The last log that passed the agent's event filters, if any.
+    pub last_observed_event: Option<Log>,
+}
+
 /// Manages simulations.
 /// # Fields
 /// * `environment` - The simulation environment that the manager controls.
 /// * `agents` - The agents that are currently running in the simulation environment.
+/// * `signers` - The signing keys of the agents that were activated with one.
+/// * `step_delay` - The wall-clock pause after each step of [`SimulationManager::run_steps`].
+/// * `blackboard` - Shared scratch space for coordination between agents that lives outside of the on-chain state. It is cleared at the start of every step.
+/// * `mempool` - Transactions that wait to be included in the next block.
+/// * `twap_oracles` - TWAP oracles, keyed by name, whose cumulative prices accrue as blocks advance.
+/// * `fork_accounts` - The accounts that were fetched from the node of a forked environment rather than created locally.
+/// * `db_memory_cap` - The approximate number of bytes the database may use before clean fork accounts are evicted.
+/// * `cheatcode_log` - Every cheatcode used so far, in the order they were used.
+/// * `snapshots` - The snapshots of the chain state that can still be reverted to, keyed by their identifier.
+/// * `next_snapshot_id` - The identifier of the next snapshot.
+/// * `rng` - The random number generator that all randomness of the simulation derives from.
+/// * `round` - The number of rounds of [`SimulationManager::run_agents`] completed so far.
 pub struct SimulationManager {
     /// `SimulationEnvironment` that the simulation manager controls.
     pub environment: SimulationEnvironment,
     /// The agents that are currently running in the simulation environment.
     pub agents: HashMap<String, AgentType<IsActive>>,
+    /// The signing keys of the agents that were activated with one.
+    signers: HashMap<String, LocalWallet>,
+    /// The wall-clock pause after each step of [`SimulationManager::run_steps`]. Zero (unthrottled) by default.
+    step_delay: Duration,
+    /// Shared scratch space for coordination between agents that lives outside of the on-chain state. It is cleared at the start of every step.
+    blackboard: HashMap<String, Vec<u8>>,
+    /// Transactions that wait to be included in the next block.
+    pub mempool: Mempool,
+    /// TWAP oracles, keyed by name, whose cumulative prices accrue as blocks advance.
+    pub twap_oracles: HashMap<String, TwapOracle>,
+    /// The accounts that were fetched from the node of a forked environment rather than created locally.
+    fork_accounts: HashSet<B160>,
+    /// The approximate number of bytes the database may use before clean fork accounts are evicted. Unbounded by default.
+    db_memory_cap: Option<usize>,
+    /// Every cheatcode used so far, in the order they were used.
+    cheatcode_log: Vec<CheatcodeRecord>,
+    /// The snapshots of the chain state that can still be reverted to, keyed by their identifier.
+    snapshots: BTreeMap<u64, ChainSnapshot>,
+    /// The identifier of the next snapshot.
+    next_snapshot_id: u64,
+    /// The random number generator that all randomness of the simulation derives from. Seeded from entropy unless the manager was created with [`SimulationManager::new_with_seed`].
+    rng: StdRng,
+    /// The number of rounds of [`SimulationManager::run_agents`] completed so far.
+    round: u64,
 }
 
 impl Default for SimulationManager {
@@ -63,9 +282,62 @@ impl SimulationManager {
     /// Constructor function to instantiate a manager that has a default admin user and a simulation environment.
     /// The admin will always be given the 0x0...1 address.
     pub fn new() -> Self {
+        Self::new_with_environment(SimulationEnvironment::new())
+    }
+
+    /// Constructor function to instantiate a manager whose randomness, i.e., the keys of [`SimulationManager::generate_signer`] and everything drawn from [`SimulationManager::rng`], derives from a seed.
+    /// Two runs with the same seed and the same inputs produce identical state.
+    /// # Arguments
+    /// * `seed` - Seed of the random number generator.
+    pub fn new_with_seed(seed: u64) -> Self {
+        let mut simulation_manager = Self::new();
+        simulation_manager.rng = StdRng::seed_from_u64(seed);
+        simulation_manager
+    }
+
+    /// Constructor function to instantiate a manager whose EVM is pinned to a specific spec.
+    /// Pinning the spec keeps the gas schedule stable so that tests can assert exact gas numbers.
+    /// # Arguments
+    /// * `spec_id` - The spec (hardfork) that the EVM executes transactions with.
+    pub fn with_pinned_gas(spec_id: SpecId) -> Self {
+        Self::new_with_environment(SimulationEnvironment::new_with_spec(spec_id))
+    }
+
+    /// Constructor function to instantiate a manager whose chain has a specific chain id, e.g., to replay transactions signed for a live chain.
+    /// # Arguments
+    /// * `chain_id` - The chain id that contracts read as `block.chainid`.
+    pub fn with_chain_id(chain_id: u64) -> Self {
+        Self::new_with_environment(SimulationEnvironment::new().with_chain_id(chain_id))
+    }
+
+    /// Constructor function to instantiate a manager whose environment forks the state of a block from a node, e.g., to run agents against live pools.
+    /// State is fetched lazily and blocks on an internal runtime, so the manager must not be created or driven from within an async context.
+    /// # Arguments
+    /// * `rpc_url` - The URL of the node. Forking older blocks requires an archive node.
+    /// * `block` - The number of the block to fork.
+    pub fn fork(rpc_url: &str, block: u64) -> Result<Self, ManagerError> {
+        let environment =
+            SimulationEnvironment::fork(rpc_url, block).map_err(ManagerError::Other)?;
+        Ok(Self::new_with_environment(environment))
+    }
+
+    /// Instantiates a manager around an environment and activates the admin at the 0x0...1 address.
+    fn new_with_environment(environment: SimulationEnvironment) -> Self {
         let mut simulation_manager = Self {
-            environment: SimulationEnvironment::new(),
+            environment,
             agents: HashMap::new(),
+            signers: HashMap::new(),
+            step_delay: Duration::ZERO,
+            blackboard: HashMap::new(),
+            mempool: Mempool::default(),
+            twap_oracles: HashMap::new(),
+            fork_accounts: HashSet::new(),
+            db_memory_cap: None,
+            cheatcode_log: Vec::new(),
+            snapshots: BTreeMap::new(),
+            next_snapshot_id: 0,
+            rng: StdRng::from_entropy(),
+            round: 0,
         };
         let admin = AgentType::User(User::new("admin", None));
         simulation_manager
@@ -74,12 +346,162 @@ impl SimulationManager {
         simulation_manager
     }
 
-    /// Run all agents concurrently in the current simulation environment.
-    pub fn run_agents() {
-        todo!()
+    /// Runs one block of the simulation: every agent may propose a transaction, e.g., several [`SimpleArbitrageur`]s reacting to the same price feed, and the proposals are built into a block.
+    /// [`PriceFeed`]s are not asked, they propose the next price of their path on their own.
+    /// The ordering policy of the mempool decides who goes first, so when agents race for the same opportunity only the first one to execute captures it and the others find it gone.
+    /// Use [`OrderingPolicy::GasPrice`] for priority-fee auctions and [`OrderingPolicy::Fifo`] to include proposals in the order of the agents' names.
+    /// # Arguments
+    /// * `strategy` - Decides the transaction an agent sends this block as `(to, call_data, value)`, or `None` if it sits the block out.
+    /// # Returns
+    /// * `Ok(Vec<ExecutionResult>)` - The execution results of the block in inclusion order.
+    pub fn run_agents<F>(&mut self, mut strategy: F) -> Result<Vec<ExecutionResult>, ManagerError>
+    where
+        F: FnMut(&AgentType<IsActive>) -> Option<(B160, Bytes, U256)>,
+    {
+        // Visit the agents in a fixed order so that ties in the ordering policy resolve the same way every run.
+        let mut names: Vec<String> = self.agents.keys().cloned().collect();
+        names.sort();
+        for name in names {
+            let proposal = match self.agents.get_mut(&name) {
+                Some(AgentType::PriceFeed(price_feed)) => price_feed
+                    .next_price_update()
+                    .map(|(to, call_data)| (to, call_data, U256::ZERO)),
+                Some(agent) => strategy(agent),
+                None => None,
+            };
+            if let Some((to, call_data, value)) = proposal {
+                self.submit_transaction(&name, to, call_data, value)?;
+            }
+        }
+        let execution_results = self.build_block()?;
+        self.round += 1;
+        Ok(execution_results)
+    }
+
+    /// Runs the given number of rounds of [`SimulationManager::run_agents`], each in its own block, pausing for the configured step delay after each round.
+    /// The strategy is told the current round, so agents can schedule behavior, e.g., rebalance every 10 rounds. Stops early and returns the error if a round fails.
+    /// # Arguments
+    /// * `rounds` - The number of rounds to run.
+    /// * `strategy` - Decides the transaction an agent sends in a round, given the round and the agent, as `(to, call_data, value)`, or `None` if it sits the round out.
+    pub fn run<F>(&mut self, rounds: u64, mut strategy: F) -> Result<(), ManagerError>
+    where
+        F: FnMut(u64, &AgentType<IsActive>) -> Option<(B160, Bytes, U256)>,
+    {
+        self.run_steps(rounds as usize, |manager| {
+            let round = manager.round;
+            manager.run_agents(|agent| strategy(round, agent))?;
+            Ok(())
+        })
+    }
+
+    /// Returns the number of rounds completed so far, which is also the index of the next round.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// Runs one block of the simulation like [`SimulationManager::run_agents`], but the agents decide concurrently on the tokio runtime, e.g., when each decision awaits RPC calls.
+    /// Each decision runs as its own task. The proposals are collected in the order of the agents' names and then built into a block on the single EVM, so the outcome does not depend on which task finished first.
+    /// The block is built inside [`tokio::task::block_in_place`] since a forked database blocks on its own runtime, so this has to run on the multi-threaded runtime.
+    /// # Arguments
+    /// * `strategy` - Starts the decision of an agent, given its name and address, that resolves to the transaction it sends this block as `(to, call_data, value)`, or `None` if it sits the block out.
+    /// # Returns
+    /// * `Ok(Vec<ExecutionResult>)` - The execution results of the block in inclusion order.
+    pub async fn run_agents_concurrent<F, Fut>(
+        &mut self,
+        strategy: F,
+    ) -> Result<Vec<ExecutionResult>, ManagerError>
+    where
+        F: Fn(String, B160) -> Fut,
+        Fut: Future<Output = Option<(B160, Bytes, U256)>> + Send + 'static,
+    {
+        let mut names: Vec<String> = self.agents.keys().cloned().collect();
+        names.sort();
+        let mut decisions = Vec::with_capacity(names.len());
+        for name in names {
+            let decision = match self.agents.get_mut(&name) {
+                Some(AgentType::PriceFeed(price_feed)) => {
+                    let proposal = price_feed
+                        .next_price_update()
+                        .map(|(to, call_data)| (to, call_data, U256::ZERO));
+                    tokio::spawn(async move { proposal })
+                }
+                Some(agent) => tokio::spawn(strategy(name.clone(), agent.address())),
+                None => continue,
+            };
+            decisions.push((name, decision));
+        }
+        for (name, decision) in decisions {
+            let proposal = decision.await.map_err(|err| {
+                ManagerError::Other(format!("The decision of {} failed: {}", name, err))
+            })?;
+            if let Some((to, call_data, value)) = proposal {
+                self.submit_transaction(&name, to, call_data, value)?;
+            }
+        }
+        let execution_results = tokio::task::block_in_place(|| self.build_block())?;
+        self.round += 1;
+        Ok(execution_results)
+    }
+
+    /// Throttles [`SimulationManager::run_steps`] so the simulation advances at a human-watchable pace, e.g., for live visualization.
+    /// A zero delay (the default) runs unthrottled.
+    /// # Arguments
+    /// * `step_delay` - The wall-clock pause after each step.
+    pub fn set_step_delay(&mut self, step_delay: Duration) {
+        self.step_delay = step_delay;
+    }
+
+    /// Throttles [`SimulationManager::run_steps`] to at most `steps_per_second` steps per second.
+    /// A non-positive rate removes the throttle.
+    /// # Arguments
+    /// * `steps_per_second` - The target number of steps per second.
+    pub fn set_steps_per_second(&mut self, steps_per_second: f64) {
+        self.step_delay = if steps_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / steps_per_second)
+        } else {
+            Duration::ZERO
+        };
+    }
+
+    /// Advances the simulation by calling `step` the given number of times, pausing for the configured step delay after each step.
+    /// The blackboard is cleared before each step. Stops early and returns the error if a step fails.
+    /// # Arguments
+    /// * `steps` - The number of steps to run.
+    /// * `step` - The work done in a single step of the simulation.
+    pub fn run_steps<F>(&mut self, steps: usize, mut step: F) -> Result<(), ManagerError>
+    where
+        F: FnMut(&mut Self) -> Result<(), ManagerError>,
+    {
+        for _ in 0..steps {
+            self.blackboard.clear();
+            step(self)?;
+            self.enforce_db_memory_cap();
+            if !self.step_delay.is_zero() {
+                thread::sleep(self.step_delay);
+            }
+        }
+        Ok(())
+    }
+
+    /// Read an entry of the blackboard, the scratch space that agents share outside of the on-chain state.
+    /// The blackboard is cleared at the start of every step of [`SimulationManager::run_steps`], so a read sees exactly the writes made earlier in the same step.
+    /// # Arguments
+    /// * `key` - The key of the entry.
+    pub fn blackboard_get(&self, key: &str) -> Option<&[u8]> {
+        self.blackboard.get(key).map(Vec::as_slice)
+    }
+
+    /// Write an entry of the blackboard, replacing any previous value.
+    /// # Arguments
+    /// * `key` - The key of the entry.
+    /// * `value` - The value to store.
+    pub fn blackboard_set<S: Into<String>>(&mut self, key: S, value: Vec<u8>) {
+        self.blackboard.insert(key.into(), value);
     }
 
     /// Adds and activates an agent to be put in the collection of agents under the manager's control.
+    /// Any `auto_approvals` of the agent are executed as the agent immediately after activation.
+    /// If an approval fails, the agent is removed again and the error is returned, so the agent can be activated again once the approval is fixed. Approvals that went through before the failing one stay on chain.
     /// # Arguments
     /// * `new_agent` - The agent to be added to the collection of agents.
     /// * `new_agent_address` - The address that the agent will be given.
@@ -89,17 +511,8 @@ impl SimulationManager {
         new_agent_address: Address,
     ) -> Result<(), ManagerError> {
         // Check to make sure we are not creating an agent with an address or name that already exists.
-        if self
-            .agents
-            .values()
-            .into_iter()
-            .any(|agent_in_db| agent_in_db.inner().address() == new_agent_address)
-        {
-            return Err(ManagerError {
-                message: "Agent with that address already exists in the simulation environment."
-                    .to_string(),
-                output: None,
-            });
+        if self.address_taken(new_agent_address) {
+            return Err(ManagerError::DuplicateAddress);
         };
         if self
             .agents
@@ -107,11 +520,7 @@ impl SimulationManager {
             .into_iter()
             .any(|name_in_db| *name_in_db == new_agent.inner().name())
         {
-            return Err(ManagerError {
-                message: "Agent with that name already exists in the simulation environment."
-                    .to_string(),
-                output: None,
-            });
+            return Err(ManagerError::DuplicateName);
         };
 
         // Create the agent and add it to the simulation environment so long as we don't throw an error above.
@@ -122,18 +531,24 @@ impl SimulationManager {
             .unwrap()
             .insert_account_info(new_agent_address, account_info.clone());
         let (event_sender, event_receiver) = unbounded::<Vec<Log>>();
+        let name = new_agent.inner().name();
+        let auto_approvals = match &new_agent {
+            AgentType::User(user) => user.auto_approvals.clone(),
+            AgentType::SimpleArbitrageur(simple_arbitrageur) => {
+                simple_arbitrageur.auto_approvals.clone()
+            }
+            AgentType::PriceFeed(_) => vec![],
+        };
         match new_agent {
             AgentType::User(user) => {
                 let new_user = User::<IsActive> {
                     name: user.name,
                     address: new_agent_address,
                     account_info,
-                    transact_settings: TransactSettings {
-                        gas_limit: u64::MAX,   // TODO: Users should have a gas limit.
-                        gas_price: U256::ZERO, // TODO: Users should have an associated gas price.
-                    },
+                    transact_settings: user.transact_settings,
                     event_receiver,
                     event_filters: user.event_filters,
+                    auto_approvals: user.auto_approvals,
                 };
                 self.agents
                     .insert(new_user.name.clone(), AgentType::User(new_user));
@@ -143,60 +558,3179 @@ impl SimulationManager {
                     name: simple_arbitrageur.name,
                     address: new_agent_address,
                     account_info,
-                    transact_settings: TransactSettings {
-                        gas_limit: u64::MAX,   // TODO: Users should have a gas limit.
-                        gas_price: U256::ZERO, // TODO: Users should have an associated gas price.
-                    },
+                    transact_settings: simple_arbitrageur.transact_settings,
                     event_receiver,
                     event_filters: simple_arbitrageur.event_filters,
                     prices: simple_arbitrageur.prices,
+                    gas_estimate: simple_arbitrageur.gas_estimate,
+                    trade_size: simple_arbitrageur.trade_size,
+                    noise: simple_arbitrageur.noise,
+                    auto_approvals: simple_arbitrageur.auto_approvals,
                 };
                 self.agents.insert(
                     new_simple_arbitrageur.name.clone(),
                     AgentType::SimpleArbitrageur(new_simple_arbitrageur),
                 );
             }
+            AgentType::PriceFeed(price_feed) => {
+                let new_price_feed = PriceFeed::<IsActive> {
+                    name: price_feed.name,
+                    address: new_agent_address,
+                    account_info,
+                    transact_settings: price_feed.transact_settings,
+                    event_receiver,
+                    event_filters: price_feed.event_filters,
+                    exchange: price_feed.exchange,
+                    prices: price_feed.prices,
+                    next: price_feed.next,
+                    market_impact: price_feed.market_impact,
+                    last_price: price_feed.last_price,
+                };
+                self.agents.insert(
+                    new_price_feed.name.clone(),
+                    AgentType::PriceFeed(new_price_feed),
+                );
+            }
         };
-        self.environment.add_sender(event_sender);
+        let event_filters = self.agents[&name].event_filters();
+        self.environment
+            .add_sender(name.clone(), event_sender, event_filters);
+        for (token, spender) in auto_approvals {
+            if let Err(err) = self.approve(&name, token, spender, U256::MAX) {
+                self.agents.remove(&name);
+                self.environment.remove_sender(&name);
+                return Err(err);
+            }
+        }
         Ok(())
     }
 
-    /// Takes an `ExecutionResult` and returns the raw bytes of the output that can then be decoded.
+    /// Removes an agent from the simulation, e.g., a liquidity provider that withdraws and leaves.
+    /// The agent stops receiving events and its signing key, if any, is forgotten. Its account stays in the database, so its balances and nonce remain on chain.
+    /// The admin cannot be removed, since the manager sends transactions such as cheatcodes on its behalf.
     /// # Arguments
-    /// * `execution_result` - The `ExecutionResult` that we want to unpack.
+    /// * `name` - The name of the agent.
     /// # Returns
-    /// * `Ok(Bytes)` - The raw bytes of the output.
-    pub fn unpack_execution(
-        &self,
-        execution_result: ExecutionResult,
-    ) -> Result<Bytes, ManagerError> {
-        match execution_result {
-            ExecutionResult::Success { output, .. } => match output {
-                Output::Call(value) => Ok(value),
-                Output::Create(value, _address) => Ok(value),
-            },
-            ExecutionResult::Halt { reason, gas_used } => Err(ManagerError {
-                message: format!(
-                    "This call halted for {:#?} and used {} gas.",
-                    reason, gas_used
-                ),
-                output: None,
+    /// * `Ok(AgentType<NotActive>)` - The agent, which can be activated again, e.g., at another address.
+    pub fn deactivate_agent(&mut self, name: &str) -> Result<AgentType<NotActive>, ManagerError> {
+        if name == "admin" {
+            return Err(ManagerError::Other(
+                "The admin agent cannot be deactivated.".to_string(),
+            ));
+        }
+        let agent = match self.agents.remove(name) {
+            Some(agent) => agent,
+            None => return Err(ManagerError::AgentNotFound(name.to_string())),
+        };
+        self.environment.remove_sender(name);
+        self.signers.remove(name);
+        Ok(match agent {
+            AgentType::User(user) => AgentType::User(User::<NotActive> {
+                name: user.name,
+                address: (),
+                account_info: (),
+                transact_settings: user.transact_settings,
+                event_receiver: (),
+                event_filters: user.event_filters,
+                auto_approvals: user.auto_approvals,
             }),
-            ExecutionResult::Revert { output, gas_used } => Err(ManagerError {
-                message: format!(
-                    "This call reverted with output {:#?} and used {} gas.",
-                    output, gas_used
-                ),
-                output: Some(output),
+            AgentType::SimpleArbitrageur(simple_arbitrageur) => {
+                AgentType::SimpleArbitrageur(SimpleArbitrageur::<NotActive> {
+                    name: simple_arbitrageur.name,
+                    address: (),
+                    account_info: (),
+                    transact_settings: simple_arbitrageur.transact_settings,
+                    event_receiver: (),
+                    event_filters: simple_arbitrageur.event_filters,
+                    prices: simple_arbitrageur.prices,
+                    gas_estimate: simple_arbitrageur.gas_estimate,
+                    trade_size: simple_arbitrageur.trade_size,
+                    noise: simple_arbitrageur.noise,
+                    auto_approvals: simple_arbitrageur.auto_approvals,
+                })
+            }
+            AgentType::PriceFeed(price_feed) => AgentType::PriceFeed(PriceFeed::<NotActive> {
+                name: price_feed.name,
+                address: (),
+                account_info: (),
+                transact_settings: price_feed.transact_settings,
+                event_receiver: (),
+                event_filters: price_feed.event_filters,
+                exchange: price_feed.exchange,
+                prices: price_feed.prices,
+                next: price_feed.next,
+                market_impact: price_feed.market_impact,
+                last_price: price_feed.last_price,
             }),
+        })
+    }
+
+    /// Activates an agent at the preferred address or, if another agent already has it, at the next free sequential address.
+    /// # Arguments
+    /// * `new_agent` - The agent to be added to the collection of agents.
+    /// * `preferred_address` - The address that the agent is given if it is free.
+    /// # Returns
+    /// * `Ok(B160)` - The address that the agent was actually given.
+    pub fn activate_agent_or_relocate(
+        &mut self,
+        new_agent: AgentType<NotActive>,
+        preferred_address: B160,
+    ) -> Result<B160, ManagerError> {
+        let mut new_agent_address = preferred_address;
+        while self.address_taken(new_agent_address) {
+            new_agent_address = next_address(new_agent_address);
         }
+        self.activate_agent(new_agent, new_agent_address)?;
+        Ok(new_agent_address)
     }
-}
 
-#[test]
-fn agent_address_collision() {
-    let mut manager = SimulationManager::default();
-    let alice = User::new("alice", None);
-    let result = manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(1));
-    assert!(result.is_err());
+    /// Whether an agent with the given address is already active.
+    fn address_taken(&self, address: B160) -> bool {
+        self.get_agent_by_address(address).is_some()
+    }
+
+    /// Looks up an active agent by name.
+    /// # Arguments
+    /// * `name` - Name of the agent.
+    /// # Returns
+    /// * `Option<&AgentType<IsActive>>` - The agent, or `None` if no active agent has that name.
+    pub fn get_agent(&self, name: &str) -> Option<&AgentType<IsActive>> {
+        self.agents.get(name)
+    }
+
+    /// Looks up an active agent by address.
+    /// # Arguments
+    /// * `address` - Address of the agent.
+    /// # Returns
+    /// * `Option<&AgentType<IsActive>>` - The agent, or `None` if no active agent has that address.
+    pub fn get_agent_by_address(&self, address: Address) -> Option<&AgentType<IsActive>> {
+        self.agents
+            .values()
+            .find(|agent| agent.inner().address() == address)
+    }
+
+    /// Has an agent approve a spender for an amount of an ERC-20 token.
+    /// # Arguments
+    /// * `owner` - The name of the agent that owns the tokens.
+    /// * `token` - The address of the ERC-20 token contract.
+    /// * `spender` - The address that is allowed to spend the tokens.
+    /// * `amount` - The amount of tokens the spender is allowed to spend.
+    pub fn approve(
+        &mut self,
+        owner: &str,
+        token: B160,
+        spender: B160,
+        amount: U256,
+    ) -> Result<(), ManagerError> {
+        let amount: ethers::types::U256 = amount.into();
+        let call_data = BaseContract::from(IERC20_ABI.clone())
+            .encode("approve", (recast_address(spender), amount))?
+            .into_iter()
+            .collect();
+        let tx = self
+            .find_agent(owner)?
+            .build_call_transaction(token, call_data, U256::ZERO);
+        let execution_result = self.environment.execute(tx)?;
+        self.unpack_execution(execution_result)?;
+        Ok(())
+    }
+
+    /// Deploys a contract as an agent, e.g., an `ArbiterToken` or `MockERC20` from the `bindings` crate that agents can mint and trade.
+    /// # Arguments
+    /// * `deployer` - The name of the agent deploying the contract.
+    /// * `contract` - The contract to deploy, which can be deployed again later.
+    /// * `constructor_arguments` - The constructor arguments of the contract.
+    /// # Returns
+    /// * `Ok(SimulationContract<IsDeployed>)` - The deployed contract. [`ManagerError::Revert`] or [`ManagerError::Halt`] is returned if the constructor fails and [`ManagerError::Rejected`] if the EVM rejects the deployment.
+    pub fn deploy<T: Tokenize>(
+        &mut self,
+        deployer: &str,
+        contract: &SimulationContract<NotDeployed>,
+        constructor_arguments: T,
+    ) -> Result<SimulationContract<IsDeployed>, ManagerError> {
+        let deployer = match self.agents.get(deployer) {
+            Some(deployer) => deployer,
+            None => return Err(ManagerError::AgentNotFound(deployer.to_string())),
+        };
+        contract.try_deploy(&mut self.environment, deployer, constructor_arguments)
+    }
+
+    /// Deploys a contract as an agent through `CREATE2`, so that its address only depends on the deployer, the salt and the init code, not on the deployer's nonce.
+    /// The address can be predicted with [`compute_create2_address`](crate::utils::compute_create2_address).
+    /// # Arguments
+    /// * `deployer` - The name of the agent deploying the contract.
+    /// * `salt` - The salt of the deployment.
+    /// * `bytecode` - The creation bytecode of the contract.
+    /// * `args` - The ABI encoded constructor arguments, which are appended to the bytecode. Empty if the constructor takes none.
+    /// # Returns
+    /// * `Ok(Address)` - The address of the deployed contract. Deploying the same init code with the same salt twice fails, since the address is taken.
+    pub fn deploy_create2(
+        &mut self,
+        deployer: &str,
+        salt: H256,
+        bytecode: Bytes,
+        args: Bytes,
+    ) -> Result<Address, ManagerError> {
+        let init_code = Bytes::from([bytecode, args].concat());
+        let mut tx =
+            self.find_agent(deployer)?
+                .build_call_transaction(B160::zero(), init_code, U256::ZERO);
+        tx.transact_to = TransactTo::Create(CreateScheme::Create2 {
+            salt: U256::from_be_bytes(salt.0),
+        });
+        match self.environment.execute(tx)? {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => Ok(address),
+            ExecutionResult::Success { .. } => Err(ManagerError::Other(
+                "The deployment succeeded without creating a contract.".to_string(),
+            )),
+            execution_result => Err(self.unpack_execution(execution_result).unwrap_err()),
+        }
+    }
+
+    /// Calls a function of a deployed contract as an agent and returns the full [`ExecutionResult`].
+    /// Unlike [`SimulationManager::unpack_execution`], this keeps the gas used, the emitted logs, and whether the call succeeded, reverted, or halted.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `contract` - The deployed contract to call.
+    /// * `function_name` - The name of the function to call.
+    /// * `args` - The arguments of the function.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The full result of the call.
+    pub fn call_with_result<T: Tokenize>(
+        &mut self,
+        caller: &str,
+        contract: &SimulationContract<IsDeployed>,
+        function_name: &str,
+        args: T,
+    ) -> Result<ExecutionResult, ManagerError> {
+        let call_data = contract.encode_function(function_name, args)?;
+        let tx = self.find_agent(caller)?.build_call_transaction(
+            contract.address,
+            call_data,
+            U256::ZERO,
+        );
+        Ok(self.environment.execute(tx)?)
+    }
+
+    /// Sends a call from an agent and decodes its output into a Rust type, e.g., the return types generated in the `bindings` crate.
+    /// Outputs with several values decode into tuples or generated return structs.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `calldata` - The calldata of the call.
+    /// # Returns
+    /// * `Ok(D)` - The decoded output. An error is returned if the call fails or its output does not decode into `D`.
+    pub fn call<D: Tokenizable + AbiType>(
+        &mut self,
+        caller: &str,
+        to: B160,
+        calldata: Bytes,
+    ) -> Result<D, ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, calldata, U256::ZERO);
+        let execution_result = self.environment.execute(tx)?;
+        let output = self.unpack_execution(execution_result)?;
+
+        // Several return values are encoded like the components of a tuple, not like a single tuple.
+        let (param_types, is_tuple) = match D::param_type() {
+            ParamType::Tuple(param_types) => (param_types, true),
+            param_type => (vec![param_type], false),
+        };
+        let decode_error = |err: &dyn Display| {
+            ManagerError::Abi(format!("Failed to decode the call output: {}", err))
+        };
+        let mut tokens = abi::decode(&param_types, &output).map_err(|err| decode_error(&err))?;
+        let token = if is_tuple {
+            Token::Tuple(tokens)
+        } else {
+            tokens.remove(0)
+        };
+        D::from_token(token).map_err(|err| decode_error(&err))
+    }
+
+    /// Sends a call from an agent with ETH attached, e.g., to wrap ETH or fund a vault.
+    /// The value is debited from the caller and credited to the callee unless the call reverts.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `call_data` - The calldata of the call.
+    /// * `value` - The ETH attached to the call in wei.
+    /// # Returns
+    /// * `Ok(Bytes)` - The raw bytes of the output. [`ManagerError::Rejected`] is returned if the caller cannot pay for the value and the gas.
+    pub fn call_with_value(
+        &mut self,
+        caller: &str,
+        to: B160,
+        call_data: Bytes,
+        value: U256,
+    ) -> Result<Bytes, ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, call_data, value);
+        let execution_result = self.environment.execute(tx)?;
+        self.unpack_execution(execution_result)
+    }
+
+    /// Sends a read-only call from an agent, e.g., to probe a pool's price many times per block.
+    /// The state changes of the call are discarded, so the caller's nonce is not consumed and nothing is recorded in the history.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `calldata` - The calldata of the call.
+    /// # Returns
+    /// * `Ok(Bytes)` - The raw bytes of the output.
+    pub fn call_static(
+        &mut self,
+        caller: &str,
+        to: Address,
+        calldata: Bytes,
+    ) -> Result<Bytes, ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, calldata, U256::ZERO);
+        match self.environment.execute_static(tx) {
+            Ok(execution_result) => self.unpack_execution(execution_result),
+            Err(err) => Err(ManagerError::Rejected(format!(
+                "The call was rejected: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// Sends several calls from an agent one after the other like an on-chain Multicall, e.g., to read `token0`, `token1` and the reserves of a pool in one step.
+    /// Every call sees the state left by the calls before it. The batch stops at the first call that fails and the calls before it stay committed, so take a [`SimulationManager::snapshot`] first if the batch has to be all-or-nothing.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the calls.
+    /// * `calls` - The `(to, calldata)` pairs of the calls in order.
+    /// # Returns
+    /// * `Ok(Vec<Bytes>)` - The raw bytes of the output of each call in order.
+    pub fn batch_call(
+        &mut self,
+        caller: &str,
+        calls: Vec<(Address, Bytes)>,
+    ) -> Result<Vec<Bytes>, ManagerError> {
+        let mut outputs = Vec::with_capacity(calls.len());
+        for (to, calldata) in calls {
+            let tx = self
+                .find_agent(caller)?
+                .build_call_transaction(to, calldata, U256::ZERO);
+            let execution_result = match self.environment.execute(tx) {
+                Ok(execution_result) => execution_result,
+                Err(err) => {
+                    return Err(ManagerError::Rejected(format!(
+                        "The transaction was rejected: {:?}",
+                        err
+                    )))
+                }
+            };
+            outputs.push(self.unpack_execution(execution_result)?);
+        }
+        Ok(outputs)
+    }
+
+    /// Sends a call from an agent and captures the return data of every call it makes along the way, e.g., to see why an inner call of an arbitrage path reverted.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `call_data` - The calldata of the call.
+    /// # Returns
+    /// * `Ok((ExecutionResult, ReturnDataTracker))` - The full result of the call and the return data of each call.
+    pub fn call_with_return_data(
+        &mut self,
+        caller: &str,
+        to: B160,
+        call_data: Bytes,
+    ) -> Result<(ExecutionResult, ReturnDataTracker), ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, call_data, U256::ZERO);
+        let mut return_data = ReturnDataTracker::default();
+        let execution_result = self
+            .environment
+            .execute_with_inspector(tx, &mut return_data)?;
+        Ok((execution_result, return_data))
+    }
+
+    /// Sends a call from an agent while a revm [`Inspector`] observes the EVM, e.g., a [`CallTracer`](crate::inspector::CallTracer) that records the call frames, their gas and the storage accesses.
+    /// Pass the inspector by `&mut` to read what it recorded afterwards.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `call_data` - The calldata of the call.
+    /// * `inspector` - The inspector that observes the execution.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The full result of the call.
+    pub fn call_with_inspector<INSP: Inspector<CacheDB<ForkDB>>>(
+        &mut self,
+        caller: &str,
+        to: B160,
+        call_data: Bytes,
+        inspector: INSP,
+    ) -> Result<ExecutionResult, ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, call_data, U256::ZERO);
+        Ok(self.environment.execute_with_inspector(tx, inspector)?)
+    }
+
+    /// Sends a call from an agent and profiles the gas used by the precompiles it calls, by precompile address.
+    /// # Arguments
+    /// * `caller` - The name of the agent making the call.
+    /// * `to` - The address the call is sent to.
+    /// * `call_data` - The calldata of the call.
+    /// # Returns
+    /// * `Ok((ExecutionResult, PrecompileGasProfiler))` - The full result of the call and the gas used by each precompile.
+    pub fn call_with_precompile_profile(
+        &mut self,
+        caller: &str,
+        to: B160,
+        call_data: Bytes,
+    ) -> Result<(ExecutionResult, PrecompileGasProfiler), ManagerError> {
+        let tx = self
+            .find_agent(caller)?
+            .build_call_transaction(to, call_data, U256::ZERO);
+        let mut profiler = PrecompileGasProfiler::default();
+        let execution_result = self.environment.execute_with_inspector(tx, &mut profiler)?;
+        Ok((execution_result, profiler))
+    }
+
+    /// Sends a transaction from an agent with an explicit nonce.
+    /// The transaction is rejected if its nonce does not match the agent's account nonce, e.g., when two transactions reuse a nonce.
+    /// # Arguments
+    /// * `sender` - The name of the agent sending the transaction.
+    /// * `to` - The address the transaction is sent to.
+    /// * `call_data` - The calldata of the transaction.
+    /// * `nonce` - The nonce of the transaction.
+    /// # Returns
+    /// * `Ok(Bytes)` - The raw bytes of the output.
+    pub fn send_transaction(
+        &mut self,
+        sender: &str,
+        to: B160,
+        call_data: Bytes,
+        nonce: u64,
+    ) -> Result<Bytes, ManagerError> {
+        let execution_result = self.send_transaction_with_result(sender, to, call_data, nonce)?;
+        self.unpack_execution(execution_result)
+    }
+
+    /// Sends a transaction from an agent with an explicit nonce like [`SimulationManager::send_transaction`] and returns the full [`ExecutionResult`].
+    /// # Arguments
+    /// * `sender` - The name of the agent sending the transaction.
+    /// * `to` - The address the transaction is sent to.
+    /// * `call_data` - The calldata of the transaction.
+    /// * `nonce` - The nonce of the transaction.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The full result of the transaction.
+    pub fn send_transaction_with_result(
+        &mut self,
+        sender: &str,
+        to: B160,
+        call_data: Bytes,
+        nonce: u64,
+    ) -> Result<ExecutionResult, ManagerError> {
+        let mut tx = self
+            .find_agent(sender)?
+            .build_call_transaction(to, call_data, U256::ZERO);
+        tx.nonce = Some(nonce);
+        match self.environment.execute(tx) {
+            Ok(execution_result) => Ok(execution_result),
+            Err(EVMError::Transaction(InvalidTransaction::NonceTooLow { tx, state })) => {
+                Err(ManagerError::Rejected(format!(
+                    "Nonce collision: nonce {} was already used by {}, whose nonce is {}.",
+                    tx, sender, state
+                )))
+            }
+            Err(err) => Err(ManagerError::Rejected(format!(
+                "The transaction was rejected: {:?}",
+                err
+            ))),
+        }
+    }
+
+    /// Looks up an active agent by name, failing with [`ManagerError::AgentNotFound`] if there is none.
+    fn find_agent(&self, name: &str) -> Result<&AgentType<IsActive>, ManagerError> {
+        match self.get_agent(name) {
+            Some(agent) => Ok(agent),
+            None => Err(ManagerError::AgentNotFound(name.to_string())),
+        }
+    }
+
+    /// Replaces the gas settings of an active agent, e.g., to have the admin pay a gas price once a base fee is set.
+    /// # Arguments
+    /// * `name` - Name of the agent.
+    /// * `transact_settings` - The gas limit, gas price and priority fee of the agent's transactions from now on.
+    /// # Returns
+    /// * `Result<(), ManagerError>` - [`ManagerError::AgentNotFound`] if there is no active agent with that name.
+    pub fn set_transact_settings(
+        &mut self,
+        name: &str,
+        transact_settings: TransactSettings,
+    ) -> Result<(), ManagerError> {
+        let settings = match self.agents.get_mut(name) {
+            Some(AgentType::User(user)) => &mut user.transact_settings,
+            Some(AgentType::SimpleArbitrageur(simple_arbitrageur)) => {
+                &mut simple_arbitrageur.transact_settings
+            }
+            Some(AgentType::PriceFeed(price_feed)) => &mut price_feed.transact_settings,
+            None => return Err(ManagerError::AgentNotFound(name.to_string())),
+        };
+        *settings = transact_settings;
+        Ok(())
+    }
+
+    /// Activates an agent that owns a signing key, placing it at the address of the key.
+    /// # Arguments
+    /// * `new_agent` - The agent to be added to the collection of agents.
+    /// * `signer` - The signing key of the agent.
+    pub fn activate_agent_with_signer(
+        &mut self,
+        new_agent: AgentType<NotActive>,
+        signer: LocalWallet,
+    ) -> Result<(), ManagerError> {
+        let name = new_agent.inner().name();
+        self.activate_agent(new_agent, B160::from(signer.address().0))?;
+        self.signers.insert(name, signer);
+        Ok(())
+    }
+
+    /// Generates a signing key from the simulation's random number generator, e.g., for [`SimulationManager::activate_agent_with_signer`]. The keys are reproducible for managers created with [`SimulationManager::new_with_seed`].
+    /// The key signs for the chain id of the simulation.
+    pub fn generate_signer(&mut self) -> LocalWallet {
+        let chain_id = self.chain_id();
+        LocalWallet::new(&mut self.rng).with_chain_id(chain_id)
+    }
+
+    /// Returns the chain id of the simulation, see [`SimulationManager::with_chain_id`].
+    pub fn chain_id(&self) -> u64 {
+        self.environment.evm.env.cfg.chain_id.to::<u64>()
+    }
+
+    /// Returns the random number generator of the simulation, e.g., to seed a [`NoiseModel`](crate::stochastic::noise::NoiseModel) or to sample from a distribution.
+    /// Drawing all randomness from it keeps runs of a manager created with [`SimulationManager::new_with_seed`] reproducible.
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Returns the signing key of an agent if it was activated with one.
+    pub fn signer(&self, name: &str) -> Option<&LocalWallet> {
+        self.signers.get(name)
+    }
+
+    /// Produces an EIP-2612 `permit` signature with the signing key of the token owner.
+    /// The token's `DOMAIN_SEPARATOR` and the owner's current nonce are read from the simulation with static calls, so signing leaves the state untouched.
+    /// # Arguments
+    /// * `owner` - The name of the agent that owns the tokens and signs the permit.
+    /// * `token` - The deployed token contract that implements EIP-2612.
+    /// * `spender` - The address that is allowed to spend the tokens.
+    /// * `value` - The amount of tokens the spender is allowed to spend.
+    /// * `deadline` - The timestamp after which the signature is no longer valid.
+    /// # Returns
+    /// * `Ok(Signature)` - The signature to be passed to the token's `permit` function.
+    pub fn sign_permit(
+        &mut self,
+        owner: &str,
+        token: &SimulationContract<IsDeployed>,
+        spender: B160,
+        value: ethers::types::U256,
+        deadline: ethers::types::U256,
+    ) -> Result<Signature, ManagerError> {
+        let owner_address = self.find_agent(owner)?.address();
+        let signer = match self.signers.get(owner) {
+            Some(signer) => signer.clone(),
+            None => {
+                return Err(ManagerError::Other(format!(
+                    "Agent with name {} has no signing key.",
+                    owner
+                )))
+            }
+        };
+
+        let call_data = token.encode_function("DOMAIN_SEPARATOR", ())?;
+        let output = self.call_static(owner, token.address, call_data)?;
+        let domain_separator: H256 = token.decode_output("DOMAIN_SEPARATOR", output)?;
+
+        let call_data = token.encode_function("nonces", recast_address(owner_address))?;
+        let output = self.call_static(owner, token.address, call_data)?;
+        let nonce: ethers::types::U256 = token.decode_output("nonces", output)?;
+
+        let digest = permit_digest(
+            domain_separator,
+            recast_address(owner_address),
+            recast_address(spender),
+            value,
+            nonce,
+            deadline,
+        );
+        signer
+            .sign_hash(digest)
+            .map_err(|err| ManagerError::Other(format!("Failed to sign the permit: {}", err)))
+    }
+
+    /// Saves the info and storage of only the given accounts, which is cheaper than a snapshot of the full state.
+    /// Reverting to a partial snapshot is only safe if the actions in between touched no other accounts, since those are left as they are.
+    /// # Arguments
+    /// * `accounts` - The addresses of the accounts to save.
+    /// # Returns
+    /// * `PartialSnapshot` - The saved accounts, to be restored with [`SimulationManager::revert_accounts`].
+    pub fn snapshot_accounts(&self, accounts: &[B160]) -> PartialSnapshot {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        PartialSnapshot {
+            accounts: accounts
+                .iter()
+                .map(|address| (*address, db.accounts.get(address).cloned()))
+                .collect(),
+        }
+    }
+
+    /// Saves the full chain state, i.e., every account, its storage, the deployed bytecode and the block environment, e.g., to try out several actions from the same starting point.
+    /// Only chain state is saved. Agent state such as the agents' event receivers, their cached prices, the mempool and the recorded log and transaction history is left as it is on revert.
+    /// # Returns
+    /// * `SnapshotId` - The identifier to pass to [`SimulationManager::revert`].
+    pub fn snapshot(&mut self) -> SnapshotId {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        let id = self.next_snapshot_id;
+        self.next_snapshot_id += 1;
+        self.snapshots.insert(
+            id,
+            ChainSnapshot {
+                accounts: db.accounts.clone(),
+                contracts: db.contracts.clone(),
+                block_hashes: db.block_hashes.clone(),
+                block: self.environment.evm.env.block.clone(),
+            },
+        );
+        SnapshotId(id)
+    }
+
+    /// Restores the chain state saved in a snapshot.
+    /// Like `evm_revert`, the snapshot and every snapshot taken after it are used up, so take a new snapshot to revert to the same state again.
+    /// # Arguments
+    /// * `id` - The snapshot to restore.
+    /// # Returns
+    /// * `Result<(), ManagerError>` - An error if the snapshot does not exist or was already used up.
+    pub fn revert(&mut self, id: SnapshotId) -> Result<(), ManagerError> {
+        let snapshot = match self.snapshots.remove(&id.0) {
+            Some(snapshot) => snapshot,
+            None => {
+                return Err(ManagerError::Other(format!(
+                    "Snapshot {} does not exist or was already reverted to.",
+                    id.0
+                )))
+            }
+        };
+        // The snapshots taken after it are used up along with it.
+        self.snapshots.split_off(&id.0);
+        let db = self.environment.evm.db().unwrap(); // The environment always has a database.
+        db.accounts = snapshot.accounts;
+        db.contracts = snapshot.contracts;
+        db.block_hashes = snapshot.block_hashes;
+        self.environment.evm.env.block = snapshot.block;
+        Ok(())
+    }
+
+    /// Builds a Merkle-Patricia proof of an account and some of its storage slots against the current state root, mirroring `eth_getProof`.
+    /// # Arguments
+    /// * `address` - The address of the account.
+    /// * `slots` - The storage slots to prove.
+    /// # Returns
+    /// * `StateProof` - The proof, which can be checked with [`StateProof::verify`].
+    pub fn state_proof(&self, address: B160, slots: &[U256]) -> StateProof {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        StateProof::new(db, address, slots)
+    }
+
+    /// Restores the accounts saved in a [`PartialSnapshot`]. Accounts that did not exist at the time of the snapshot are removed.
+    /// # Arguments
+    /// * `snapshot` - The snapshot to restore.
+    pub fn revert_accounts(&mut self, snapshot: PartialSnapshot) {
+        let db = self.environment.evm.db().unwrap(); // The environment always has a database.
+        for (address, account) in snapshot.accounts {
+            match account {
+                Some(account) => {
+                    db.accounts.insert(address, account);
+                }
+                None => {
+                    db.accounts.remove(&address);
+                }
+            }
+        }
+    }
+
+    /// Queues a transaction from an agent in the mempool. It is executed once a block that includes it is built with [`SimulationManager::build_block`].
+    /// The transaction takes the agent's next nonce, counting the transactions it already has pending, so its pending transactions execute in submission order.
+    /// # Arguments
+    /// * `sender` - The name of the agent sending the transaction.
+    /// * `to` - The address the transaction is sent to.
+    /// * `call_data` - The calldata of the transaction.
+    /// * `value` - The value sent with the transaction.
+    pub fn submit_transaction(
+        &mut self,
+        sender: &str,
+        to: B160,
+        call_data: Bytes,
+        value: U256,
+    ) -> Result<(), ManagerError> {
+        let tx = self
+            .find_agent(sender)?
+            .build_call_transaction(to, call_data, value);
+        let account_nonce = self.nonce_of(sender)?;
+        self.mempool
+            .submit(sender.to_string(), tx, account_nonce)
+            .map_err(ManagerError::Rejected)?;
+        Ok(())
+    }
+
+    /// Replaces the policy that decides which pending transactions go into a block and in which order, e.g., with a custom builder algorithm.
+    /// # Arguments
+    /// * `policy` - The ordering policy.
+    pub fn set_ordering_policy(&mut self, policy: OrderingPolicy) {
+        self.mempool.policy = policy;
+    }
+
+    /// Builds a block from the mempool: executes the transactions the ordering policy picks in its order and then moves to the next block.
+    /// Transactions the EVM rejects, e.g., because the sender cannot pay for their gas, are dropped from the block and recorded in [`Mempool::rejected`].
+    /// # Returns
+    /// * `Ok(Vec<ExecutionResult>)` - The execution results of the executed transactions in inclusion order.
+    pub fn build_block(&mut self) -> Result<Vec<ExecutionResult>, ManagerError> {
+        let block = self.mempool.take_block().map_err(|err| {
+            ManagerError::Other(format!(
+                "The ordering policy returned an invalid order: {}",
+                err
+            ))
+        })?;
+        let mut execution_results = Vec::with_capacity(block.len());
+        for pending_tx in block {
+            match self.environment.execute(pending_tx.tx.clone()) {
+                Ok(execution_result) => execution_results.push(execution_result),
+                Err(err) => self.mempool.rejected.push(RejectedTx {
+                    pending_tx,
+                    block_number: self.block_number(),
+                    reason: format!("{:?}", err),
+                }),
+            }
+        }
+        self.advance_block(1);
+        Ok(execution_results)
+    }
+
+    /// Returns the number of the block the simulation is currently in.
+    pub fn block_number(&self) -> u64 {
+        self.environment.evm.env.block.number.to::<u64>()
+    }
+
+    /// Moves the simulation forward by a number of blocks. Transactions executed afterwards observe the new `block.number` and `block.timestamp`.
+    /// Every TWAP oracle accrues its price up to the new timestamp and the database is brought back under its memory cap, if any.
+    /// # Arguments
+    /// * `blocks` - The number of blocks to move forward.
+    pub fn advance_block(&mut self, blocks: u64) {
+        self.environment.advance_block(blocks);
+        self.accumulate_twap_oracles();
+        self.enforce_db_memory_cap();
+    }
+
+    /// Sets the `block.timestamp` that transactions observe from now on, e.g., to jump past a vesting cliff or accrue a funding rate.
+    /// Every TWAP oracle accrues its price up to the new timestamp. Moving the timestamp backwards is allowed but the oracles ignore it.
+    /// # Arguments
+    /// * `timestamp` - The new timestamp in seconds.
+    pub fn set_timestamp(&mut self, timestamp: u64) {
+        self.environment.set_timestamp(timestamp);
+        self.accumulate_twap_oracles();
+    }
+
+    /// Sets the `block.basefee` that transactions pay from now on, see [`SimulationEnvironment::set_base_fee`].
+    /// The EVM rejects transactions whose gas price is below the base fee. The admin sends free transactions by default, so raise its gas price with [`SimulationManager::set_transact_settings`] before sending transactions on its behalf.
+    /// # Arguments
+    /// * `base_fee` - The base fee per gas in wei.
+    pub fn set_base_fee(&mut self, base_fee: U256) {
+        self.environment.set_base_fee(base_fee);
+    }
+
+    /// Accrues the price of every TWAP oracle up to the current timestamp.
+    fn accumulate_twap_oracles(&mut self) {
+        let timestamp = self.environment.evm.env.block.timestamp.to::<u64>();
+        for oracle in self.twap_oracles.values_mut() {
+            oracle.accumulate(timestamp);
+        }
+    }
+
+    /// Retrieves an active agent as its concrete type, e.g., to read the state of a specific strategy.
+    /// # Arguments
+    /// * `name` - Name of the agent.
+    /// # Returns
+    /// * `Option<&A>` - The agent, or `None` if there is no agent with that name or it is of another type.
+    pub fn agent_as<A: Agent + 'static>(&self, name: &str) -> Option<&A> {
+        self.agents.get(name)?.as_any().downcast_ref::<A>()
+    }
+
+    /// Starts a TWAP oracle that observes from the current block onward.
+    /// # Arguments
+    /// * `name` - Name of the oracle, e.g., the pool it tracks.
+    /// * `price` - The initial price.
+    pub fn add_twap_oracle(&mut self, name: &str, price: f64) {
+        let timestamp = self.environment.evm.env.block.timestamp.to::<u64>();
+        self.twap_oracles
+            .insert(name.to_string(), TwapOracle::new(price, timestamp));
+    }
+
+    /// Reports the throughput of the simulation engine, i.e., how many transactions and how much gas it executed per second of wall-clock execution time.
+    /// Only the time spent inside the EVM is counted, so the agents' own logic does not skew the numbers.
+    /// The report also flags the agents that never sent a transaction.
+    pub fn report(&self) -> SimulationReport {
+        let transactions = self.environment.transactions_executed;
+        let gas_used = self.environment.gas_executed;
+        let execution_time = self.environment.execution_time;
+        let seconds = execution_time.as_secs_f64();
+        let per_second = |amount: u64| {
+            if seconds > 0.0 {
+                amount as f64 / seconds
+            } else {
+                0.0
+            }
+        };
+        SimulationReport {
+            transactions,
+            gas_used,
+            execution_time,
+            transactions_per_second: per_second(transactions),
+            gas_per_second: per_second(gas_used),
+            idle_agents: self.idle_agents(),
+        }
+    }
+
+    /// Finds the agents that never sent a transaction along with the last event each of them observed.
+    fn idle_agents(&self) -> Vec<IdleAgent> {
+        let senders: HashSet<B160> = self
+            .environment
+            .transaction_history
+            .iter()
+            .map(|transaction| B160::from(transaction.sender.0))
+            .collect();
+        let logs: Vec<Log> = self
+            .environment
+            .log_history
+            .iter()
+            .map(|indexed_log| indexed_log.log.clone())
+            .collect();
+        let mut idle_agents: Vec<IdleAgent> = self
+            .agents
+            .iter()
+            .filter(|(_, agent)| !senders.contains(&agent.inner().address()))
+            .map(|(name, agent)| IdleAgent {
+                name: name.clone(),
+                last_observed_event: filter_events(agent.inner().event_filters(), logs.clone())
+                    .pop(),
+            })
+            .collect();
+        idle_agents.sort_by(|a, b| a.name.cmp(&b.name));
+        idle_agents
+    }
+
+    /// Exports every account of the simulated state, including balances, nonces, code and storage.
+    /// # Arguments
+    /// * `format` - The serialization format, e.g., JSON for debugging or a binary format for large states.
+    /// # Returns
+    /// * `Ok(Vec<u8>)` - The serialized state.
+    pub fn export_state(&self, format: StateFormat) -> Result<Vec<u8>, ManagerError> {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        StateDump::from_db(db).encode(format).map_err(|err| {
+            ManagerError::Other(format!(
+                "Failed to export the state as {:?}: {}",
+                format, err
+            ))
+        })
+    }
+
+    /// Imports a state exported with [`SimulationManager::export_state`], replacing accounts that already exist at the same address.
+    /// # Arguments
+    /// * `bytes` - The serialized state.
+    /// * `format` - The serialization format the state was exported with.
+    pub fn import_state(&mut self, bytes: &[u8], format: StateFormat) -> Result<(), ManagerError> {
+        let state_dump = StateDump::decode(bytes, format).map_err(|err| {
+            ManagerError::Other(format!(
+                "Failed to import the state as {:?}: {}",
+                format, err
+            ))
+        })?;
+        state_dump.into_db(self.environment.evm.db().unwrap()); // The environment always has a database.
+        Ok(())
+    }
+
+    /// Saves the simulation to a file, i.e., the block and configuration environment, the agent roster with its gas settings and every account with its balance, nonce, code and storage.
+    /// The file is JSON with sorted keys, so two saves can be diffed or shared. Event filters, the mempool and the recorded history are not saved.
+    /// # Arguments
+    /// * `path` - The file to write.
+    pub fn save(&self, path: &Path) -> Result<(), ManagerError> {
+        let mut agents: Vec<AgentDump> = self
+            .agents
+            .iter()
+            .map(|(name, agent)| AgentDump {
+                name: name.clone(),
+                address: recast_address(agent.inner().address()),
+                kind: match agent {
+                    AgentType::User(_) => AgentKind::User,
+                    AgentType::SimpleArbitrageur(_) => AgentKind::SimpleArbitrageur,
+                    AgentType::PriceFeed(_) => AgentKind::PriceFeed,
+                },
+                gas_limit: agent.inner().transact_settings().gas_limit,
+                gas_price: recast_u256(agent.inner().transact_settings().gas_price),
+                gas_priority_fee: agent
+                    .inner()
+                    .transact_settings()
+                    .gas_priority_fee
+                    .map(recast_u256),
+                price_feed: match agent {
+                    AgentType::PriceFeed(price_feed) => Some(PriceFeedDump {
+                        exchange: recast_address(price_feed.exchange),
+                        prices: price_feed.prices.clone(),
+                        next: price_feed.next,
+                    }),
+                    _ => None,
+                },
+            })
+            .collect();
+        agents.sort_by(|a, b| a.name.cmp(&b.name));
+        let env = &self.environment.evm.env;
+        let save = SimulationSave {
+            block_number: self.block_number(),
+            timestamp: env.block.timestamp.to::<u64>(),
+            block: BlockDump {
+                coinbase: recast_address(env.block.coinbase),
+                difficulty: recast_u256(env.block.difficulty),
+                prevrandao: env
+                    .block
+                    .prevrandao
+                    .map(|prevrandao| H256::from(prevrandao.0)),
+                base_fee: recast_u256(env.block.basefee),
+                gas_limit: recast_u256(env.block.gas_limit),
+            },
+            config: ConfigDump {
+                chain_id: env.cfg.chain_id.to::<u64>(),
+                spec_id: env.cfg.spec_id as u8,
+                limit_contract_code_size: env.cfg.limit_contract_code_size,
+            },
+            agents,
+            state: StateDump::from_db(self.environment.evm.db.as_ref().unwrap()), // The environment always has a database.
+        };
+        let bytes = serde_json::to_vec_pretty(&save).map_err(|err| {
+            ManagerError::Other(format!("Failed to save the simulation: {}", err))
+        })?;
+        fs::write(path, bytes)
+            .map_err(|err| ManagerError::Other(format!("Failed to save the simulation: {}", err)))
+    }
+
+    /// Loads a simulation saved with [`SimulationManager::save`] into a new manager. The agents are activated again without event filters.
+    /// # Arguments
+    /// * `path` - The file to read.
+    pub fn load(path: &Path) -> Result<Self, ManagerError> {
+        let load_error = |err: &dyn Display| {
+            ManagerError::Other(format!("Failed to load the simulation: {}", err))
+        };
+        let bytes = fs::read(path).map_err(|err| load_error(&err))?;
+        let save: SimulationSave =
+            serde_json::from_slice(&bytes).map_err(|err| load_error(&err))?;
+
+        let spec_id = SpecId::try_from_u8(save.config.spec_id).ok_or_else(|| {
+            load_error(&format!("the spec id {} is unknown", save.config.spec_id))
+        })?;
+        let mut manager = Self::new();
+        let env = &mut manager.environment.evm.env;
+        env.block.number = U256::from(save.block_number);
+        env.block.timestamp = U256::from(save.timestamp);
+        env.block.coinbase = B160::from(save.block.coinbase.0);
+        env.block.difficulty = recast_ethers_u256(save.block.difficulty);
+        env.block.prevrandao = save
+            .block
+            .prevrandao
+            .map(|prevrandao| B256::from(prevrandao.0));
+        env.block.basefee = recast_ethers_u256(save.block.base_fee);
+        env.block.gas_limit = recast_ethers_u256(save.block.gas_limit);
+        env.cfg.chain_id = U256::from(save.config.chain_id);
+        env.cfg.spec_id = spec_id;
+        env.cfg.limit_contract_code_size = save.config.limit_contract_code_size;
+        for agent in save.agents {
+            let transact_settings = TransactSettings {
+                gas_limit: agent.gas_limit,
+                gas_price: recast_ethers_u256(agent.gas_price),
+                gas_priority_fee: agent.gas_priority_fee.map(recast_ethers_u256),
+            };
+            // The admin is always active.
+            if agent.name == "admin" {
+                manager.set_transact_settings(&agent.name, transact_settings)?;
+                continue;
+            }
+            let name = agent.name.clone();
+            let new_agent = match agent.kind {
+                AgentKind::User => AgentType::User(User::new(agent.name, None)),
+                AgentKind::SimpleArbitrageur => {
+                    AgentType::SimpleArbitrageur(SimpleArbitrageur::new(agent.name, vec![]))
+                }
+                AgentKind::PriceFeed => {
+                    let dump = agent.price_feed.ok_or_else(|| {
+                        ManagerError::Other(format!(
+                            "Failed to load the simulation: the price feed {} has no price path.",
+                            agent.name
+                        ))
+                    })?;
+                    let mut price_feed =
+                        PriceFeed::new(agent.name, B160::from(dump.exchange.0), dump.prices);
+                    price_feed.next = dump.next;
+                    AgentType::PriceFeed(price_feed)
+                }
+            };
+            manager.activate_agent(new_agent, B160::from(agent.address.0))?;
+            manager.set_transact_settings(&name, transact_settings)?;
+        }
+        // Activating an agent resets its account, so the accounts are restored last.
+        save.state.into_db(manager.environment.evm.db().unwrap()); // The environment always has a database.
+        Ok(manager)
+    }
+
+    /// Exports every transaction executed in the simulation so far along with its receipt as a replayable [`TraceFile`].
+    pub fn export_trace(&self) -> TraceFile {
+        TraceFile {
+            transactions: self.environment.transaction_history.clone(),
+        }
+    }
+
+    /// Loads a base state and re-executes the transactions of a [`TraceFile`] in order on top of it.
+    /// The base state is the state the trace started from, e.g., decoded from [`SimulationManager::export_state`] or the `state` of a [`SimulationSave`]. Its accounts replace accounts that already exist at the same address.
+    /// Fails at the first transaction whose receipt differs from the recorded one, since the replay no longer reproduces the trace from there on.
+    /// # Arguments
+    /// * `trace` - The trace to replay.
+    /// * `base_state` - The state the trace started from.
+    pub fn import_and_replay(
+        &mut self,
+        trace: &TraceFile,
+        base_state: StateDump,
+    ) -> Result<(), ManagerError> {
+        base_state.into_db(self.environment.evm.db().unwrap()); // The environment always has a database.
+        for (index, transaction) in trace.transactions.iter().enumerate() {
+            let execution_result = match self.environment.execute(transaction.tx_env()) {
+                Ok(execution_result) => execution_result,
+                Err(err) => {
+                    return Err(ManagerError::Rejected(format!(
+                        "Transaction {} of the trace was rejected: {:?}",
+                        index, err
+                    )))
+                }
+            };
+            if TraceReceipt::from(&execution_result) != transaction.receipt {
+                return Err(ManagerError::Other(format!(
+                    "Replay diverged from the trace at transaction {}.",
+                    index
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reproduces a transaction in the exact context of a historical block: the state is forked at the start of the block,
+    /// the transactions that precede the target in the block are applied, and then the target is executed.
+    /// The block's context (number, timestamp, coinbase, base fee, ...) replaces the current block environment.
+    /// # Arguments
+    /// * `provider` - The node to fork from. It needs to serve `debug_traceTransaction` and `debug_traceCall` for the block.
+    /// * `tx` - The raw signed transaction to reproduce.
+    /// * `block` - The number of the block to reproduce the transaction in.
+    /// * `tx_index` - The position of the transaction within the block. The transactions before it are applied first.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The result of the transaction in its historical context.
+    pub async fn simulate_tx_at_block<M: Middleware>(
+        &mut self,
+        provider: &M,
+        tx: Bytes,
+        block: u64,
+        tx_index: usize,
+    ) -> Result<ExecutionResult, ManagerError> {
+        let fork_error = |message: String| {
+            ManagerError::Other(format!("Could not fork block {}: {}", block, message))
+        };
+        let mut target: Transaction =
+            rlp::decode(&tx).map_err(|err| fork_error(err.to_string()))?;
+        target.from = target
+            .recover_from()
+            .map_err(|err| fork_error(err.to_string()))?;
+        let historic_block = provider
+            .get_block_with_txs(block)
+            .await
+            .map_err(|err| fork_error(err.to_string()))?
+            .ok_or_else(|| fork_error("the block does not exist".to_string()))?;
+        let preceding = historic_block.transactions.get(..tx_index).ok_or_else(|| {
+            fork_error(format!(
+                "the block has fewer than {} transactions",
+                tx_index
+            ))
+        })?;
+
+        let state = fork::block_start_state(provider, block, preceding, &target)
+            .await
+            .map_err(fork_error)?;
+        self.load_fork_state(state);
+        self.environment.evm.env.block = fork::block_env(&historic_block);
+
+        for (index, transaction) in preceding.iter().enumerate() {
+            if let Err(err) = self.environment.execute(fork::tx_env(transaction)) {
+                return Err(fork_error(format!(
+                    "transaction {} was rejected: {:?}",
+                    index, err
+                )));
+            }
+        }
+        let execution_result = self
+            .environment
+            .execute(fork::tx_env(&target))
+            .map_err(|err| fork_error(format!("the target transaction was rejected: {:?}", err)))?;
+        self.enforce_db_memory_cap();
+        Ok(execution_result)
+    }
+
+    /// Writes the prestate accounts of a block into the database.
+    /// They are not evictable, since the fork database cannot fetch them again at the state of that block.
+    /// # Arguments
+    /// * `state` - The fetched accounts.
+    pub(crate) fn load_fork_state(&mut self, state: StateDump) {
+        state.into_db(self.environment.evm.db().unwrap()); // The environment always has a database.
+    }
+
+    /// Returns the approximate number of bytes the database uses for accounts, storage and bytecode.
+    pub fn db_memory_estimate(&self) -> usize {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        let accounts: usize = db.accounts.values().map(account_memory_estimate).sum();
+        let contracts: usize = db
+            .contracts
+            .values()
+            .map(|code| size_of::<B256>() + size_of::<Bytecode>() + code.bytes().len())
+            .sum();
+        accounts + contracts
+    }
+
+    /// Caps the approximate memory of the database of a forked environment. Whenever the cap is exceeded, checked as blocks and steps advance,
+    /// the least recently used accounts fetched from the node are evicted until the database fits again. Only clean fork accounts are evicted,
+    /// i.e., accounts that no local transaction has written to, so agent accounts and local changes are never lost, and an evicted account is
+    /// fetched from the node again when it is next read. Without a node nothing can be fetched again, so nothing is evicted.
+    /// # Arguments
+    /// * `db_memory_cap` - The cap in bytes, or `None` to remove it.
+    pub fn set_db_memory_cap(&mut self, db_memory_cap: Option<usize>) {
+        self.db_memory_cap = db_memory_cap;
+        self.enforce_db_memory_cap();
+    }
+
+    /// Evicts the least recently used clean fork accounts until the database fits its memory cap or no such accounts are left.
+    fn enforce_db_memory_cap(&mut self) {
+        let fork_db = &self.environment.evm.db.as_ref().unwrap().db; // The environment always has a database.
+        if !fork_db.is_forked() {
+            return;
+        }
+        self.fork_accounts.extend(fork_db.take_fetched());
+        let Some(db_memory_cap) = self.db_memory_cap else {
+            return;
+        };
+        let mut estimate = self.db_memory_estimate();
+        while estimate > db_memory_cap {
+            let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+            let last_access = &self.environment.last_access;
+            let least_recently_used = self
+                .fork_accounts
+                .iter()
+                .filter(|address| {
+                    db.accounts.get(address).map_or(false, |account| {
+                        matches!(account.account_state, AccountState::None)
+                    })
+                })
+                .min_by_key(|address| (last_access.get(address).copied().unwrap_or(0), **address))
+                .copied();
+            let Some(address) = least_recently_used else {
+                break;
+            };
+            self.fork_accounts.remove(&address);
+            if let Some(account) = self.environment.evm.db().unwrap().accounts.remove(&address) {
+                estimate -= account_memory_estimate(&account);
+            }
+        }
+    }
+
+    /// Cheatcode that overwrites the balance of an account. The change is recorded in the [`SimulationManager::cheatcode_log`].
+    /// # Arguments
+    /// * `address` - The address of the account.
+    /// * `balance` - The new balance.
+    pub fn set_balance(&mut self, address: B160, balance: U256) {
+        self.record_cheatcode(Cheatcode::SetBalance { address, balance });
+        let db = self.environment.evm.db().unwrap(); // The environment always has a database.
+        let mut info = db
+            .accounts
+            .get(&address)
+            .map(|account| account.info.clone())
+            .unwrap_or_default();
+        info.balance = balance;
+        db.insert_account_info(address, info);
+    }
+
+    /// Sets the ETH balance of an agent, e.g., to start a well-capitalized arbitrageur. Like [`SimulationManager::set_balance`], the change is recorded in the [`SimulationManager::cheatcode_log`].
+    /// # Arguments
+    /// * `name` - The name of the agent.
+    /// * `amount` - The new balance in wei.
+    /// # Returns
+    /// * `Result<(), ManagerError>` - An error if there is no active agent with that name.
+    pub fn fund_agent(&mut self, name: &str, amount: U256) -> Result<(), ManagerError> {
+        let address = self.find_agent(name)?.address();
+        self.set_balance(address, amount);
+        Ok(())
+    }
+
+    /// Returns the ETH balance of an agent.
+    /// # Arguments
+    /// * `name` - The name of the agent.
+    /// # Returns
+    /// * `Result<U256, ManagerError>` - The balance in wei, or [`ManagerError::AgentNotFound`] if there is no active agent with that name.
+    pub fn balance_of(&self, name: &str) -> Result<U256, ManagerError> {
+        let address = self.find_agent(name)?.address();
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        Ok(db
+            .accounts
+            .get(&address)
+            .map_or(U256::ZERO, |account| account.info.balance))
+    }
+
+    /// Returns the account nonce of an agent, i.e., the nonce of its next transaction.
+    /// The EVM increments the nonce of the sender with every executed call or deployment, reverted ones included, so the address of the next `CREATE` deployment of an agent is determined by its address and this nonce.
+    /// # Arguments
+    /// * `name` - The name of the agent.
+    /// # Returns
+    /// * `Result<u64, ManagerError>` - The nonce, or [`ManagerError::AgentNotFound`] if there is no active agent with that name.
+    pub fn nonce_of(&self, name: &str) -> Result<u64, ManagerError> {
+        let address = self.find_agent(name)?.address();
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        Ok(db
+            .accounts
+            .get(&address)
+            .map_or(0, |account| account.info.nonce))
+    }
+
+    /// Returns the runtime code deployed at an address, e.g., to check that a deployment or a self-destruct went through.
+    /// Accounts of a forked environment that are not cached yet are fetched from the node.
+    /// # Arguments
+    /// * `addr` - The address of the account.
+    /// # Returns
+    /// * `Bytes` - The runtime code, empty if the account has no code.
+    /// # Panics
+    /// * If the environment is forked and the node fails to serve the account, like every read of a [`ForkDB`].
+    pub fn code_at(&self, addr: Address) -> Bytes {
+        // The environment always has a database.
+        let db = self.environment.evm.db.as_ref().unwrap();
+        // The fork database reports no errors, a failed fetch panics inside it.
+        let account_info = match db.basic(addr).unwrap() {
+            Some(account_info) => account_info,
+            None => return Bytes::new(),
+        };
+        if account_info.code_hash == KECCAK_EMPTY {
+            return Bytes::new();
+        }
+        match account_info.code {
+            Some(code) => code.original_bytes(),
+            None => db
+                .code_by_hash(account_info.code_hash)
+                .unwrap() // The fork database reports no errors.
+                .original_bytes(),
+        }
+    }
+
+    /// Returns the value of a storage slot of an account without calling the contract, e.g., to assert the liquidity a pool stores after a run.
+    /// Slots of a forked environment that are not cached yet are fetched from the node.
+    /// # Arguments
+    /// * `addr` - The address of the account.
+    /// * `slot` - The storage slot.
+    /// # Returns
+    /// * `U256` - The value of the slot, zero if it was never written.
+    /// # Panics
+    /// * If the environment is forked and the node fails to serve the slot, like every read of a [`ForkDB`].
+    pub fn storage_at(&self, addr: Address, slot: U256) -> U256 {
+        let db = self.environment.evm.db.as_ref().unwrap(); // The environment always has a database.
+        db.storage(addr, slot).unwrap() // The fork database reports no errors, a failed fetch panics inside it.
+    }
+
+    /// Cheatcode that overwrites a storage slot of an account. The change is recorded in the [`SimulationManager::cheatcode_log`].
+    /// # Arguments
+    /// * `address` - The address of the account.
+    /// * `slot` - The storage slot.
+    /// * `value` - The new value of the slot.
+    /// # Panics
+    /// * If the environment is forked and the node fails to serve an account that is not cached yet, like every read of a [`ForkDB`].
+    pub fn set_storage(&mut self, address: B160, slot: U256, value: U256) {
+        self.record_cheatcode(Cheatcode::SetStorage {
+            address,
+            slot,
+            value,
+        });
+        // The environment always has a database.
+        let db = self.environment.evm.db().unwrap();
+        // An account that is not cached yet is loaded from the backing database first, which may fetch it from a forked node.
+        // The fork database reports no errors, a failed fetch panics inside it.
+        db.insert_account_storage(address, slot, value).unwrap();
+    }
+
+    /// Cheatcode that overwrites the token balance of an account by writing the token's balance mapping directly, mirroring Foundry's `deal`.
+    /// The total supply is left untouched. The change is recorded in the [`SimulationManager::cheatcode_log`] as a storage write.
+    /// # Arguments
+    /// * `token` - The address of the ERC20 token.
+    /// * `to` - The account whose balance is set.
+    /// * `amount` - The new balance.
+    /// * `balance_slot` - The storage slot of the token's `mapping(address => uint256)` of balances, e.g., 3 for solmate's `ERC20` and 0 for OpenZeppelin's.
+    pub fn deal(&mut self, token: B160, to: B160, amount: U256, balance_slot: U256) {
+        let mut key = [0_u8; 64];
+        key[12..32].copy_from_slice(to.as_bytes());
+        key[32..].copy_from_slice(&balance_slot.to_be_bytes::<32>());
+        let slot = U256::from_be_bytes(keccak256(&key).0);
+        self.set_storage(token, slot, amount);
+    }
+
+    /// Cheatcode that overwrites the runtime bytecode of an account. The change is recorded in the [`SimulationManager::cheatcode_log`].
+    /// # Arguments
+    /// * `address` - The address of the account.
+    /// * `code` - The new runtime bytecode.
+    pub fn set_code(&mut self, address: B160, code: Bytes) {
+        self.record_cheatcode(Cheatcode::SetCode {
+            address,
+            code: code.clone(),
+        });
+        let db = self.environment.evm.db().unwrap(); // The environment always has a database.
+        let mut info = db
+            .accounts
+            .get(&address)
+            .map(|account| account.info.clone())
+            .unwrap_or_default();
+        info.code_hash = if code.is_empty() {
+            KECCAK_EMPTY
+        } else {
+            keccak256(&code)
+        };
+        info.code = Some(Bytecode::new_raw(code));
+        db.insert_account_info(address, info);
+    }
+
+    /// Cheatcode that sends a transaction on behalf of any address, without its signature. The transaction uses the admin's gas settings.
+    /// The transaction is recorded in the [`SimulationManager::cheatcode_log`].
+    /// # Arguments
+    /// * `address` - The address to impersonate.
+    /// * `to` - The address the transaction is sent to.
+    /// * `call_data` - The calldata of the transaction.
+    /// * `value` - The value of the transaction.
+    /// # Returns
+    /// * `Ok(ExecutionResult)` - The full result of the transaction.
+    pub fn impersonate(
+        &mut self,
+        address: B160,
+        to: B160,
+        call_data: Bytes,
+        value: U256,
+    ) -> Result<ExecutionResult, ManagerError> {
+        self.record_cheatcode(Cheatcode::Impersonate {
+            address,
+            to,
+            call_data: call_data.clone(),
+            value,
+        });
+        let mut tx = self
+            .find_agent("admin")?
+            .build_call_transaction(to, call_data, value);
+        tx.caller = address;
+        self.environment.execute(tx).map_err(|err| {
+            ManagerError::Rejected(format!(
+                "The impersonated transaction was rejected: {:?}",
+                err
+            ))
+        })
+    }
+
+    /// Returns every cheatcode used so far with its arguments, in the order they were used.
+    /// It shows exactly how the simulation deviated from organic execution.
+    pub fn cheatcode_log(&self) -> &[CheatcodeRecord] {
+        &self.cheatcode_log
+    }
+
+    /// Adds a cheatcode to the audit log. Accounts a cheatcode touches are local changes, so they are no longer evictable fork accounts.
+    fn record_cheatcode(&mut self, cheatcode: Cheatcode) {
+        match &cheatcode {
+            Cheatcode::SetBalance { address, .. }
+            | Cheatcode::SetStorage { address, .. }
+            | Cheatcode::SetCode { address, .. } => {
+                self.fork_accounts.remove(address);
+            }
+            Cheatcode::Impersonate { .. } => {}
+        }
+        self.cheatcode_log.push(CheatcodeRecord {
+            block_number: self.block_number(),
+            transactions_executed: self.environment.transactions_executed,
+            cheatcode,
+        });
+    }
+
+    /// Returns every log emitted in the simulation so far, numbered within their blocks.
+    pub fn log_history(&self) -> &[IndexedLog] {
+        &self.environment.log_history
+    }
+
+    /// Queries the logs emitted in the simulation so far, e.g., to reconstruct every `Swap` of a pool after a run without a dedicated agent.
+    /// Addresses and topics are matched like `eth_getLogs` does. A numeric block range narrows the search, while block tags and block hashes are ignored.
+    /// # Arguments
+    /// * `filter` - The ethers-style filter the logs have to match.
+    /// # Returns
+    /// * `Vec<Log>` - The matching logs in the order they were emitted.
+    pub fn get_logs(&self, filter: &Filter) -> Vec<Log> {
+        let from_block = filter
+            .get_from_block()
+            .map(|block| U256::from(block.as_u64()));
+        let to_block = filter
+            .get_to_block()
+            .map(|block| U256::from(block.as_u64()));
+        self.environment
+            .log_history
+            .iter()
+            .filter(|indexed_log| {
+                from_block.map_or(true, |from| indexed_log.block_number >= from)
+                    && to_block.map_or(true, |to| indexed_log.block_number <= to)
+                    && log_matches(filter, &indexed_log.log)
+            })
+            .map(|indexed_log| indexed_log.log.clone())
+            .collect()
+    }
+
+    /// Takes an `ExecutionResult` and returns the raw bytes of the output that can then be decoded.
+    /// # Arguments
+    /// * `execution_result` - The `ExecutionResult` that we want to unpack.
+    /// # Returns
+    /// * `Ok(Bytes)` - The raw bytes of the output.
+    pub fn unpack_execution(
+        &self,
+        execution_result: ExecutionResult,
+    ) -> Result<Bytes, ManagerError> {
+        self.unpack_execution_with_gas(execution_result)
+            .map(|execution_output| execution_output.output)
+    }
+
+    /// Takes an `ExecutionResult` and returns the raw bytes of the output along with the gas used, e.g., to measure gas without running the call again.
+    /// The errors report the gas used by halted and reverted calls in their message.
+    /// # Arguments
+    /// * `execution_result` - The `ExecutionResult` that we want to unpack.
+    /// # Returns
+    /// * `Ok(ExecutionOutput)` - The raw bytes of the output and the gas used.
+    pub fn unpack_execution_with_gas(
+        &self,
+        execution_result: ExecutionResult,
+    ) -> Result<ExecutionOutput, ManagerError> {
+        match execution_result {
+            ExecutionResult::Success {
+                output, gas_used, ..
+            } => {
+                let output = match output {
+                    Output::Call(value) => value,
+                    Output::Create(value, _address) => value,
+                };
+                Ok(ExecutionOutput { output, gas_used })
+            }
+            ExecutionResult::Halt { reason, gas_used } => {
+                Err(ManagerError::Halt { reason, gas_used })
+            }
+            ExecutionResult::Revert { output, gas_used } => {
+                Err(ManagerError::Revert { output, gas_used })
+            }
+        }
+    }
+}
+
+/// Describes revert data for a human: the reason of an `Error(string)`, otherwise the 4-byte selector of the custom error.
+/// # Arguments
+/// * `output` - The revert data.
+fn describe_revert(output: &[u8]) -> String {
+    if let Some(reason) = decode_revert_reason(output) {
+        return format!("Revert: {}", reason);
+    }
+    match output.get(..4) {
+        Some(selector) => format!(
+            "Revert with custom error 0x{}",
+            ethers::utils::hex::encode(selector)
+        ),
+        None => "Revert without a reason".to_string(),
+    }
+}
+
+/// Returns the address directly after the given one, wrapping around at the maximum address.
+fn next_address(address: B160) -> B160 {
+    let mut bytes = address.0;
+    for byte in bytes.iter_mut().rev() {
+        let (incremented, overflowed) = byte.overflowing_add(1);
+        *byte = incremented;
+        if !overflowed {
+            break;
+        }
+    }
+    B160(bytes)
+}
+
+/// Returns the approximate number of bytes an account of the database uses, including its storage and inline bytecode.
+fn account_memory_estimate(account: &DbAccount) -> usize {
+    size_of::<B160>()
+        + size_of::<DbAccount>()
+        + account.storage.len() * 2 * size_of::<U256>()
+        + account
+            .info
+            .code
+            .as_ref()
+            .map_or(0, |code| code.bytes().len())
+}
+
+/// Checks whether a log matches the addresses and topics of a filter like `eth_getLogs` does.
+/// An unset or empty address or topic, as well as a `None` entry in a topic array, matches anything.
+/// # Arguments
+/// * `filter` - The filter to match against.
+/// * `log` - The log to check.
+fn log_matches(filter: &Filter, log: &Log) -> bool {
+    let address = recast_address(log.address);
+    let address_matches = match &filter.address {
+        None => true,
+        Some(ValueOrArray::Value(filter_address)) => *filter_address == address,
+        Some(ValueOrArray::Array(filter_addresses)) => {
+            filter_addresses.is_empty() || filter_addresses.contains(&address)
+        }
+    };
+    address_matches
+        && filter.topics.iter().enumerate().all(|(index, topic)| {
+            let log_topic = log
+                .topics
+                .get(index)
+                .map(|topic| H256::from_slice(topic.as_bytes()));
+            match topic {
+                None | Some(ValueOrArray::Value(None)) => true,
+                Some(ValueOrArray::Value(Some(topic))) => log_topic == Some(*topic),
+                Some(ValueOrArray::Array(topics)) => {
+                    topics.is_empty()
+                        || topics
+                            .iter()
+                            .any(|topic| topic.is_none() || log_topic == *topic)
+                }
+            }
+        })
+}
+
+#[test]
+fn get_agent_by_name_and_address() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let alice_address = B160::from_low_u64_be(2);
+    manager.activate_agent(AgentType::User(User::new("alice", None)), alice_address)?;
+
+    let alice = manager.get_agent("alice").unwrap();
+    assert_eq!(alice.inner().address(), alice_address);
+    let alice = manager.get_agent_by_address(alice_address).unwrap();
+    assert_eq!(alice.inner().name(), "alice");
+    assert!(manager.get_agent("bob").is_none());
+    assert!(manager
+        .get_agent_by_address(B160::from_low_u64_be(3))
+        .is_none());
+    Ok(())
+}
+
+#[test]
+fn agent_address_collision() {
+    let mut manager = SimulationManager::default();
+    let alice = User::new("alice", None);
+    let result = manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(1));
+    assert!(matches!(result, Err(ManagerError::DuplicateAddress)));
+}
+
+#[test]
+fn agent_auto_approvals() -> Result<(), Box<dyn Error>> {
+    use bindings::arbiter_token;
+
+    use crate::contract::SimulationContract;
+
+    let mut manager = SimulationManager::default();
+
+    // Deploy a token for alice to approve.
+    let arbiter_token = SimulationContract::new(
+        arbiter_token::ARBITERTOKEN_ABI.clone(),
+        arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    );
+    let args = ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8);
+    let arbiter_token = arbiter_token.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        args,
+    );
+
+    // Activate alice with an auto-approval for the spender.
+    let alice_address = B160::from_low_u64_be(2);
+    let spender = B160::from_low_u64_be(3);
+    let alice =
+        User::new("alice", None).with_auto_approvals(vec![(arbiter_token.address, spender)]);
+    manager.activate_agent(AgentType::User(alice), alice_address)?;
+
+    // Check the allowance right after activation.
+    let call_data = arbiter_token.encode_function(
+        "allowance",
+        (recast_address(alice_address), recast_address(spender)),
+    )?;
+    let execution_result = manager.agents.get("admin").unwrap().call_contract(
+        &mut manager.environment,
+        &arbiter_token,
+        call_data,
+        U256::ZERO,
+    )?;
+    let value = manager.unpack_execution(execution_result)?;
+    let allowance: ethers::types::U256 = arbiter_token.decode_output("allowance", value)?;
+    assert_eq!(allowance, ethers::types::U256::MAX);
+    Ok(())
+}
+
+#[test]
+fn failed_auto_approval_rolls_back_activation() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    // Reverts on every call.
+    let reverter = crate::tests::runtime_contract(&[
+        0x60, 0x00, // PUSH1 0
+        0x60, 0x00, // PUSH1 0
+        0xfd, // REVERT
+    ]);
+    let reverter = manager.deploy("admin", &reverter, ())?;
+
+    let alice = User::new("alice", None)
+        .with_auto_approvals(vec![(reverter.address, B160::from_low_u64_be(3))]);
+    let result = manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2));
+    assert!(matches!(result, Err(ManagerError::Revert { .. })));
+    assert!(manager.get_agent("alice").is_none());
+    assert!(!manager.environment.event_senders.contains_key("alice"));
+
+    // The name and the address are free again.
+    let alice = AgentType::User(User::new("alice", None));
+    manager.activate_agent(alice, B160::from_low_u64_be(2))?;
+    Ok(())
+}
+
+#[test]
+fn agent_permit_signature() -> Result<(), Box<dyn Error>> {
+    use bindings::arbiter_token;
+
+    let mut manager = SimulationManager::default();
+
+    // Activate alice with a signing key and bob as the spender.
+    let signer: LocalWallet =
+        "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".parse()?;
+    let alice_address = B160::from(signer.address().0);
+    manager.activate_agent_with_signer(AgentType::User(User::new("alice", None)), signer)?;
+    let bob_address = B160::from_low_u64_be(3);
+    manager.activate_agent(AgentType::User(User::new("bob", None)), bob_address)?;
+
+    // Deploy a permit-enabled token and mint some to alice.
+    let arbiter_token = SimulationContract::new(
+        arbiter_token::ARBITERTOKEN_ABI.clone(),
+        arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    );
+    let args = ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8);
+    let arbiter_token = arbiter_token.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        args,
+    );
+    let amount = ethers::types::U256::from(1000);
+    let call_data =
+        arbiter_token.encode_function("mint", (recast_address(alice_address), amount))?;
+    manager.agents.get("admin").unwrap().call_contract(
+        &mut manager.environment,
+        &arbiter_token,
+        call_data,
+        U256::ZERO,
+    )?;
+
+    // Alice signs a permit off-chain and bob submits it.
+    let deadline = ethers::types::U256::MAX;
+    let transactions = manager.environment.transaction_history.len();
+    let signature = manager.sign_permit("alice", &arbiter_token, bob_address, amount, deadline)?;
+    // Signing reads the token without sending transactions.
+    assert_eq!(manager.nonce_of("alice")?, 0);
+    assert_eq!(manager.environment.transaction_history.len(), transactions);
+    let call_data = arbiter_token.encode_function(
+        "permit",
+        (
+            recast_address(alice_address),
+            recast_address(bob_address),
+            amount,
+            deadline,
+            signature.v as u8,
+            H256::from_uint(&signature.r),
+            H256::from_uint(&signature.s),
+        ),
+    )?;
+    let bob = manager.agents.get("bob").unwrap();
+    let execution_result = bob.call_contract(
+        &mut manager.environment,
+        &arbiter_token,
+        call_data,
+        U256::ZERO,
+    )?;
+    manager.unpack_execution(execution_result)?;
+
+    // Bob can now transfer alice's tokens since the permit set the allowance.
+    let call_data = arbiter_token.encode_function(
+        "transferFrom",
+        (
+            recast_address(alice_address),
+            recast_address(bob_address),
+            amount,
+        ),
+    )?;
+    let execution_result = bob.call_contract(
+        &mut manager.environment,
+        &arbiter_token,
+        call_data,
+        U256::ZERO,
+    )?;
+    manager.unpack_execution(execution_result)?;
+
+    let call_data = arbiter_token.encode_function("balanceOf", recast_address(bob_address))?;
+    let execution_result = bob.call_contract(
+        &mut manager.environment,
+        &arbiter_token,
+        call_data,
+        U256::ZERO,
+    )?;
+    let value = manager.unpack_execution(execution_result)?;
+    let balance: ethers::types::U256 = arbiter_token.decode_output("balanceOf", value)?;
+    assert_eq!(balance, amount);
+    Ok(())
+}
+
+#[test]
+fn agent_nonce_collision() -> Result<(), Box<dyn Error>> {
+    let mut manager = SimulationManager::default();
+    let alice = User::new("alice", None);
+    manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2))?;
+
+    let to = B160::from_low_u64_be(3);
+    manager.send_transaction("alice", to, Bytes::new(), 0)?;
+    let result = manager.send_transaction("alice", to, Bytes::new(), 0);
+    assert!(result.unwrap_err().to_string().contains("Nonce collision"));
+    Ok(())
+}
+
+#[test]
+fn nonce_of_tracks_calls_and_deployments() -> Result<(), Box<dyn Error>> {
+    let mut manager = SimulationManager::default();
+    let alice = User::new("alice", None);
+    manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2))?;
+    assert_eq!(manager.nonce_of("alice")?, 0);
+
+    manager.call_with_value("alice", B160::from_low_u64_be(3), Bytes::new(), U256::ZERO)?;
+    manager.call_with_value("alice", B160::from_low_u64_be(3), Bytes::new(), U256::ZERO)?;
+    assert_eq!(manager.nonce_of("alice")?, 2);
+
+    // The deployment lands at the `CREATE` address of alice's current nonce.
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let writer = manager.deploy("alice", &writer, ())?;
+    let expected = ethers::utils::get_contract_address(recast_address(B160::from_low_u64_be(2)), 2);
+    assert_eq!(recast_address(writer.address), expected);
+    assert_eq!(manager.nonce_of("alice")?, 3);
+    assert!(manager.nonce_of("bob").is_err());
+    Ok(())
+}
+
+#[test]
+fn deploy_create2_matches_computed_address() -> Result<(), Box<dyn Error>> {
+    let mut manager = SimulationManager::default();
+    let admin = manager.agents.get("admin").unwrap().address();
+    let salt = H256::from_low_u64_be(42);
+    let bytecode = Bytes::from(bindings::writer::WRITER_BYTECODE.to_vec());
+
+    let init_code_hash = H256::from(keccak256(&bytecode).0);
+    let predicted =
+        crate::utils::compute_create2_address(recast_address(admin), salt, init_code_hash);
+    let address = manager.deploy_create2("admin", salt, bytecode.clone(), Bytes::new())?;
+    assert_eq!(recast_address(address), predicted);
+    let code_hash = manager.environment.evm.db().unwrap().accounts[&address]
+        .info
+        .code_hash;
+    assert_ne!(code_hash, KECCAK_EMPTY);
+
+    // The address is taken, while another salt deploys elsewhere.
+    assert!(manager
+        .deploy_create2("admin", salt, bytecode.clone(), Bytes::new())
+        .is_err());
+    let other =
+        manager.deploy_create2("admin", H256::from_low_u64_be(43), bytecode, Bytes::new())?;
+    assert_ne!(other, address);
+    Ok(())
+}
+
+#[test]
+fn code_and_storage_are_readable_after_a_run() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let runtime = [
+        0x60, 0x2a, // PUSH1 42
+        0x60, 0x00, // PUSH1 0
+        0x55, // SSTORE
+        0x00, // STOP
+    ];
+    let store = crate::tests::runtime_contract(&runtime);
+    let store = manager.deploy("admin", &store, ())?;
+    assert_eq!(
+        manager.code_at(store.address),
+        Bytes::from(runtime.to_vec())
+    );
+    assert_eq!(manager.storage_at(store.address, U256::ZERO), U256::ZERO);
+
+    manager.call_with_value("admin", store.address, Bytes::new(), U256::ZERO)?;
+    assert_eq!(
+        manager.storage_at(store.address, U256::ZERO),
+        U256::from(42)
+    );
+    assert_eq!(manager.storage_at(store.address, U256::from(1)), U256::ZERO);
+
+    // Accounts without code, and accounts that do not exist at all, read as empty.
+    let admin = manager.agents.get("admin").unwrap().address();
+    assert!(manager.code_at(admin).is_empty());
+    assert!(manager.code_at(B160::from_low_u64_be(0xdead)).is_empty());
+    Ok(())
+}
+
+#[test]
+fn pinned_gas_transfer() {
+    let mut manager = SimulationManager::with_pinned_gas(SpecId::LONDON);
+    let tx = manager.agents.get("admin").unwrap().build_call_transaction(
+        B160::from_low_u64_be(2),
+        Bytes::new(),
+        U256::ZERO,
+    );
+    let execution_result = manager.environment.execute(tx).unwrap();
+    assert_eq!(execution_result.gas_used(), 21000);
+}
+
+#[test]
+fn pinned_gas_storage_refund() {
+    let mut manager = SimulationManager::with_pinned_gas(SpecId::ISTANBUL);
+    // The constructor sets slot 0 to 1 and the runtime code clears it again.
+    let mut bytecode = vec![
+        0x60, 0x01, // PUSH1 1
+        0x60, 0x00, // PUSH1 0
+        0x55, // SSTORE
+        0x60, 0x06, // PUSH1 length of the runtime code
+        0x60, 0x11, // PUSH1 offset of the runtime code
+        0x60, 0x00, // PUSH1 0
+        0x39, // CODECOPY
+        0x60, 0x06, // PUSH1 length of the runtime code
+        0x60, 0x00, // PUSH1 0
+        0xf3, // RETURN
+    ];
+    bytecode.extend_from_slice(&[
+        0x60, 0x00, // PUSH1 0
+        0x60, 0x00, // PUSH1 0
+        0x55, // SSTORE
+        0x00, // STOP
+    ]);
+    let storage_clearer =
+        SimulationContract::new(ethers::abi::Contract::default(), bytecode.into()).deploy(
+            &mut manager.environment,
+            manager.agents.get("admin").unwrap(),
+            (),
+        );
+
+    let tx = manager.agents.get("admin").unwrap().build_call_transaction(
+        storage_clearer.address,
+        Bytes::new(),
+        U256::ZERO,
+    );
+    let execution_result = manager.environment.execute(tx).unwrap();
+
+    // 21000 intrinsic + 6 for the pushes + 5000 for resetting the slot, half of which is refunded before London.
+    assert_eq!(crate::utils::gas_refunded(&execution_result), 13_003);
+    assert_eq!(execution_result.gas_used(), 13_003);
+}
+
+#[test]
+fn throttled_steps() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.set_step_delay(Duration::from_millis(20));
+
+    let mut steps_run = 0;
+    let start = std::time::Instant::now();
+    manager.run_steps(5, |_| {
+        steps_run += 1;
+        Ok(())
+    })?;
+
+    assert_eq!(steps_run, 5);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    Ok(())
+}
+
+#[test]
+fn blackboard_shared_within_step() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.activate_agent(
+        AgentType::User(User::new("signaler", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    manager.activate_agent(
+        AgentType::User(User::new("listener", None)),
+        B160::from_low_u64_be(3),
+    )?;
+
+    // The signaler publishes its belief about the price, but only in the first step.
+    let signaler_step = |manager: &mut SimulationManager, step: usize| {
+        if step == 0 {
+            let belief = manager.agents.get("signaler").unwrap().inner().address();
+            manager.blackboard_set("price_belief", belief.as_bytes().to_vec());
+        }
+    };
+    // The listener reads whatever belief was published in the current step.
+    let listener_step =
+        |manager: &SimulationManager| manager.blackboard_get("price_belief").map(<[u8]>::to_vec);
+
+    let mut heard = Vec::new();
+    let mut step = 0;
+    manager.run_steps(2, |manager| {
+        signaler_step(manager, step);
+        heard.push(listener_step(manager));
+        step += 1;
+        Ok(())
+    })?;
+
+    // The belief is visible later in the step it was written in and gone by the next one.
+    assert_eq!(
+        heard,
+        vec![Some(B160::from_low_u64_be(2).as_bytes().to_vec()), None]
+    );
+    assert_eq!(manager.blackboard_get("missing"), None);
+    Ok(())
+}
+
+#[test]
+fn agent_relocated_on_address_collision() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+
+    // The admin already lives at 0x0...1, so the agent moves to the next free address.
+    let address = manager.activate_agent_or_relocate(
+        AgentType::User(User::new("relocated", None)),
+        B160::from_low_u64_be(1),
+    )?;
+    assert_eq!(address, B160::from_low_u64_be(2));
+    assert_eq!(
+        manager.agents.get("relocated").unwrap().inner().address(),
+        address
+    );
+
+    // A free preferred address is used as is.
+    let address = manager.activate_agent_or_relocate(
+        AgentType::User(User::new("placed", None)),
+        B160::from_low_u64_be(10),
+    )?;
+    assert_eq!(address, B160::from_low_u64_be(10));
+    Ok(())
+}
+
+#[test]
+fn call_returns_full_execution_result() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+
+    let execution_result =
+        manager.call_with_result("admin", &writer, "echoString", "Hello, world!".to_string())?;
+
+    assert!(execution_result.is_success());
+    assert!(execution_result.gas_used() > 21000);
+    assert_eq!(execution_result.logs().len(), 1);
+    assert_eq!(execution_result.logs()[0].address, writer.address);
+    Ok(())
+}
+
+#[test]
+fn snapshot_reverts_chain_state() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.activate_agent(
+        AgentType::User(User::new("alice", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    let alice_address = recast_address(B160::from_low_u64_be(2));
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+    let block_number = manager.block_number();
+    let snapshot = manager.snapshot();
+
+    let mint_amount = ethers::types::U256::from(1000);
+    let execution_result =
+        manager.call_with_result("admin", &token, "mint", (alice_address, mint_amount))?;
+    manager.unpack_execution(execution_result)?;
+    manager.advance_block(1);
+    manager.revert(snapshot)?;
+
+    let execution_result = manager.call_with_result("admin", &token, "balanceOf", alice_address)?;
+    let balance: ethers::types::U256 =
+        token.decode_output("balanceOf", manager.unpack_execution(execution_result)?)?;
+    assert_eq!(balance, ethers::types::U256::zero());
+    assert_eq!(manager.block_number(), block_number);
+
+    // The snapshot is used up by the revert.
+    assert!(manager.revert(snapshot).is_err());
+    Ok(())
+}
+
+#[test]
+fn stale_snapshot_id_is_not_reused() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let first = manager.snapshot();
+    let later = manager.snapshot();
+    manager.revert(first)?;
+
+    // A new snapshot after the revert gets a fresh identifier, so the used up ones stay invalid.
+    manager.advance_block(1);
+    let fresh = manager.snapshot();
+    assert_ne!(fresh, first);
+    assert_ne!(fresh, later);
+    assert!(manager.revert(first).is_err());
+    assert!(manager.revert(later).is_err());
+    assert_eq!(manager.block_number(), 1);
+
+    manager.advance_block(1);
+    manager.revert(fresh)?;
+    assert_eq!(manager.block_number(), 1);
+    Ok(())
+}
+
+#[test]
+fn partial_snapshot_reverts_only_snapshotted_accounts() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.activate_agent(
+        AgentType::User(User::new("alice", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    let alice_address = recast_address(B160::from_low_u64_be(2));
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+    let mint_amount = ethers::types::U256::from(1000);
+    let execution_result =
+        manager.call_with_result("admin", &token, "mint", (alice_address, mint_amount))?;
+    manager.unpack_execution(execution_result)?;
+
+    // Try a trade and undo it by restoring only the token account.
+    let snapshot = manager.snapshot_accounts(&[token.address]);
+    let execution_result = manager.call_with_result(
+        "alice",
+        &token,
+        "transfer",
+        (recast_address(B160::from_low_u64_be(1)), mint_amount),
+    )?;
+    manager.unpack_execution(execution_result)?;
+    manager.revert_accounts(snapshot);
+
+    let execution_result = manager.call_with_result("admin", &token, "balanceOf", alice_address)?;
+    let balance: ethers::types::U256 =
+        token.decode_output("balanceOf", manager.unpack_execution(execution_result)?)?;
+    assert_eq!(balance, mint_amount);
+
+    // Alice was not in the snapshot, so her nonce still reflects the transfer.
+    let alice_nonce = manager.environment.evm.db().unwrap().accounts[&B160::from_low_u64_be(2)]
+        .info
+        .nonce;
+    assert_eq!(alice_nonce, 1);
+    Ok(())
+}
+
+#[test]
+fn report_throughput() {
+    let mut manager = SimulationManager::default();
+    for _ in 0..5 {
+        let tx = manager.agents.get("admin").unwrap().build_call_transaction(
+            B160::from_low_u64_be(2),
+            Bytes::new(),
+            U256::ZERO,
+        );
+        manager.environment.execute(tx).unwrap();
+    }
+
+    let report = manager.report();
+    assert_eq!(report.transactions, 5);
+    assert_eq!(report.gas_used, 5 * 21000);
+    assert!(report.transactions_per_second > 0.0);
+    assert!(report.gas_per_second > 0.0);
+}
+
+#[test]
+fn report_flags_idle_agents() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+
+    // The active agent listens to the writer and writes to it, the idle agent listens to the wrong contract.
+    let active = User::new(
+        "active",
+        Some(vec![crate::agent::create_filter(&writer, "WasWritten")]),
+    );
+    manager.activate_agent(AgentType::User(active), B160::from_low_u64_be(2))?;
+    let idle = User::new(
+        "idle",
+        Some(vec![crate::agent::create_filter(&token, "Approval")]),
+    );
+    manager.activate_agent(AgentType::User(idle), B160::from_low_u64_be(3))?;
+    let execution_result =
+        manager.call_with_result("active", &writer, "echoString", "busy".to_string())?;
+    manager.unpack_execution(execution_result)?;
+
+    let report = manager.report();
+    assert_eq!(report.idle_agents.len(), 1);
+    assert_eq!(report.idle_agents[0].name, "idle");
+    assert_eq!(report.idle_agents[0].last_observed_event, None);
+    Ok(())
+}
+
+#[test]
+fn build_block_drops_rejected_transactions() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let broke = User::new("broke", None)
+        .with_gas_limit(21_000)
+        .with_gas_price(U256::from(1));
+    manager.activate_agent(AgentType::User(broke), B160::from_low_u64_be(2))?;
+
+    let to = B160::from_low_u64_be(3);
+    manager.submit_transaction("broke", to, Bytes::new(), U256::ZERO)?;
+    manager.submit_transaction("admin", to, Bytes::new(), U256::ZERO)?;
+    let execution_results = manager.build_block()?;
+
+    // The unfunded transaction cannot pay for its gas, so only the admin's transaction makes it into the block.
+    assert_eq!(execution_results.len(), 1);
+    assert_eq!(manager.export_trace().transactions.len(), 1);
+    assert_eq!(manager.mempool.rejected.len(), 1);
+    assert_eq!(manager.mempool.rejected[0].pending_tx.sender, "broke");
+    assert_eq!(manager.mempool.rejected[0].block_number, 0);
+    assert!(manager.mempool.pending.is_empty());
+    assert_eq!(manager.block_number(), 1);
+    Ok(())
+}
+
+#[test]
+fn custom_ordering_policy() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    // Order the block by calldata size, smallest first.
+    manager.set_ordering_policy(OrderingPolicy::Custom(Box::new(|pending| {
+        let mut order: Vec<usize> = (0..pending.len()).collect();
+        order.sort_by_key(|index| pending[*index].tx.data.len());
+        order
+    })));
+
+    // Each transaction comes from its own sender, since a sender's transactions must keep their nonce order.
+    for (name, size) in [("alice", 3), ("bob", 1), ("carol", 2)] {
+        let address = B160::from_low_u64_be(10 + size as u64);
+        manager.activate_agent(AgentType::User(User::new(name, None)), address)?;
+        manager.submit_transaction(
+            name,
+            B160::from_low_u64_be(2),
+            Bytes::from(vec![0xff; size]),
+            U256::ZERO,
+        )?;
+    }
+    let execution_results = manager.build_block()?;
+
+    assert_eq!(execution_results.len(), 3);
+    assert!(manager.mempool.pending.is_empty());
+    assert_eq!(manager.block_number(), 1);
+    let sizes: Vec<usize> = manager
+        .export_trace()
+        .transactions
+        .iter()
+        .map(|transaction| transaction.data.len())
+        .collect();
+    assert_eq!(sizes, vec![1, 2, 3]);
+    Ok(())
+}
+
+#[test]
+fn twap_oracle_accrues_with_blocks() {
+    use crate::environment::BLOCK_TIME;
+
+    let mut manager = SimulationManager::default();
+    manager.add_twap_oracle("pool", 100.0);
+
+    // The price is 100 for one block, 200 for two blocks and 400 for one block.
+    for price in [100.0, 200.0, 200.0, 400.0] {
+        manager
+            .twap_oracles
+            .get_mut("pool")
+            .unwrap()
+            .update_price(price);
+        manager.advance_block(1);
+    }
+
+    let oracle = manager.twap_oracles.get("pool").unwrap();
+    let average = (100.0 + 200.0 + 200.0 + 400.0) / 4.0;
+    assert_eq!(oracle.consult(4 * BLOCK_TIME), Some(average));
+    assert_eq!(oracle.consult(2 * BLOCK_TIME), Some(300.0));
+    // Half a block into the 200 period and the full 400 block.
+    assert_eq!(
+        oracle.consult(BLOCK_TIME + BLOCK_TIME / 2),
+        Some((200.0 * 6.0 + 400.0 * 12.0) / 18.0)
+    );
+    assert_eq!(oracle.consult(5 * BLOCK_TIME), None);
+}
+
+#[test]
+fn agent_downcast_to_concrete_type() {
+    let mut manager = SimulationManager::default();
+    let arbitrageur = SimpleArbitrageur::new("arbitrageur", vec![]);
+    let gas_estimate = arbitrageur.gas_estimate;
+    manager
+        .activate_agent(
+            AgentType::SimpleArbitrageur(arbitrageur),
+            B160::from_low_u64_be(2),
+        )
+        .unwrap();
+
+    let arbitrageur = manager
+        .agent_as::<SimpleArbitrageur<IsActive>>("arbitrageur")
+        .unwrap();
+    assert_eq!(arbitrageur.gas_estimate, gas_estimate);
+    assert_eq!(*arbitrageur.prices.lock().unwrap(), [U256::MAX; 2]);
+
+    assert!(manager.agent_as::<User<IsActive>>("arbitrageur").is_none());
+    assert!(manager.agent_as::<User<IsActive>>("admin").is_some());
+    assert!(manager.agent_as::<User<IsActive>>("nobody").is_none());
+}
+
+/// Reproduces a mainnet transaction in its block and compares the outcome with its receipt.
+/// Needs an archive node that serves the `debug` namespace, so it only runs if `ARBITER_FORK_RPC_URL` is set.
+#[test]
+fn fork_reads_live_state() {
+    let Ok(url) = std::env::var("ARBITER_FORK_RPC_URL") else {
+        return;
+    };
+    use revm::Database;
+
+    // WETH on mainnet.
+    let weth: B160 = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"
+        .parse()
+        .unwrap();
+    let mut manager = SimulationManager::fork(&url, 17_000_000).unwrap();
+    assert_eq!(
+        manager.environment.evm.env.block.number,
+        U256::from(17_000_001)
+    );
+    let account = manager
+        .environment
+        .evm
+        .db()
+        .unwrap()
+        .basic(weth)
+        .unwrap()
+        .unwrap();
+    assert!(account.code.is_some_and(|code| !code.is_empty()));
+}
+
+#[tokio::test]
+async fn simulate_tx_at_block_reproduces_receipt() {
+    use ethers::providers::{Http, Provider};
+
+    let Ok(url) = std::env::var("ARBITER_FORK_RPC_URL") else {
+        return;
+    };
+    let provider = Provider::<Http>::try_from(url).unwrap();
+    let (block, tx_index) = (17_000_000, 3);
+    let transaction = provider
+        .get_block_with_txs(block)
+        .await
+        .unwrap()
+        .unwrap()
+        .transactions[tx_index]
+        .clone();
+    let receipt = provider
+        .get_transaction_receipt(transaction.hash)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let mut manager = SimulationManager::default();
+    let execution_result = manager
+        .simulate_tx_at_block(&provider, transaction.rlp().0, block, tx_index)
+        .await
+        .unwrap();
+    assert_eq!(
+        execution_result.is_success(),
+        receipt.status == Some(1.into())
+    );
+    assert_eq!(Some(execution_result.gas_used().into()), receipt.gas_used);
+}
+
+#[test]
+fn db_memory_cap_evicts_clean_fork_accounts() {
+    use crate::state::{AccountDump, StateDump};
+
+    let mut manager = SimulationManager::default();
+    let fork_state = |addresses: &[B160]| {
+        let mut state = StateDump::default();
+        for address in addresses {
+            let account = AccountDump {
+                balance: 1.into(),
+                storage: (0..100_u64).map(|slot| (slot.into(), 1.into())).collect(),
+                ..Default::default()
+            };
+            state.accounts.insert(recast_address(*address), account);
+        }
+        state
+    };
+    let (a, b, c) = (
+        B160::from_low_u64_be(0xa),
+        B160::from_low_u64_be(0xb),
+        B160::from_low_u64_be(0xc),
+    );
+    let accounts = |manager: &SimulationManager| {
+        let db = manager.environment.evm.db.as_ref().unwrap();
+        [a, b, c, B160::from_low_u64_be(1)].map(|address| db.accounts.contains_key(&address))
+    };
+
+    // Without a node, prestate accounts could not be fetched again, so they are never evicted.
+    manager.load_fork_state(fork_state(&[a, b, c]));
+    manager.set_db_memory_cap(Some(0));
+    assert_eq!(accounts(&manager), [true; 4]);
+    manager.set_db_memory_cap(None);
+
+    // Forked, the accounts stand in for accounts fetched from the node.
+    let mut manager = SimulationManager::default();
+    manager.load_fork_state(fork_state(&[a, b]));
+    // A local transaction writes to `a`, so it is no longer clean.
+    let tx =
+        manager
+            .agents
+            .get("admin")
+            .unwrap()
+            .build_call_transaction(a, Bytes::new(), U256::ZERO);
+    manager.environment.execute(tx).unwrap();
+    manager.load_fork_state(fork_state(&[c]));
+    // Every account the test reads is cached by now, so the node is never reached.
+    let fork_db = ForkDB::unreachable();
+    for address in [a, b, c] {
+        fork_db.record_fetched(address);
+    }
+    manager.environment.evm.db().unwrap().db = fork_db;
+    assert_eq!(accounts(&manager), [true; 4]);
+
+    // Just over the cap, only the least recently used clean account is evicted.
+    manager.set_db_memory_cap(Some(manager.db_memory_estimate() - 1));
+    assert_eq!(accounts(&manager), [true, false, true, true]);
+
+    // Even without any room, the written fork account and the admin persist.
+    manager.set_db_memory_cap(Some(0));
+    assert_eq!(accounts(&manager), [true, false, false, true]);
+}
+
+#[test]
+fn set_balance_is_audited() {
+    let mut manager = SimulationManager::default();
+    let whale = B160::from_low_u64_be(0x3a1e);
+    let balance = U256::from(1_000_000_u64);
+    manager.set_balance(whale, balance);
+
+    let db = manager.environment.evm.db.as_ref().unwrap();
+    assert_eq!(db.accounts[&whale].info.balance, balance);
+    assert_eq!(
+        manager.cheatcode_log(),
+        &[CheatcodeRecord {
+            block_number: 0,
+            transactions_executed: 0,
+            cheatcode: Cheatcode::SetBalance {
+                address: whale,
+                balance,
+            },
+        }]
+    );
+}
+
+#[test]
+fn fund_agent_sets_balance() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.activate_agent(
+        AgentType::User(User::new("alice", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    let amount = U256::from(10_u64).pow(U256::from(21));
+    manager.fund_agent("alice", amount)?;
+
+    assert_eq!(manager.balance_of("alice")?, amount);
+    assert!(matches!(
+        manager.balance_of("bob"),
+        Err(ManagerError::AgentNotFound(_))
+    ));
+    assert!(matches!(
+        manager.fund_agent("bob", amount),
+        Err(ManagerError::AgentNotFound(name)) if name == "bob"
+    ));
+    Ok(())
+}
+
+#[test]
+fn call_with_value_moves_eth() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let weth = SimulationContract::new(
+        bindings::weth9::WETH9_ABI.clone(),
+        bindings::weth9::WETH9_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+    manager.activate_agent(
+        AgentType::User(User::new("alice", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    let ether = U256::from(10_u64).pow(U256::from(18));
+    manager.fund_agent("alice", ether * U256::from(10))?;
+
+    let call_data = weth.encode_function("deposit", ())?;
+    manager.call_with_value(
+        "alice",
+        weth.address,
+        call_data.clone(),
+        ether * U256::from(3),
+    )?;
+    assert_eq!(manager.balance_of("alice")?, ether * U256::from(7));
+    let weth_balance = manager.environment.evm.db().unwrap().accounts[&weth.address]
+        .info
+        .balance;
+    assert_eq!(weth_balance, ether * U256::from(3));
+    let call_data_balance =
+        weth.encode_function("balanceOf", recast_address(B160::from_low_u64_be(2)))?;
+    let wrapped: ethers::types::U256 = manager.call("alice", weth.address, call_data_balance)?;
+    assert_eq!(wrapped, ethers::types::U256::exp10(18) * 3);
+
+    // The caller cannot attach more ETH than it holds.
+    assert!(matches!(
+        manager.call_with_value("alice", weth.address, call_data, ether * U256::from(8)),
+        Err(ManagerError::Rejected(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn base_fee_sets_effective_gas_price() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let gwei = U256::from(1_000_000_000_u64);
+    manager.set_base_fee(gwei * U256::from(10));
+    let alice = User::new("alice", None)
+        .with_gas_limit(100_000)
+        .with_gas_price(gwei * U256::from(50))
+        .with_priority_fee(gwei * U256::from(2));
+    manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2))?;
+    manager.activate_agent(
+        AgentType::User(User::new("bob", None).with_gas_limit(100_000)),
+        B160::from_low_u64_be(3),
+    )?;
+    let ether = U256::from(10_u64).pow(U256::from(18));
+    manager.fund_agent("alice", ether)?;
+    manager.fund_agent("bob", ether)?;
+
+    // A plain transfer uses 21,000 gas at the base fee plus the priority fee, well below the maximum fee.
+    let to = B160::from_low_u64_be(0xbeef);
+    manager.call_with_value("alice", to, Bytes::new(), U256::ZERO)?;
+    assert_eq!(
+        manager.balance_of("alice")?,
+        ether - U256::from(21_000) * gwei * U256::from(12)
+    );
+
+    // Free transactions no longer cover the base fee.
+    assert!(matches!(
+        manager.call_with_value("bob", to, Bytes::new(), U256::ZERO),
+        Err(ManagerError::Rejected(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn deploy_mintable_tokens() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let arbiter_token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    );
+    let mock_erc20 = SimulationContract::new(
+        bindings::mock_erc20::MOCKERC20_ABI.clone(),
+        bindings::mock_erc20::MOCKERC20_BYTECODE.clone(),
+    );
+    let args = ("Token X".to_string(), "TKNX".to_string(), 18_u8);
+    let tokens = [
+        manager.deploy("admin", &arbiter_token, args.clone())?,
+        manager.deploy("admin", &mock_erc20, args.clone())?,
+    ];
+    let holder = recast_address(B160::from_low_u64_be(0xa11ce));
+    let amount = ethers::types::U256::from(1000);
+    for token in &tokens {
+        manager.call_with_result("admin", token, "mint", (holder, amount))?;
+        let execution_result = manager.call_with_result("admin", token, "balanceOf", holder)?;
+        let balance: ethers::types::U256 =
+            token.decode_output("balanceOf", manager.unpack_execution(execution_result)?)?;
+        assert_eq!(balance, amount);
+    }
+    assert!(matches!(
+        manager.deploy("bob", &arbiter_token, args),
+        Err(ManagerError::AgentNotFound(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn multiprocess_builder_matches_portfolio_encoder() -> Result<(), ManagerError> {
+    use bindings::multiprocess::MultiprocessBuilder;
+
+    /// Encodes an instruction with the Portfolio encoders deployed on chain.
+    fn encode<T: Tokenize>(
+        manager: &mut SimulationManager,
+        encoder: &SimulationContract<IsDeployed>,
+        function_name: &str,
+        args: T,
+    ) -> Result<Vec<u8>, ManagerError> {
+        let execution_result = manager.call_with_result("admin", encoder, function_name, args)?;
+        let output = manager.unpack_execution(execution_result)?;
+        let encoded: ethers::types::Bytes = encoder.decode_output(function_name, output)?;
+        Ok(encoded.to_vec())
+    }
+
+    let mut manager = SimulationManager::default();
+    let encoder = SimulationContract::new(
+        bindings::encoder_target::ENCODERTARGET_ABI.clone(),
+        bindings::encoder_target::ENCODERTARGET_BYTECODE.clone(),
+    );
+    let encoder = manager.deploy("admin", &encoder, ())?;
+    let pool_id = 0x0100_0001_u64;
+    let wad = 10_u128.pow(18);
+
+    let allocate = MultiprocessBuilder::new()
+        .allocate(true, pool_id, 5_000, wad, 123)
+        .build();
+    let args = (true, 1_u8, pool_id, 5_000_u128, wad, 123_u128);
+    let expected = encode(&mut manager, &encoder, "allocateOrDeallocate", args)?;
+    assert_eq!(allocate.to_vec(), expected);
+
+    let deallocate = MultiprocessBuilder::new()
+        .deallocate(false, pool_id, 5_000, 0, 7)
+        .build();
+    let args = (false, 0_u8, pool_id, 5_000_u128, 0_u128, 7_u128);
+    let expected = encode(&mut manager, &encoder, "allocateOrDeallocate", args)?;
+    assert_eq!(deallocate.to_vec(), expected);
+
+    let swap = MultiprocessBuilder::new()
+        .swap(false, pool_id, 2_500, 990, true)
+        .build();
+    let args = (0_u8, pool_id, 2_500_u128, 990_u128, 1_u8);
+    let expected = encode(&mut manager, &encoder, "swap", args)?;
+    assert_eq!(swap.to_vec(), expected);
+
+    let asset = recast_address(B160::from_low_u64_be(0xa));
+    let quote = recast_address(B160::from_low_u64_be(0xb));
+    let create_pair = MultiprocessBuilder::new().create_pair(asset, quote).build();
+    let expected = encode(&mut manager, &encoder, "createPair", (asset, quote))?;
+    assert_eq!(create_pair.to_vec(), expected);
+
+    // `uint24` has no Rust counterpart, so the pair id is passed as a token.
+    let create_pool = MultiprocessBuilder::new()
+        .create_pool(1, quote, 10, 30, 10_000, 365, 4, wad, wad)
+        .build();
+    let args = (
+        Token::Uint(1.into()),
+        quote,
+        10_u16,
+        30_u16,
+        10_000_u16,
+        365_u16,
+        4_u16,
+        wad,
+        wad,
+    );
+    let expected = encode(&mut manager, &encoder, "createPool", args)?;
+    assert_eq!(create_pool.to_vec(), expected);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_agents_apply_in_name_order() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    for (name, address) in [("bob", 3), ("alice", 2)] {
+        manager.activate_agent(
+            AgentType::User(User::new(name, None)),
+            B160::from_low_u64_be(address),
+        )?;
+    }
+    manager.set_ordering_policy(OrderingPolicy::Fifo);
+
+    let results = manager
+        .run_agents_concurrent(|name, address| async move {
+            // The admin sits the block out, everyone else sends an empty call to itself.
+            (name != "admin").then_some((address, Bytes::new(), U256::ZERO))
+        })
+        .await?;
+    assert_eq!(results.len(), 2);
+    // The first-in-first-out policy includes the proposals in the order they were collected, whichever task finished first.
+    let senders: Vec<Address> = manager
+        .environment
+        .transaction_history
+        .iter()
+        .map(|transaction| B160::from(transaction.sender.0))
+        .collect();
+    assert!(senders.ends_with(&[B160::from_low_u64_be(2), B160::from_low_u64_be(3)]));
+    Ok(())
+}
+
+#[test]
+fn run_counts_rounds() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    manager.activate_agent(
+        AgentType::User(User::new("alice", None)),
+        B160::from_low_u64_be(2),
+    )?;
+    let block_number = manager.block_number();
+    let mut rounds_sent = vec![];
+
+    // Alice acts every third round.
+    manager.run(7, |round, agent| {
+        if agent.inner().name() != "alice" || round % 3 != 0 {
+            return None;
+        }
+        rounds_sent.push(round);
+        Some((B160::from_low_u64_be(0xbeef), Bytes::new(), U256::ZERO))
+    })?;
+    assert_eq!(rounds_sent, vec![0, 3, 6]);
+    assert_eq!(manager.round(), 7);
+    assert_eq!(manager.block_number(), block_number + 7);
+    Ok(())
+}
+
+#[test]
+fn deal_sets_token_balance() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+    let whale = B160::from_low_u64_be(0x3a1e);
+    let amount = U256::from(1_000_000_u64);
+    // Solmate's `ERC20` keeps `balanceOf` in slot 3, after `name`, `symbol` and `totalSupply`.
+    manager.deal(token.address, whale, amount, U256::from(3));
+
+    let execution_result =
+        manager.call_with_result("admin", &token, "balanceOf", recast_address(whale))?;
+    let balance: ethers::types::U256 =
+        token.decode_output("balanceOf", manager.unpack_execution(execution_result)?)?;
+    assert_eq!(balance, ethers::types::U256::from(1_000_000_u64));
+    Ok(())
+}
+
+#[test]
+fn call_static_discards_state_changes() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+    let admin = B160::from_low_u64_be(1);
+    let admin_nonce = |manager: &mut SimulationManager| {
+        manager.environment.evm.db().unwrap().accounts[&admin]
+            .info
+            .nonce
+    };
+    let nonce = admin_nonce(&mut manager);
+    let transactions = manager.environment.transaction_history.len();
+
+    let mint_amount = ethers::types::U256::from(1000);
+    let call_data = token.encode_function("mint", (recast_address(admin), mint_amount))?;
+    manager.call_static("admin", token.address, call_data)?;
+    let call_data = token.encode_function("balanceOf", recast_address(admin))?;
+    let output = manager.call_static("admin", token.address, call_data)?;
+    let balance: ethers::types::U256 = token.decode_output("balanceOf", output)?;
+
+    assert_eq!(balance, ethers::types::U256::zero());
+    assert_eq!(admin_nonce(&mut manager), nonce);
+    assert_eq!(manager.environment.transaction_history.len(), transactions);
+    Ok(())
+}
+
+#[test]
+fn batch_call_runs_calls_in_sequence() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::default();
+    let token = SimulationContract::new(
+        bindings::arbiter_token::ARBITERTOKEN_ABI.clone(),
+        bindings::arbiter_token::ARBITERTOKEN_BYTECODE.clone(),
+    )
+    .deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        ("ArbiterToken".to_string(), "ARBT".to_string(), 18_u8),
+    );
+    let admin = recast_address(B160::from_low_u64_be(1));
+    let mint_amount = ethers::types::U256::from(1000);
+
+    // The balance is read after the mint in the same batch.
+    let outputs = manager.batch_call(
+        "admin",
+        vec![
+            (token.address, token.encode_function("symbol", ())?),
+            (
+                token.address,
+                token.encode_function("mint", (admin, mint_amount))?,
+            ),
+            (token.address, token.encode_function("balanceOf", admin)?),
+        ],
+    )?;
+    assert_eq!(outputs.len(), 3);
+    let symbol: String = token.decode_output("symbol", outputs[0].clone())?;
+    assert_eq!(symbol, "ARBT");
+    let balance: ethers::types::U256 = token.decode_output("balanceOf", outputs[2].clone())?;
+    assert_eq!(balance, mint_amount);
+
+    // A failing call stops the batch.
+    let result = manager.batch_call(
+        "admin",
+        vec![
+            (
+                token.address,
+                token.encode_function(
+                    "transfer",
+                    (recast_address(B160::from_low_u64_be(2)), mint_amount * 2),
+                )?,
+            ),
+            (token.address, token.encode_function("balanceOf", admin)?),
+        ],
+    );
+    assert!(matches!(result, Err(ManagerError::Revert { .. })));
+    Ok(())
+}
+
+#[test]
+fn deactivated_agent_stops_receiving_events() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let alice = AgentType::User(User::new("alice", None));
+    manager.activate_agent(alice, B160::from_low_u64_be(2))?;
+    let receiver = manager.agents.get("alice").unwrap().receiver();
+
+    let alice = manager.deactivate_agent("alice")?;
+    assert!(!manager.agents.contains_key("alice"));
+    assert!(!manager.environment.event_senders.contains_key("alice"));
+    // The channel is closed once the environment dropped its sender.
+    assert!(receiver.recv().is_err());
+
+    // The agent can come back, and the admin can never leave.
+    manager.activate_agent(alice, B160::from_low_u64_be(3))?;
+    assert!(manager.deactivate_agent("admin").is_err());
+    Ok(())
+}
+
+#[test]
+fn get_logs_filters_by_address_and_topic() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let admin = manager.agents.get("admin").unwrap();
+    let first = writer.deploy(&mut manager.environment, admin, ());
+    let second = writer.deploy(&mut manager.environment, admin, ());
+    for writer in [&first, &second] {
+        let call_data = writer.encode_function("echoString", "Hello, world!".to_string())?;
+        let execution_result =
+            admin.call_contract(&mut manager.environment, writer, call_data, U256::ZERO)?;
+        manager.unpack_execution(execution_result)?;
+    }
+
+    let topic = first
+        .base_contract
+        .abi()
+        .event("WasWritten")
+        .unwrap()
+        .signature();
+    let logs = manager.get_logs(
+        &Filter::new()
+            .address(recast_address(first.address))
+            .topic0(topic),
+    );
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0].address, first.address);
+
+    assert_eq!(manager.get_logs(&Filter::new().topic0(topic)).len(), 2);
+    assert!(manager
+        .get_logs(&Filter::new().topic0(H256::zero()))
+        .is_empty());
+    Ok(())
+}
+
+#[test]
+fn unpack_execution_reports_gas() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let writer = writer.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+
+    let execution_result =
+        manager.call_with_result("admin", &writer, "echoString", "Hello, world!".to_string())?;
+    let gas_used = execution_result.gas_used();
+    let execution_output = manager.unpack_execution_with_gas(execution_result)?;
+    assert!(execution_output.gas_used > 21_000);
+    assert_eq!(execution_output.gas_used, gas_used);
+    let echoed: String = writer.decode_output("echoString", execution_output.output)?;
+    assert_eq!(echoed, "Hello, world!");
+    Ok(())
+}
+
+#[test]
+fn revert_reason_is_decoded() {
+    let manager = SimulationManager::new();
+    let revert = |output: Bytes| {
+        manager
+            .unpack_execution(ExecutionResult::Revert {
+                output,
+                gas_used: 21_000,
+            })
+            .unwrap_err()
+            .to_string()
+    };
+
+    let mut output = vec![0x08, 0xc3, 0x79, 0xa0];
+    output.extend(abi::encode(&[Token::String(
+        "INSUFFICIENT_OUTPUT_AMOUNT".to_string(),
+    )]));
+    assert_eq!(
+        revert(output.into()),
+        "Revert: INSUFFICIENT_OUTPUT_AMOUNT (used 21000 gas)"
+    );
+    assert_eq!(
+        revert(Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef, 0x01])),
+        "Revert with custom error 0xdeadbeef (used 21000 gas)"
+    );
+    assert_eq!(
+        revert(Bytes::new()),
+        "Revert without a reason (used 21000 gas)"
+    );
+}
+
+#[test]
+fn seeded_managers_are_reproducible() {
+    use rand::Rng;
+
+    let mut first = SimulationManager::new_with_seed(7);
+    let mut second = SimulationManager::new_with_seed(7);
+    assert_eq!(
+        first.generate_signer().address(),
+        second.generate_signer().address()
+    );
+    assert_eq!(first.rng().gen::<u64>(), second.rng().gen::<u64>());
+
+    let mut other = SimulationManager::new_with_seed(8);
+    assert_ne!(
+        first.generate_signer().address(),
+        other.generate_signer().address()
+    );
+}
+
+#[test]
+fn call_decodes_output() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let writer = writer.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+
+    let call_data = writer.encode_function("echoString", "Hello, world!".to_string())?;
+    let echoed: String = manager.call("admin", writer.address, call_data)?;
+    assert_eq!(echoed, "Hello, world!");
+    Ok(())
+}
+
+#[test]
+fn higher_gas_price_arbitrageur_wins() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    manager.set_ordering_policy(OrderingPolicy::GasPrice);
+    let gwei = U256::from(1_000_000_000_u64);
+    for (name, address, gas_price) in [("slow", 2, gwei), ("fast", 3, gwei * U256::from(2))] {
+        let arbitrageur = SimpleArbitrageur::new(name, vec![])
+            .with_gas_limit(100_000)
+            .with_gas_price(gas_price);
+        manager.activate_agent(
+            AgentType::SimpleArbitrageur(arbitrageur),
+            B160::from_low_u64_be(address),
+        )?;
+        manager.fund_agent(name, U256::from(10_u64).pow(U256::from(18)))?;
+    }
+
+    // Pays out to the first caller only, like an arbitrage that closes the spread: it stores the caller in slot 0 and reverts once the slot is taken.
+    let opportunity = crate::tests::runtime_contract(&[
+        0x60, 0x00, // PUSH1 0
+        0x54, // SLOAD
+        0x60, 0x0b, // PUSH1 0x0b
+        0x57, // JUMPI
+        0x33, // CALLER
+        0x60, 0x00, // PUSH1 0
+        0x55, // SSTORE
+        0x00, // STOP
+        0x5b, // JUMPDEST
+        0x60, 0x00, // PUSH1 0
+        0x80, // DUP1
+        0xfd, // REVERT
+    ]);
+    let opportunity = opportunity.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+
+    // Both arbitrageurs see the same wide spread.
+    let wad = U256::from(10_u64).pow(U256::from(18));
+    let prices = [wad * U256::from(1000), wad * U256::from(1100)];
+    let execution_results = manager.run_agents(|agent| match agent {
+        AgentType::SimpleArbitrageur(arbitrageur) => {
+            *arbitrageur.prices.lock().unwrap() = prices;
+            arbitrageur.should_arbitrage(prices).then_some((
+                opportunity.address,
+                Bytes::new(),
+                U256::ZERO,
+            ))
+        }
+        AgentType::User(_) | AgentType::PriceFeed(_) => None,
+    })?;
+
+    assert_eq!(execution_results.len(), 2);
+    assert!(execution_results[0].is_success());
+    assert!(matches!(
+        execution_results[1],
+        ExecutionResult::Revert { .. }
+    ));
+    let db = manager.environment.evm.db.as_ref().unwrap();
+    let winner = db.accounts[&opportunity.address].storage[&U256::ZERO];
+    assert_eq!(winner, U256::from(3));
+    Ok(())
+}
+
+#[test]
+fn agent_gas_limit_is_enforced() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let alice = AgentType::User(User::new("alice", None).with_gas_limit(50_000));
+    manager.activate_agent(alice, B160::from_low_u64_be(2))?;
+
+    // Loops forever, so any gas limit runs out.
+    let spinner = crate::tests::runtime_contract(&[
+        0x5b, // JUMPDEST
+        0x60, 0x00, // PUSH1 0
+        0x56, // JUMP
+    ]);
+    let spinner = spinner.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+
+    let (execution_result, _) =
+        manager.call_with_return_data("alice", spinner.address, Bytes::new())?;
+    assert!(matches!(
+        execution_result,
+        ExecutionResult::Halt {
+            gas_used: 50_000,
+            ..
+        }
+    ));
+    Ok(())
+}
+
+#[test]
+fn failed_deployment_returns_an_error() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    // An init code that reverts right away.
+    let reverting = SimulationContract::new(
+        ethers::abi::Contract::default(),
+        vec![0x60, 0x00, 0x60, 0x00, 0xfd].into(),
+    );
+    let result = manager.deploy("admin", &reverting, ());
+    assert!(matches!(result, Err(ManagerError::Revert { .. })));
+
+    // A free deployment is rejected once a base fee is set.
+    manager.set_base_fee(U256::from(1));
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let result = manager.deploy("admin", &writer, ());
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+    Ok(())
+}
+
+#[test]
+fn underpriced_transactions_are_rejected() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let writer = manager.deploy(
+        "admin",
+        &SimulationContract::new(
+            bindings::writer::WRITER_ABI.clone(),
+            bindings::writer::WRITER_BYTECODE.clone(),
+        ),
+        (),
+    )?;
+    let gwei = U256::from(1_000_000_000_u64);
+    manager.set_base_fee(gwei);
+
+    // The admin still sends free transactions, which the base fee rules out.
+    let result = manager.call_with_result("admin", &writer, "echoString", "hi".to_string());
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+    let result = manager.approve("admin", writer.address, B160::from_low_u64_be(2), U256::MAX);
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+
+    // So do the calls observed by an inspector and the calls agents make themselves.
+    let call_data = writer.encode_function("echoString", "hi".to_string())?;
+    let result = manager.call_with_inspector(
+        "admin",
+        writer.address,
+        call_data.clone(),
+        crate::inspector::CallTracer::default(),
+    );
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+    let result = manager.call_with_return_data("admin", writer.address, call_data.clone());
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+    let result = manager.call_with_precompile_profile("admin", writer.address, call_data.clone());
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+    let result = manager.agents.get("admin").unwrap().call_contract(
+        &mut manager.environment,
+        &writer,
+        call_data,
+        U256::ZERO,
+    );
+    assert!(matches!(result, Err(ManagerError::Rejected(_))));
+
+    let admin = B160::from_low_u64_be(1);
+    manager.set_balance(admin, U256::from(10_u64).pow(U256::from(18)));
+    manager.set_transact_settings(
+        "admin",
+        TransactSettings {
+            gas_limit: 1_000_000,
+            gas_price: gwei * U256::from(2),
+            gas_priority_fee: None,
+        },
+    )?;
+    let execution_result =
+        manager.call_with_result("admin", &writer, "echoString", "hi".to_string())?;
+    assert!(execution_result.is_success());
+    assert!(manager
+        .set_transact_settings("missing", TransactSettings::default())
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn gas_price_clamps_unlimited_gas_limit() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let gas_price = U256::from(1_000_000_000_u64);
+    let alice = User::new("alice", None).with_gas_price(gas_price);
+    assert_eq!(
+        alice.transact_settings.gas_limit,
+        crate::agent::PRICED_GAS_LIMIT
+    );
+    let alice_address = B160::from_low_u64_be(2);
+    manager.activate_agent(AgentType::User(alice), alice_address)?;
+    let balance = U256::from(10_u64).pow(U256::from(18));
+    manager.set_balance(alice_address, balance);
+
+    // The balance covers the clamped gas limit, so the transfer is not rejected.
+    manager.call_with_value("alice", B160::from_low_u64_be(3), Bytes::new(), U256::ZERO)?;
+    let db = manager.environment.evm.db().unwrap();
+    assert_eq!(
+        db.accounts[&alice_address].info.balance,
+        balance - U256::from(21_000) * gas_price
+    );
+    Ok(())
+}
+
+#[test]
+fn agent_pays_for_gas() -> Result<(), ManagerError> {
+    let mut manager = SimulationManager::new();
+    let gas_price = U256::from(1_000_000_000_u64);
+    let alice = AgentType::User(
+        User::new("alice", None)
+            .with_gas_limit(1_000_000)
+            .with_gas_price(gas_price),
+    );
+    let alice_address = B160::from_low_u64_be(2);
+    manager.activate_agent(alice, alice_address)?;
+    let balance = U256::from(10_u64).pow(U256::from(18));
+    manager.set_balance(alice_address, balance);
+
+    let writer = SimulationContract::new(
+        bindings::writer::WRITER_ABI.clone(),
+        bindings::writer::WRITER_BYTECODE.clone(),
+    );
+    let writer = writer.deploy(
+        &mut manager.environment,
+        manager.agents.get("admin").unwrap(),
+        (),
+    );
+    let execution_result =
+        manager.call_with_result("alice", &writer, "echoString", "Hello, world!".to_string())?;
+    assert!(execution_result.is_success());
+
+    let db = manager.environment.evm.db().unwrap();
+    assert_eq!(
+        db.accounts[&alice_address].info.balance,
+        balance - U256::from(execution_result.gas_used()) * gas_price
+    );
+    Ok(())
 }
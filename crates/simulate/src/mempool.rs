@@ -0,0 +1,312 @@
+#![warn(missing_docs)]
+//! Pending transactions that wait for the next block along with the policy that orders them.
+
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter, Result as FmtResult},
+};
+
+use revm::primitives::TxEnv;
+
+/// A transaction that waits in the [`Mempool`] to be included in a block.
+/// # Fields
+/// * `sender` - Name of the agent that submitted the transaction.
+/// * `tx` - The transaction environment that is executed upon inclusion. Its nonce is always set.
+/// * `arrival` - Sequence number of the submission, lower numbers arrived earlier.
+#[derive(Debug, Clone)]
+pub struct PendingTx {
+    /// Name of the agent that submitted the transaction.
+    pub sender: String,
+    /// The transaction environment that is executed upon inclusion. Its nonce is always set.
+    pub tx: TxEnv,
+    /// Sequence number of the submission, lower numbers arrived earlier.
+    pub arrival: u64,
+}
+
+/// A pending transaction that the EVM rejected when its block was built, e.g., because the sender could not pay for its gas.
+/// # Fields
+/// * `pending_tx` - The rejected transaction.
+/// * `block_number` - The number of the block the transaction was dropped from.
+/// * `reason` - Why the EVM rejected the transaction.
+#[derive(Debug, Clone)]
+pub struct RejectedTx {
+    /// The rejected transaction.
+    pub pending_tx: PendingTx,
+    /// The number of the block the transaction was dropped from.
+    pub block_number: u64,
+    /// Why the EVM rejected the transaction.
+    pub reason: String,
+}
+
+/// A user-provided block-building algorithm. It receives the pending transactions and returns the indices of those to include, in inclusion order.
+pub type CustomOrdering = Box<dyn Fn(&[PendingTx]) -> Vec<usize>>;
+
+/// Decides which pending transactions go into a block and in which order.
+/// # Variants
+/// * `Fifo` - Include every transaction in the order it arrived.
+/// * `GasPrice` - Include every transaction, highest gas price first and ties in arrival order. The transactions of one sender keep their nonce order.
+/// * `Custom` - Include the transactions a user-provided algorithm picks, in its order.
+#[derive(Default)]
+pub enum OrderingPolicy {
+    /// Include every transaction in the order it arrived.
+    #[default]
+    Fifo,
+    /// Include every transaction, highest gas price first and ties in arrival order. The transactions of one sender keep their nonce order.
+    GasPrice,
+    /// Include the transactions a user-provided algorithm picks, in its order.
+    Custom(CustomOrdering),
+}
+
+impl Debug for OrderingPolicy {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            OrderingPolicy::Fifo => write!(f, "Fifo"),
+            OrderingPolicy::GasPrice => write!(f, "GasPrice"),
+            OrderingPolicy::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+impl OrderingPolicy {
+    /// Returns the indices of the pending transactions to include, in inclusion order.
+    /// # Arguments
+    /// * `pending` - The pending transactions in arrival order.
+    pub fn order(&self, pending: &[PendingTx]) -> Vec<usize> {
+        match self {
+            OrderingPolicy::Fifo => (0..pending.len()).collect(),
+            OrderingPolicy::GasPrice => {
+                let mut order: Vec<usize> = (0..pending.len()).collect();
+                // The sort is stable, so ties stay in arrival order.
+                order.sort_by(|a, b| pending[*b].tx.gas_price.cmp(&pending[*a].tx.gas_price));
+                // Refill the slots of every sender with its transactions in nonce order, so a later transaction never overtakes an earlier one.
+                let mut by_sender: HashMap<&str, Vec<usize>> = HashMap::new();
+                for index in order.iter() {
+                    by_sender
+                        .entry(pending[*index].sender.as_str())
+                        .or_default()
+                        .push(*index);
+                }
+                for indices in by_sender.values_mut() {
+                    indices.sort_by_key(|index| std::cmp::Reverse(pending[*index].tx.nonce));
+                }
+                order
+                    .iter()
+                    .map(|index| {
+                        by_sender
+                            .get_mut(pending[*index].sender.as_str())
+                            .and_then(|indices| indices.pop())
+                            .unwrap() // Every sender has as many slots as transactions.
+                    })
+                    .collect()
+            }
+            OrderingPolicy::Custom(custom) => custom(pending),
+        }
+    }
+}
+
+/// Pending transactions that wait for the next block.
+/// # Fields
+/// * `pending` - The pending transactions in arrival order.
+/// * `policy` - The policy that orders the transactions of a block.
+/// * `rejected` - The transactions the EVM rejected while building blocks, oldest first.
+/// * `arrivals` - The number of transactions submitted so far.
+#[derive(Debug, Default)]
+pub struct Mempool {
+    /// The pending transactions in arrival order.
+    pub pending: Vec<PendingTx>,
+    /// The policy that orders the transactions of a block.
+    pub policy: OrderingPolicy,
+    /// The transactions the EVM rejected while building blocks, oldest first.
+    pub rejected: Vec<RejectedTx>,
+    /// The number of transactions submitted so far.
+    arrivals: u64,
+}
+
+impl Mempool {
+    /// Returns the nonce the next transaction of a sender takes, i.e., one past its last pending transaction or its account nonce if it has none pending.
+    /// # Arguments
+    /// * `sender` - Name of the agent.
+    /// * `account_nonce` - The current account nonce of the agent.
+    pub fn next_nonce(&self, sender: &str, account_nonce: u64) -> u64 {
+        self.pending
+            .iter()
+            .filter(|pending_tx| pending_tx.sender == sender)
+            .filter_map(|pending_tx| pending_tx.tx.nonce)
+            .map(|nonce| nonce + 1)
+            .fold(account_nonce, u64::max)
+    }
+
+    /// Adds a transaction to the pending transactions.
+    /// A transaction without a nonce takes the sender's next nonce, see [`Mempool::next_nonce`]. A transaction with a nonce must carry exactly that one.
+    /// # Arguments
+    /// * `sender` - Name of the agent that submitted the transaction.
+    /// * `tx` - The transaction environment.
+    /// * `account_nonce` - The current account nonce of the sender.
+    /// # Returns
+    /// * `Ok(u64)` - The nonce of the queued transaction.
+    /// * `Err(String)` - The nonce is already used by the account or by a pending transaction, or it leaves a gap.
+    pub fn submit(
+        &mut self,
+        sender: String,
+        mut tx: TxEnv,
+        account_nonce: u64,
+    ) -> Result<u64, String> {
+        let next_nonce = self.next_nonce(&sender, account_nonce);
+        match tx.nonce {
+            Some(nonce) if nonce < next_nonce => {
+                return Err(format!(
+                    "nonce {} of {} is already used, the next nonce is {}",
+                    nonce, sender, next_nonce
+                ))
+            }
+            Some(nonce) if nonce > next_nonce => {
+                return Err(format!(
+                    "nonce {} of {} leaves a gap, the next nonce is {}",
+                    nonce, sender, next_nonce
+                ))
+            }
+            _ => tx.nonce = Some(next_nonce),
+        }
+        self.pending.push(PendingTx {
+            sender,
+            tx,
+            arrival: self.arrivals,
+        });
+        self.arrivals += 1;
+        Ok(next_nonce)
+    }
+
+    /// Takes the transactions of the next block out of the mempool in inclusion order. Transactions the policy leaves out stay pending.
+    /// # Returns
+    /// * `Ok(Vec<PendingTx>)` - The transactions of the block in inclusion order.
+    /// * `Err(String)` - The policy returned an index that is out of range or repeated, or it includes the transactions of a sender out of nonce order.
+    pub fn take_block(&mut self) -> Result<Vec<PendingTx>, String> {
+        let order = self.policy.order(&self.pending);
+        let mut included = vec![false; self.pending.len()];
+        for index in order.iter() {
+            match included.get_mut(*index) {
+                Some(seen) if *seen => {
+                    return Err(format!("transaction {} is included twice", index))
+                }
+                Some(seen) => *seen = true,
+                None => return Err(format!("transaction {} does not exist", index)),
+            }
+        }
+
+        // Every sender's included transactions must be its lowest pending nonces in increasing order.
+        let mut expected_nonces: HashMap<&str, u64> = HashMap::new();
+        for pending_tx in self.pending.iter() {
+            let nonce = pending_tx.tx.nonce.unwrap_or_default(); // Submission always sets the nonce.
+            expected_nonces
+                .entry(pending_tx.sender.as_str())
+                .and_modify(|expected| *expected = (*expected).min(nonce))
+                .or_insert(nonce);
+        }
+        for index in order.iter() {
+            let pending_tx = &self.pending[*index];
+            let nonce = pending_tx.tx.nonce.unwrap_or_default();
+            let expected = expected_nonces.get_mut(pending_tx.sender.as_str()).unwrap(); // Every sender of a pending transaction has an entry.
+            if nonce != *expected {
+                return Err(format!(
+                    "transaction {} of {} has nonce {} but nonce {} comes first",
+                    index, pending_tx.sender, nonce, expected
+                ));
+            }
+            *expected += 1;
+        }
+
+        let block = order
+            .iter()
+            .map(|index| self.pending[*index].clone())
+            .collect();
+        let mut index = 0;
+        self.pending.retain(|_| {
+            index += 1;
+            !included[index - 1]
+        });
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use revm::primitives::{TxEnv, U256};
+
+    use super::{Mempool, OrderingPolicy};
+
+    fn priced_tx(gas_price: u64) -> TxEnv {
+        TxEnv {
+            gas_price: U256::from(gas_price),
+            ..TxEnv::default()
+        }
+    }
+
+    #[test]
+    fn submit_assigns_and_enforces_nonces() {
+        let mut mempool = Mempool::default();
+        assert_eq!(mempool.submit("alice".into(), TxEnv::default(), 5), Ok(5));
+        assert_eq!(mempool.submit("alice".into(), TxEnv::default(), 5), Ok(6));
+        assert_eq!(mempool.submit("bob".into(), TxEnv::default(), 0), Ok(0));
+
+        // Nonce 6 is pending and nonce 4 is used by the account, nonce 8 skips nonce 7.
+        for nonce in [6, 4, 8] {
+            let tx = TxEnv {
+                nonce: Some(nonce),
+                ..TxEnv::default()
+            };
+            assert!(mempool.submit("alice".into(), tx, 5).is_err());
+        }
+        let tx = TxEnv {
+            nonce: Some(7),
+            ..TxEnv::default()
+        };
+        assert_eq!(mempool.submit("alice".into(), tx, 5), Ok(7));
+        assert_eq!(mempool.pending.len(), 4);
+    }
+
+    #[test]
+    fn gas_price_ordering_keeps_nonce_order() {
+        let mut mempool = Mempool {
+            policy: OrderingPolicy::GasPrice,
+            ..Mempool::default()
+        };
+        mempool.submit("alice".into(), priced_tx(1), 0).unwrap();
+        mempool.submit("alice".into(), priced_tx(3), 0).unwrap();
+        mempool.submit("bob".into(), priced_tx(2), 0).unwrap();
+
+        // Alice's second transaction pays the most, but it takes the slot after her first one.
+        let block: Vec<(String, Option<u64>)> = mempool
+            .take_block()
+            .unwrap()
+            .into_iter()
+            .map(|pending_tx| (pending_tx.sender, pending_tx.tx.nonce))
+            .collect();
+        assert_eq!(
+            block,
+            vec![
+                ("alice".to_string(), Some(0)),
+                ("bob".to_string(), Some(0)),
+                ("alice".to_string(), Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn take_block_rejects_nonce_reordering() {
+        let mut mempool = Mempool {
+            policy: OrderingPolicy::Custom(Box::new(|pending| (0..pending.len()).rev().collect())),
+            ..Mempool::default()
+        };
+        mempool.submit("alice".into(), TxEnv::default(), 0).unwrap();
+        mempool.submit("alice".into(), TxEnv::default(), 0).unwrap();
+        assert!(mempool.take_block().is_err());
+        assert_eq!(mempool.pending.len(), 2);
+
+        // Leaving out the earlier nonce is a gap as well.
+        mempool.policy = OrderingPolicy::Custom(Box::new(|_| vec![1]));
+        assert!(mempool.take_block().is_err());
+        mempool.policy = OrderingPolicy::Custom(Box::new(|_| vec![0]));
+        assert_eq!(mempool.take_block().unwrap()[0].tx.nonce, Some(0));
+        assert_eq!(mempool.pending.len(), 1);
+    }
+}
@@ -0,0 +1,565 @@
+#![warn(missing_docs)]
+//! Merkle-Patricia proofs of accounts and storage slots of the simulated state, mirroring `eth_getProof`.
+//! The state root and storage roots are computed over the in-memory database the same way Ethereum clients compute them.
+
+use bytes::Bytes;
+use revm::{
+    db::{AccountState, CacheDB, DbAccount},
+    primitives::{keccak256, B160, B256, KECCAK_EMPTY, U256},
+};
+
+use crate::fork::ForkDB;
+
+/// A key of the trie split into nibbles and the RLP encoded value stored under it.
+type Entry = (Vec<u8>, Vec<u8>);
+
+/// Proof of a storage slot of a [`StateProof`].
+/// # Fields
+/// * `key` - The storage slot.
+/// * `value` - The value of the slot, zero if the slot is empty.
+/// * `proof` - The RLP encoded trie nodes from the storage root to the slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageProof {
+    /// The storage slot.
+    pub key: U256,
+    /// The value of the slot, zero if the slot is empty.
+    pub value: U256,
+    /// The RLP encoded trie nodes from the storage root to the slot.
+    pub proof: Vec<Bytes>,
+}
+
+/// Proof of an account and some of its storage slots against the state root, mirroring the response of `eth_getProof`.
+/// # Fields
+/// * `address` - The address of the account.
+/// * `balance` - The balance of the account.
+/// * `nonce` - The nonce of the account.
+/// * `code_hash` - The hash of the runtime bytecode of the account.
+/// * `storage_hash` - The root of the storage trie of the account.
+/// * `state_root` - The root of the state trie the account is proven against.
+/// * `account_proof` - The RLP encoded trie nodes from the state root to the account.
+/// * `storage_proof` - The proofs of the requested storage slots against the storage root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateProof {
+    /// The address of the account.
+    pub address: B160,
+    /// The balance of the account.
+    pub balance: U256,
+    /// The nonce of the account.
+    pub nonce: u64,
+    /// The hash of the runtime bytecode of the account.
+    pub code_hash: B256,
+    /// The root of the storage trie of the account.
+    pub storage_hash: B256,
+    /// The root of the state trie the account is proven against.
+    pub state_root: B256,
+    /// The RLP encoded trie nodes from the state root to the account.
+    pub account_proof: Vec<Bytes>,
+    /// The proofs of the requested storage slots against the storage root.
+    pub storage_proof: Vec<StorageProof>,
+}
+
+impl StateProof {
+    /// Builds the proof of an account and some of its storage slots against the current state of a database.
+    /// # Arguments
+    /// * `db` - The database of the simulation.
+    /// * `address` - The address of the account.
+    /// * `slots` - The storage slots to prove.
+    pub(crate) fn new(db: &CacheDB<ForkDB>, address: B160, slots: &[U256]) -> Self {
+        let state = Trie::new(
+            db.accounts
+                .iter()
+                .filter(|(_, account)| !matches!(account.account_state, AccountState::NotExisting))
+                .map(|(address, account)| {
+                    (
+                        keccak256(address.as_bytes()),
+                        encode_account(
+                            account.info.nonce,
+                            account.info.balance,
+                            storage_trie(account).root(),
+                            account.info.code_hash,
+                        ),
+                    )
+                })
+                .collect(),
+        );
+        let account = db
+            .accounts
+            .get(&address)
+            .filter(|account| !matches!(account.account_state, AccountState::NotExisting));
+        let storage = account.map(storage_trie).unwrap_or_default();
+
+        Self {
+            address,
+            balance: account.map_or(U256::ZERO, |account| account.info.balance),
+            nonce: account.map_or(0, |account| account.info.nonce),
+            code_hash: account.map_or(KECCAK_EMPTY, |account| account.info.code_hash),
+            storage_hash: storage.root(),
+            state_root: state.root(),
+            account_proof: state.proof(keccak256(address.as_bytes())),
+            storage_proof: slots
+                .iter()
+                .map(|slot| StorageProof {
+                    key: *slot,
+                    value: account
+                        .and_then(|account| account.storage.get(slot).copied())
+                        .unwrap_or_default(),
+                    proof: storage.proof(keccak256(&slot.to_be_bytes::<32>())),
+                })
+                .collect(),
+        }
+    }
+
+    /// Verifies the account proof against the state root and every storage proof against the storage root.
+    /// # Returns
+    /// * `bool` - Whether the account and every storage slot are proven.
+    pub fn verify(&self) -> bool {
+        let account = match verify_proof(
+            self.state_root,
+            keccak256(self.address.as_bytes()),
+            &self.account_proof,
+        ) {
+            Ok(account) => account,
+            Err(_) => return false,
+        };
+        let expected = encode_account(self.nonce, self.balance, self.storage_hash, self.code_hash);
+        let account_proven = match account {
+            Some(account) => account == expected,
+            // An absent account is proven if it is empty.
+            None => {
+                self.nonce == 0
+                    && self.balance == U256::ZERO
+                    && self.code_hash == KECCAK_EMPTY
+                    && self.storage_hash == Trie::default().root()
+            }
+        };
+        account_proven
+            && self.storage_proof.iter().all(|storage_proof| {
+                match verify_proof(
+                    self.storage_hash,
+                    keccak256(&storage_proof.key.to_be_bytes::<32>()),
+                    &storage_proof.proof,
+                ) {
+                    Ok(Some(value)) => value == encode_bytes(&u256_bytes(storage_proof.value)),
+                    Ok(None) => storage_proof.value == U256::ZERO,
+                    Err(_) => false,
+                }
+            })
+    }
+}
+
+/// Verifies a Merkle-Patricia proof of a key against a trie root.
+/// # Arguments
+/// * `root` - The root of the trie.
+/// * `key` - The hashed key to prove.
+/// * `proof` - The RLP encoded trie nodes from the root to the key.
+/// # Returns
+/// * `Ok(Some(Vec<u8>))` - The RLP encoded value stored under the key.
+/// * `Ok(None)` - The proof shows that the key is absent from the trie.
+/// * `Err(String)` - The proof is malformed or does not match the root.
+pub fn verify_proof(root: B256, key: B256, proof: &[Bytes]) -> Result<Option<Vec<u8>>, String> {
+    let key = nibbles(key.as_bytes());
+    let mut nodes = proof.iter();
+    let mut node = next_node(&mut nodes, root)?;
+    let mut depth = 0;
+    loop {
+        if !is_list(&node) {
+            // Only the root of an empty trie is not a list.
+            if decode_bytes(&node)?.is_empty() {
+                return Ok(None);
+            }
+            return Err("trie node is not a list".to_string());
+        }
+        let items = decode_list(&node)?;
+        let child = match items.len() {
+            17 => {
+                let nibble = *key.get(depth).ok_or("key ends at a branch node")?;
+                depth += 1;
+                items[nibble as usize]
+            }
+            2 => {
+                let (path, leaf) = decode_hex_prefix(decode_bytes(items[0])?)?;
+                if leaf {
+                    if key[depth..] != path[..] {
+                        return Ok(None);
+                    }
+                    return Ok(Some(decode_bytes(items[1])?.to_vec()));
+                }
+                if !key[depth..].starts_with(&path) {
+                    return Ok(None);
+                }
+                depth += path.len();
+                items[1]
+            }
+            _ => return Err(format!("trie node has {} items", items.len())),
+        };
+        node = if is_list(child) {
+            // Nodes shorter than 32 bytes are embedded in their parent.
+            child.to_vec()
+        } else {
+            let hash = decode_bytes(child)?;
+            if hash.is_empty() {
+                return Ok(None);
+            }
+            if hash.len() != 32 {
+                return Err("trie node reference is not a hash".to_string());
+            }
+            next_node(&mut nodes, B256::from_slice(hash))?
+        };
+    }
+}
+
+/// Takes the next node of a proof and checks that it matches the hash its parent references.
+fn next_node<'a>(
+    nodes: &mut impl Iterator<Item = &'a Bytes>,
+    hash: B256,
+) -> Result<Vec<u8>, String> {
+    let node = nodes.next().ok_or("proof is missing a trie node")?;
+    if keccak256(node) != hash {
+        return Err("trie node does not match its hash".to_string());
+    }
+    Ok(node.to_vec())
+}
+
+/// A Merkle-Patricia trie over hashed keys, built at once from all of its entries.
+#[derive(Debug, Default)]
+struct Trie {
+    /// The entries sorted by key.
+    entries: Vec<Entry>,
+}
+
+impl Trie {
+    /// Builds a trie from hashed keys and the RLP encoded values stored under them.
+    fn new(entries: Vec<(B256, Vec<u8>)>) -> Self {
+        let mut entries: Vec<Entry> = entries
+            .into_iter()
+            .map(|(key, value)| (nibbles(key.as_bytes()), value))
+            .collect();
+        entries.sort();
+        Self { entries }
+    }
+
+    /// The root hash of the trie.
+    fn root(&self) -> B256 {
+        keccak256(&encode_node(&self.entries, 0, None, &mut Vec::new()))
+    }
+
+    /// The RLP encoded nodes from the root to a key, which prove either its value or its absence.
+    fn proof(&self, key: B256) -> Vec<Bytes> {
+        let key = nibbles(key.as_bytes());
+        let mut nodes = Vec::new();
+        encode_node(&self.entries, 0, Some(&key), &mut nodes);
+        // Nodes are collected from the key up to the root. Besides the root, nodes shorter than 32 bytes are embedded in their parent.
+        nodes
+            .into_iter()
+            .rev()
+            .enumerate()
+            .filter(|(index, node)| *index == 0 || node.len() >= 32)
+            .map(|(_, node)| Bytes::from(node))
+            .collect()
+    }
+}
+
+/// The storage trie of an account. Empty slots are not part of the trie.
+fn storage_trie(account: &DbAccount) -> Trie {
+    Trie::new(
+        account
+            .storage
+            .iter()
+            .filter(|(_, value)| **value != U256::ZERO)
+            .map(|(slot, value)| {
+                (
+                    keccak256(&slot.to_be_bytes::<32>()),
+                    encode_bytes(&u256_bytes(*value)),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// RLP encodes the node of a trie that holds a sorted set of entries whose keys share their first `depth` nibbles.
+/// # Arguments
+/// * `entries` - The entries below the node, sorted by key.
+/// * `depth` - The number of nibbles the keys share above the node.
+/// * `target` - The key to prove, if it runs through the node.
+/// * `proof` - Collects the nodes the key runs through, from the key up to the root.
+fn encode_node(
+    entries: &[Entry],
+    depth: usize,
+    target: Option<&[u8]>,
+    proof: &mut Vec<Vec<u8>>,
+) -> Vec<u8> {
+    let node = match entries {
+        [] => encode_bytes(&[]),
+        [(key, value)] => encode_list(&[
+            encode_bytes(&hex_prefix(&key[depth..], true)),
+            encode_bytes(value),
+        ]),
+        _ => {
+            // The entries are sorted, so the prefix all keys share is the one the first and last key share.
+            let (first, last) = (&entries[0].0, &entries[entries.len() - 1].0);
+            let shared = first[depth..]
+                .iter()
+                .zip(&last[depth..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if shared > 0 {
+                let path = &first[depth..depth + shared];
+                let target = target.filter(|target| &target[depth..depth + shared] == path);
+                let child = encode_node(entries, depth + shared, target, proof);
+                encode_list(&[encode_bytes(&hex_prefix(path, false)), reference(child)])
+            } else {
+                let mut items = Vec::with_capacity(17);
+                let mut rest = entries;
+                for nibble in 0..16 {
+                    let count = rest
+                        .iter()
+                        .take_while(|(key, _)| key[depth] == nibble)
+                        .count();
+                    let (group, remaining) = rest.split_at(count);
+                    rest = remaining;
+                    if group.is_empty() {
+                        items.push(encode_bytes(&[]));
+                    } else {
+                        let target = target.filter(|target| target[depth] == nibble);
+                        items.push(reference(encode_node(group, depth + 1, target, proof)));
+                    }
+                }
+                // Keys all have the same length, so no value ends at a branch.
+                items.push(encode_bytes(&[]));
+                encode_list(&items)
+            }
+        }
+    };
+    if target.is_some() {
+        proof.push(node.clone());
+    }
+    node
+}
+
+/// How a parent references a node: nodes shorter than 32 bytes are embedded, longer ones are referenced by their hash.
+fn reference(node: Vec<u8>) -> Vec<u8> {
+    if node.len() < 32 {
+        return node;
+    }
+    encode_bytes(keccak256(&node).as_bytes())
+}
+
+/// RLP encodes an account of the state trie.
+fn encode_account(nonce: u64, balance: U256, storage_root: B256, code_hash: B256) -> Vec<u8> {
+    encode_list(&[
+        encode_bytes(trim_leading_zeros(&nonce.to_be_bytes())),
+        encode_bytes(&u256_bytes(balance)),
+        encode_bytes(storage_root.as_bytes()),
+        encode_bytes(code_hash.as_bytes()),
+    ])
+}
+
+/// The big-endian bytes of a `U256` without leading zeros, as RLP encodes integers.
+fn u256_bytes(value: U256) -> Vec<u8> {
+    trim_leading_zeros(&value.to_be_bytes::<32>()).to_vec()
+}
+
+/// Strips the leading zero bytes of a big-endian integer.
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let first = bytes
+        .iter()
+        .position(|byte| *byte != 0)
+        .unwrap_or(bytes.len());
+    &bytes[first..]
+}
+
+/// Splits bytes into nibbles, high nibble first.
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0x0f])
+        .collect()
+}
+
+/// Compacts a path of nibbles into bytes with the hex-prefix encoding, which flags leaves and paths of odd length.
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 };
+    let mut encoded = match path.len() % 2 {
+        1 => vec![((flag + 1) << 4) | path[0]],
+        _ => vec![flag << 4],
+    };
+    encoded.extend(
+        path[path.len() % 2..]
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair[1]),
+    );
+    encoded
+}
+
+/// Expands a hex-prefix encoded path into its nibbles and whether it belongs to a leaf.
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), String> {
+    let first = *encoded.first().ok_or("trie node path is empty")?;
+    // The odd flag means the first nibble is stored next to the flags.
+    let mut path = if first & 0x10 != 0 {
+        vec![first & 0x0f]
+    } else {
+        Vec::new()
+    };
+    path.extend(nibbles(&encoded[1..]));
+    Ok((path, first & 0x20 != 0))
+}
+
+/// RLP encodes a byte string.
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut encoded = encode_length(bytes.len(), 0x80);
+    encoded.extend_from_slice(bytes);
+    encoded
+}
+
+/// RLP encodes a list of already encoded items.
+fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    let mut encoded = encode_length(payload.len(), 0xc0);
+    encoded.extend(payload);
+    encoded
+}
+
+/// The RLP header of a string (offset `0x80`) or list (offset `0xc0`) with a payload of the given length.
+fn encode_length(length: usize, offset: u8) -> Vec<u8> {
+    if length < 56 {
+        return vec![offset + length as u8];
+    }
+    let length_bytes = trim_leading_zeros(&length.to_be_bytes()).to_vec();
+    let mut header = vec![offset + 55 + length_bytes.len() as u8];
+    header.extend(length_bytes);
+    header
+}
+
+/// Whether an RLP item is a list.
+fn is_list(item: &[u8]) -> bool {
+    item.first().map_or(false, |prefix| *prefix >= 0xc0)
+}
+
+/// The lengths of the header and payload of the RLP item at the start of the data.
+fn decode_header(data: &[u8]) -> Result<(usize, usize), String> {
+    let prefix = *data.first().ok_or("RLP item is empty")?;
+    let long_length = |length_of_length: usize| -> Result<(usize, usize), String> {
+        let length_bytes = data
+            .get(1..1 + length_of_length)
+            .ok_or("RLP item is truncated")?;
+        let length = length_bytes
+            .iter()
+            .fold(0_usize, |length, byte| (length << 8) | *byte as usize);
+        Ok((1 + length_of_length, length))
+    };
+    let (header, payload) = match prefix {
+        0x00..=0x7f => (0, 1),
+        0x80..=0xb7 => (1, (prefix - 0x80) as usize),
+        0xb8..=0xbf => long_length((prefix - 0xb7) as usize)?,
+        0xc0..=0xf7 => (1, (prefix - 0xc0) as usize),
+        0xf8..=0xff => long_length((prefix - 0xf7) as usize)?,
+    };
+    if data.len() < header + payload {
+        return Err("RLP item is truncated".to_string());
+    }
+    Ok((header, payload))
+}
+
+/// Decodes an RLP byte string.
+fn decode_bytes(item: &[u8]) -> Result<&[u8], String> {
+    if is_list(item) {
+        return Err("RLP item is a list, not a string".to_string());
+    }
+    let (header, payload) = decode_header(item)?;
+    Ok(&item[header..header + payload])
+}
+
+/// Splits an RLP list into its encoded items.
+fn decode_list(item: &[u8]) -> Result<Vec<&[u8]>, String> {
+    if !is_list(item) {
+        return Err("RLP item is a string, not a list".to_string());
+    }
+    let (header, payload) = decode_header(item)?;
+    let mut payload = &item[header..header + payload];
+    let mut items = Vec::new();
+    while !payload.is_empty() {
+        let (item_header, item_payload) = decode_header(payload)?;
+        let (item, rest) = payload.split_at(item_header + item_payload);
+        items.push(item);
+        payload = rest;
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use revm::{
+        db::CacheDB,
+        primitives::{AccountInfo, B160, B256, U256},
+    };
+
+    use super::{StateProof, Trie};
+    use crate::{fork::ForkDB, manager::SimulationManager};
+
+    fn b256(hex: &str) -> B256 {
+        B256::from_slice(&ethers::utils::hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn storage_proof_verifies_against_root() {
+        // The root of the empty trie is the well-known `keccak256(rlp(""))`.
+        assert_eq!(
+            Trie::default().root(),
+            b256("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421")
+        );
+
+        // Known answers from an independent reference trie, itself checked against the `doe`, `dog`, `dogglesworth` example root `0x8aad789d...`.
+        let address = B160::from_low_u64_be(0xb41d6e);
+        let mut db = CacheDB::new(ForkDB::default());
+        db.insert_account_info(
+            address,
+            AccountInfo {
+                nonce: 1,
+                ..Default::default()
+            },
+        );
+        for slot in 0..20_u64 {
+            db.insert_account_storage(address, U256::from(slot), U256::from(slot * 1000 + 1))
+                .unwrap();
+        }
+        let proof = StateProof::new(&db, address, &[]);
+        let storage_hash = b256("64d51dc02612cd687fc8fd8267c3a4cff667c182c6471f133eb355974eb709a4");
+        assert_eq!(proof.storage_hash, storage_hash);
+        assert_eq!(
+            proof.state_root,
+            b256("01a66c32e7dfa37b526b0b5f664314cdcb632233eb9e8b652c33a134ffbafb56")
+        );
+
+        let mut manager = SimulationManager::default();
+        let db = manager.environment.evm.db().unwrap();
+        db.insert_account_info(
+            address,
+            AccountInfo {
+                nonce: 1,
+                ..Default::default()
+            },
+        );
+        for slot in 0..20_u64 {
+            db.insert_account_storage(address, U256::from(slot), U256::from(slot * 1000 + 1))
+                .unwrap();
+        }
+
+        let proof = manager.state_proof(address, &[U256::from(7), U256::from(100)]);
+        assert_eq!(proof.nonce, 1);
+        assert_eq!(proof.storage_hash, storage_hash);
+        assert_eq!(proof.storage_proof[0].value, U256::from(7001));
+        // Slot 100 is empty, which the proof shows through the absence of its key.
+        assert_eq!(proof.storage_proof[1].value, U256::ZERO);
+        assert!(proof.verify());
+
+        let mut tampered = proof.clone();
+        tampered.storage_proof[0].value = U256::from(7002);
+        assert!(!tampered.verify());
+        let mut tampered = proof;
+        tampered.nonce = 2;
+        assert!(!tampered.verify());
+    }
+}
@@ -0,0 +1,365 @@
+#![warn(missing_docs)]
+//! Export and import of the simulated state in several serialization formats.
+
+use std::collections::BTreeMap;
+
+use ethers::types::{Address, Bytes as EthersBytes, H256, U256 as EthersU256};
+use revm::{
+    db::CacheDB,
+    primitives::{keccak256, AccountInfo, Bytecode, B160, KECCAK_EMPTY, U256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{fork::ForkDB, utils::recast_address};
+
+/// Serialization format of an exported state.
+/// # Variants
+/// * `Json` - Human-readable JSON, e.g., for debugging small test states.
+/// * `Bincode` - Compact binary encoding, e.g., for large forked states.
+/// * `MessagePack` - Compact binary encoding that other languages can read as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateFormat {
+    /// Human-readable JSON, e.g., for debugging small test states.
+    Json,
+    /// Compact binary encoding, e.g., for large forked states.
+    Bincode,
+    /// Compact binary encoding that other languages can read as well.
+    MessagePack,
+}
+
+/// Every account of the simulated state.
+/// # Fields
+/// * `accounts` - The accounts keyed by their address.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDump {
+    /// The accounts keyed by their address.
+    pub accounts: BTreeMap<Address, AccountDump>,
+}
+
+/// A simulation saved to disk with [`SimulationManager::save`](crate::manager::SimulationManager::save): the block and configuration environment, the agent roster and every account.
+/// It is stored as pretty-printed JSON with sorted keys, so two saves can be diffed.
+/// # Fields
+/// * `block_number` - The block the simulation was in.
+/// * `timestamp` - The timestamp of the block the simulation was in.
+/// * `block` - The rest of the block environment.
+/// * `config` - The configuration environment of the EVM.
+/// * `agents` - The active agents sorted by name.
+/// * `state` - Every account of the simulated state.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SimulationSave {
+    /// The block the simulation was in.
+    pub block_number: u64,
+    /// The timestamp of the block the simulation was in.
+    pub timestamp: u64,
+    /// The rest of the block environment.
+    pub block: BlockDump,
+    /// The configuration environment of the EVM.
+    pub config: ConfigDump,
+    /// The active agents sorted by name.
+    pub agents: Vec<AgentDump>,
+    /// Every account of the simulated state.
+    pub state: StateDump,
+}
+
+/// The block environment of a [`SimulationSave`] besides its number and timestamp, i.e., the rest of the `block.*` values that contracts read.
+/// # Fields
+/// * `coinbase` - The beneficiary of the block.
+/// * `difficulty` - The difficulty of the block.
+/// * `prevrandao` - The randomness of the block after the merge.
+/// * `base_fee` - The base fee per gas of the block.
+/// * `gas_limit` - The gas limit of the block.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockDump {
+    /// The beneficiary of the block.
+    pub coinbase: Address,
+    /// The difficulty of the block.
+    pub difficulty: EthersU256,
+    /// The randomness of the block after the merge.
+    pub prevrandao: Option<H256>,
+    /// The base fee per gas of the block.
+    pub base_fee: EthersU256,
+    /// The gas limit of the block.
+    pub gas_limit: EthersU256,
+}
+
+/// The configuration environment of a [`SimulationSave`].
+/// # Fields
+/// * `chain_id` - The chain id that contracts read as `block.chainid`.
+/// * `spec_id` - The spec (hardfork) the EVM executes transactions with, as the number of revm's `SpecId`.
+/// * `limit_contract_code_size` - The limit on the size of deployed contracts, the EIP-170 limit if unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfigDump {
+    /// The chain id that contracts read as `block.chainid`.
+    pub chain_id: u64,
+    /// The spec (hardfork) the EVM executes transactions with, as the number of revm's `SpecId`.
+    pub spec_id: u8,
+    /// The limit on the size of deployed contracts, the EIP-170 limit if unset.
+    pub limit_contract_code_size: Option<usize>,
+}
+
+/// The kind of an agent of a [`SimulationSave`], mirroring the variants of [`AgentType`](crate::agent::AgentType).
+/// # Variants
+/// * `User` - A [`User`](crate::agent::user::User).
+/// * `SimpleArbitrageur` - A [`SimpleArbitrageur`](crate::agent::simple_arbitrageur::SimpleArbitrageur).
+/// * `PriceFeed` - A [`PriceFeed`](crate::agent::price_feed::PriceFeed).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgentKind {
+    /// A [`User`](crate::agent::user::User).
+    User,
+    /// A [`SimpleArbitrageur`](crate::agent::simple_arbitrageur::SimpleArbitrageur).
+    SimpleArbitrageur,
+    /// A [`PriceFeed`](crate::agent::price_feed::PriceFeed).
+    PriceFeed,
+}
+
+/// An agent of a [`SimulationSave`]. Event filters hold contract ABIs and are not saved.
+/// # Fields
+/// * `name` - Name of the agent.
+/// * `address` - Address of the agent.
+/// * `kind` - The kind of the agent.
+/// * `gas_limit` - Gas limit of the agent's transactions.
+/// * `gas_price` - Gas price of the agent's transactions.
+/// * `gas_priority_fee` - Priority fee per gas of the agent's EIP-1559 transactions, `None` for legacy transactions.
+/// * `price_feed` - The price path of a [`PriceFeed`](crate::agent::price_feed::PriceFeed), `None` for the other kinds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentDump {
+    /// Name of the agent.
+    pub name: String,
+    /// Address of the agent.
+    pub address: Address,
+    /// The kind of the agent.
+    pub kind: AgentKind,
+    /// Gas limit of the agent's transactions.
+    pub gas_limit: u64,
+    /// Gas price of the agent's transactions.
+    pub gas_price: EthersU256,
+    /// Priority fee per gas of the agent's EIP-1559 transactions, `None` for legacy transactions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gas_priority_fee: Option<EthersU256>,
+    /// The price path of a [`PriceFeed`](crate::agent::price_feed::PriceFeed), `None` for the other kinds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_feed: Option<PriceFeedDump>,
+}
+
+/// The price path of a [`PriceFeed`](crate::agent::price_feed::PriceFeed) in an [`AgentDump`].
+/// # Fields
+/// * `exchange` - The exchange whose price the feed sets.
+/// * `prices` - The price path.
+/// * `next` - The index of the next price to push.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceFeedDump {
+    /// The exchange whose price the feed sets.
+    pub exchange: Address,
+    /// The price path.
+    pub prices: Vec<f64>,
+    /// The index of the next price to push.
+    pub next: usize,
+}
+
+/// An account of a [`StateDump`].
+/// # Fields
+/// * `balance` - The balance of the account.
+/// * `nonce` - The nonce of the account.
+/// * `code` - The runtime bytecode of the account, empty for externally owned accounts.
+/// * `storage` - The storage slots of the account.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountDump {
+    /// The balance of the account.
+    pub balance: EthersU256,
+    /// The nonce of the account.
+    pub nonce: u64,
+    /// The runtime bytecode of the account, empty for externally owned accounts.
+    pub code: EthersBytes,
+    /// The storage slots of the account.
+    pub storage: BTreeMap<EthersU256, EthersU256>,
+}
+
+impl StateDump {
+    /// Collects every account of a database.
+    /// # Arguments
+    /// * `db` - The database of the simulation.
+    pub(crate) fn from_db(db: &CacheDB<ForkDB>) -> Self {
+        let accounts = db
+            .accounts
+            .iter()
+            .map(|(address, account)| {
+                let code = match &account.info.code {
+                    Some(code) => code.original_bytes(),
+                    None => match db.contracts.get(&account.info.code_hash) {
+                        Some(code) => code.original_bytes(),
+                        None => Default::default(),
+                    },
+                };
+                let account_dump = AccountDump {
+                    balance: recast_u256(account.info.balance),
+                    nonce: account.info.nonce,
+                    code: EthersBytes(code),
+                    storage: account
+                        .storage
+                        .iter()
+                        .map(|(slot, value)| (recast_u256(*slot), recast_u256(*value)))
+                        .collect(),
+                };
+                (recast_address(*address), account_dump)
+            })
+            .collect();
+        Self { accounts }
+    }
+
+    /// Writes every account into a database, replacing accounts that already exist at the same address.
+    /// # Arguments
+    /// * `db` - The database of the simulation.
+    pub(crate) fn into_db(self, db: &mut CacheDB<ForkDB>) {
+        for (address, account_dump) in self.accounts {
+            let address = B160::from(address.0);
+            let code_hash = if account_dump.code.is_empty() {
+                KECCAK_EMPTY
+            } else {
+                keccak256(&account_dump.code)
+            };
+            db.insert_account_info(
+                address,
+                AccountInfo {
+                    balance: recast_ethers_u256(account_dump.balance),
+                    nonce: account_dump.nonce,
+                    code_hash,
+                    code: Some(Bytecode::new_raw(account_dump.code.0)),
+                },
+            );
+            let account = db.accounts.get_mut(&address).unwrap(); // The account was inserted above.
+            account.storage = account_dump
+                .storage
+                .into_iter()
+                .map(|(slot, value)| (recast_ethers_u256(slot), recast_ethers_u256(value)))
+                .collect();
+        }
+    }
+
+    /// Serializes the state.
+    /// # Arguments
+    /// * `format` - The serialization format.
+    /// # Returns
+    /// * `Result<Vec<u8>, String>` - The serialized state or the reason it could not be serialized.
+    pub fn encode(&self, format: StateFormat) -> Result<Vec<u8>, String> {
+        match format {
+            StateFormat::Json => serde_json::to_vec_pretty(self).map_err(|err| err.to_string()),
+            StateFormat::Bincode => bincode::serialize(self).map_err(|err| err.to_string()),
+            StateFormat::MessagePack => rmp_serde::to_vec(self).map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Deserializes a state.
+    /// # Arguments
+    /// * `bytes` - The serialized state.
+    /// * `format` - The serialization format the state was serialized with.
+    /// # Returns
+    /// * `Result<StateDump, String>` - The state or the reason it could not be deserialized.
+    pub fn decode(bytes: &[u8], format: StateFormat) -> Result<Self, String> {
+        match format {
+            StateFormat::Json => serde_json::from_slice(bytes).map_err(|err| err.to_string()),
+            StateFormat::Bincode => bincode::deserialize(bytes).map_err(|err| err.to_string()),
+            StateFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Recast a revm `U256` into an ethers `U256`.
+pub(crate) fn recast_u256(value: U256) -> EthersU256 {
+    EthersU256::from_big_endian(&value.to_be_bytes::<32>())
+}
+
+/// Recast an ethers `U256` into a revm `U256`.
+pub(crate) fn recast_ethers_u256(value: EthersU256) -> U256 {
+    let mut bytes = [0_u8; 32];
+    value.to_big_endian(&mut bytes);
+    U256::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bindings::writer;
+    use revm::primitives::{SpecId, B160, U256};
+
+    use super::StateFormat;
+    use crate::{
+        agent::{user::User, Agent, AgentType, TransactSettings},
+        contract::SimulationContract,
+        manager::SimulationManager,
+    };
+
+    #[test]
+    fn state_round_trips_in_every_format() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let writer = writer.deploy(
+            &mut manager.environment,
+            manager.agents.get("admin").unwrap(),
+            (),
+        );
+        let execution_result =
+            manager.call_with_result("admin", &writer, "echoString", "exported".to_string())?;
+        manager.unpack_execution(execution_result)?;
+        let expected = manager.export_state(StateFormat::Json)?;
+
+        for format in [StateFormat::Bincode, StateFormat::MessagePack] {
+            let exported = manager.export_state(format)?;
+            let mut imported = SimulationManager::default();
+            imported.import_state(&exported, format)?;
+            assert_eq!(imported.export_state(StateFormat::Json)?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn simulation_save_round_trips() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::with_chain_id(31_337);
+        let alice = User::new("alice", None)
+            .with_gas_limit(1_000_000)
+            .with_gas_price(U256::from(10))
+            .with_priority_fee(U256::from(2));
+        manager.activate_agent(AgentType::User(alice), B160::from_low_u64_be(2))?;
+        manager.fund_agent("alice", U256::from(1_000_000_u64))?;
+        manager.set_transact_settings(
+            "admin",
+            TransactSettings {
+                gas_limit: u64::MAX,
+                gas_price: U256::from(9),
+                gas_priority_fee: None,
+            },
+        )?;
+        manager.advance_block(3);
+        manager.set_base_fee(U256::from(7));
+        manager.environment.evm.env.block.coinbase = B160::from_low_u64_be(0xc0);
+        manager.environment.evm.env.cfg.spec_id = SpecId::SHANGHAI;
+
+        let path = std::env::temp_dir().join("arbiter_test_simulation_save.json");
+        manager.save(&path)?;
+        let loaded = SimulationManager::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(
+            loaded.export_state(StateFormat::Json)?,
+            manager.export_state(StateFormat::Json)?
+        );
+        assert_eq!(loaded.block_number(), 3);
+        assert_eq!(
+            loaded.environment.evm.env.block,
+            manager.environment.evm.env.block
+        );
+        assert_eq!(loaded.environment.evm.env.block.basefee, U256::from(7));
+        assert_eq!(loaded.chain_id(), 31_337);
+        assert_eq!(loaded.environment.evm.env.cfg.spec_id, SpecId::SHANGHAI);
+        let alice = loaded.agents.get("alice").unwrap();
+        assert_eq!(alice.inner().address(), B160::from_low_u64_be(2));
+        let transact_settings = alice.inner().transact_settings();
+        assert_eq!(transact_settings.gas_limit, 1_000_000);
+        assert_eq!(transact_settings.gas_price, U256::from(10));
+        assert_eq!(transact_settings.gas_priority_fee, Some(U256::from(2)));
+        let admin = loaded.agents.get("admin").unwrap();
+        assert_eq!(admin.inner().transact_settings().gas_price, U256::from(9));
+        Ok(())
+    }
+}
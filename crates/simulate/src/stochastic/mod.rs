@@ -9,6 +9,7 @@ use rand_distr::{
     Poisson as PoissonDistr,
 };
 
+pub mod noise;
 pub mod price_process;
 
 /// A trait for distribution sampling.
@@ -0,0 +1,54 @@
+//! Module for noise models that make agent decisions imperfect.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::stochastic::*;
+
+/// Noise applied to the decisions of an agent so that it does not behave with knife-edge precision.
+/// The noise is drawn from a seeded random number generator, so the same seed always produces the same decisions.
+/// # Fields
+/// * `price_std_dev` - Standard deviation of the relative Gaussian noise added to every price the agent perceives. (f64)
+/// * `skip_probability` - Probability that the agent skips an action it would otherwise take. (f64)
+/// * `seed` - Seed of the random number generator. (u64)
+#[derive(Debug, Clone)]
+pub struct NoiseModel {
+    /// Standard deviation of the relative Gaussian noise added to every price the agent perceives.
+    pub price_std_dev: f64,
+    /// Probability that the agent skips an action it would otherwise take.
+    pub skip_probability: f64,
+    /// Seed of the random number generator.
+    pub seed: u64,
+    /// Random number generator that draws the noise.
+    rng: StdRng,
+}
+
+impl NoiseModel {
+    /// Public builder function that instantiates a [`NoiseModel`].
+    /// # Arguments
+    /// * `price_std_dev` - Standard deviation of the relative noise on perceived prices, e.g., `0.01` for 1%. (f64)
+    /// * `skip_probability` - Probability of skipping an action, between 0 and 1. (f64)
+    /// * `seed` - Seed of the random number generator. (u64)
+    pub fn new(price_std_dev: f64, skip_probability: f64, seed: u64) -> Self {
+        NoiseModel {
+            price_std_dev,
+            skip_probability: skip_probability.clamp(0.0, 1.0),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the price as the agent perceives it, i.e., with relative Gaussian noise added.
+    /// # Arguments
+    /// * `price` - The true price. (f64)
+    /// # Returns
+    /// * `f64` - The perceived price.
+    pub fn perceive_price(&mut self, price: f64) -> f64 {
+        let normal = Normal::new(0.0, 1.0);
+        price * (1.0 + self.price_std_dev * normal.sample(&mut self.rng))
+    }
+
+    /// Draws whether the agent skips its next action.
+    pub fn skips_action(&mut self) -> bool {
+        self.rng.gen_bool(self.skip_probability)
+    }
+}
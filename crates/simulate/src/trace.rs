@@ -0,0 +1,219 @@
+#![warn(missing_docs)]
+//! Replayable traces of every transaction executed in a simulation, e.g., to share a reproduction in a bug report.
+
+use ethers::types::{Address, Bytes as EthersBytes, H256, U256 as EthersU256};
+use revm::primitives::{ExecutionResult, Log, TransactTo, TxEnv, B160, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::recast_address;
+
+/// The ordered transactions of a simulation and their receipts. Serializes to JSON.
+/// # Fields
+/// * `transactions` - The executed transactions in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceFile {
+    /// The executed transactions in order.
+    pub transactions: Vec<TraceTransaction>,
+}
+
+/// A transaction in a [`TraceFile`].
+/// # Fields
+/// * `sender` - The sender of the transaction.
+/// * `to` - The receiver of the transaction, `None` for contract deployments.
+/// * `data` - The calldata or deployment bytecode of the transaction.
+/// * `value` - The value sent with the transaction.
+/// * `gas_limit` - The gas limit of the transaction.
+/// * `gas_price` - The gas price of the transaction.
+/// * `receipt` - The outcome of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceTransaction {
+    /// The sender of the transaction.
+    pub sender: Address,
+    /// The receiver of the transaction, `None` for contract deployments.
+    pub to: Option<Address>,
+    /// The calldata or deployment bytecode of the transaction.
+    pub data: EthersBytes,
+    /// The value sent with the transaction.
+    pub value: EthersU256,
+    /// The gas limit of the transaction.
+    pub gas_limit: u64,
+    /// The gas price of the transaction.
+    pub gas_price: EthersU256,
+    /// The outcome of the transaction.
+    pub receipt: TraceReceipt,
+}
+
+/// The outcome of a [`TraceTransaction`].
+/// # Fields
+/// * `success` - Whether the transaction succeeded.
+/// * `gas_used` - The gas used by the transaction.
+/// * `logs` - The logs emitted by the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceReceipt {
+    /// Whether the transaction succeeded.
+    pub success: bool,
+    /// The gas used by the transaction.
+    pub gas_used: u64,
+    /// The logs emitted by the transaction.
+    pub logs: Vec<TraceLog>,
+}
+
+/// A log in a [`TraceReceipt`].
+/// # Fields
+/// * `address` - The contract that emitted the log.
+/// * `topics` - The topics of the log.
+/// * `data` - The data of the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceLog {
+    /// The contract that emitted the log.
+    pub address: Address,
+    /// The topics of the log.
+    pub topics: Vec<H256>,
+    /// The data of the log.
+    pub data: EthersBytes,
+}
+
+impl TraceTransaction {
+    /// Records an executed transaction.
+    /// # Arguments
+    /// * `tx` - The transaction environment that the transaction was executed with.
+    /// * `execution_result` - The execution result of the transaction.
+    pub(crate) fn new(tx: &TxEnv, execution_result: &ExecutionResult) -> Self {
+        Self {
+            sender: recast_address(tx.caller),
+            to: match tx.transact_to {
+                TransactTo::Call(to) => Some(recast_address(to)),
+                TransactTo::Create(_) => None,
+            },
+            data: EthersBytes(tx.data.clone()),
+            value: recast_u256(tx.value),
+            gas_limit: tx.gas_limit,
+            gas_price: recast_u256(tx.gas_price),
+            receipt: TraceReceipt::from(execution_result),
+        }
+    }
+
+    /// Rebuilds the transaction environment to execute the transaction again.
+    pub fn tx_env(&self) -> TxEnv {
+        TxEnv {
+            caller: B160::from(self.sender.0),
+            gas_limit: self.gas_limit,
+            gas_price: recast_ethers_u256(self.gas_price),
+            gas_priority_fee: None,
+            transact_to: match self.to {
+                Some(to) => TransactTo::Call(B160::from(to.0)),
+                None => TransactTo::create(),
+            },
+            value: recast_ethers_u256(self.value),
+            data: self.data.0.clone(),
+            chain_id: None,
+            nonce: None,
+            access_list: Vec::new(),
+        }
+    }
+}
+
+impl From<&ExecutionResult> for TraceReceipt {
+    fn from(execution_result: &ExecutionResult) -> Self {
+        Self {
+            success: execution_result.is_success(),
+            gas_used: execution_result.gas_used(),
+            logs: execution_result
+                .logs()
+                .into_iter()
+                .map(TraceLog::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<Log> for TraceLog {
+    fn from(log: Log) -> Self {
+        Self {
+            address: recast_address(log.address),
+            topics: log.topics.iter().map(|topic| H256::from(topic.0)).collect(),
+            data: EthersBytes(log.data),
+        }
+    }
+}
+
+/// Recast a revm `U256` into an ethers `U256`.
+fn recast_u256(value: U256) -> EthersU256 {
+    EthersU256::from_big_endian(&value.to_be_bytes::<32>())
+}
+
+/// Recast an ethers `U256` into a revm `U256`.
+fn recast_ethers_u256(value: EthersU256) -> U256 {
+    let mut bytes = [0_u8; 32];
+    value.to_big_endian(&mut bytes);
+    U256::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error;
+
+    use bindings::writer;
+    use bytes::Bytes;
+    use revm::primitives::{B160, U256};
+
+    use super::TraceFile;
+    use crate::{
+        contract::SimulationContract,
+        manager::SimulationManager,
+        state::{StateDump, StateFormat},
+    };
+
+    #[test]
+    fn replayed_trace_reproduces_state() -> Result<(), Box<dyn Error>> {
+        let mut manager = SimulationManager::default();
+        // The admin is funded before the trace starts, so only the base state knows about it.
+        manager.set_balance(B160::from_low_u64_be(1), U256::from(1_000));
+        let base_state = manager.export_state(StateFormat::Json)?;
+
+        let writer =
+            SimulationContract::new(writer::WRITER_ABI.clone(), writer::WRITER_BYTECODE.clone());
+        let writer = writer.deploy(
+            &mut manager.environment,
+            manager.agents.get("admin").unwrap(),
+            (),
+        );
+        let execution_result =
+            manager.call_with_result("admin", &writer, "echoString", "replay me".to_string())?;
+        manager.unpack_execution(execution_result)?;
+        manager.call_with_value(
+            "admin",
+            B160::from_low_u64_be(2),
+            Bytes::new(),
+            U256::from(400),
+        )?;
+
+        // Round trip the trace through JSON as it would be shared.
+        let trace = manager.export_trace();
+        assert_eq!(trace.transactions.len(), 3);
+        let trace: TraceFile = serde_json::from_str(&serde_json::to_string(&trace)?)?;
+
+        // Without the base state the unfunded admin cannot make the transfer.
+        let mut replay = SimulationManager::default();
+        assert!(replay
+            .import_and_replay(&trace, StateDump::default())
+            .is_err());
+
+        let mut replay = SimulationManager::default();
+        replay.import_and_replay(&trace, StateDump::decode(&base_state, StateFormat::Json)?)?;
+
+        let original = &manager.environment.evm.db().unwrap().accounts[&writer.address];
+        let replayed = &replay.environment.evm.db().unwrap().accounts[&writer.address];
+        assert_eq!(original.info.code_hash, replayed.info.code_hash);
+        assert_eq!(original.storage, replayed.storage);
+        let balance = |manager: &mut SimulationManager, address: u64| {
+            manager.environment.evm.db().unwrap().accounts[&B160::from_low_u64_be(address)]
+                .info
+                .balance
+        };
+        assert_eq!(balance(&mut replay, 1), U256::from(600));
+        assert_eq!(balance(&mut replay, 2), U256::from(400));
+        assert_eq!(replay.export_trace(), trace);
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 #![warn(missing_docs)]
 //! Module for utility functionality.
-use ethers::prelude::{Address, U256};
-use revm::primitives::B160;
+use ethers::{
+    abi::{encode, Token},
+    prelude::{Address, H256, U256},
+    utils::keccak256,
+};
+use revm::primitives::{ExecutionResult, B160};
 
 /// Recast a B160 into an Address type
 /// # Arguments
@@ -13,6 +17,35 @@ pub fn recast_address(address: B160) -> Address {
     Address::from(temp)
 }
 
+/// Computes the address that a `CREATE2` deployment lands at, as specified in EIP-1014.
+/// # Arguments
+/// * `deployer` - Account executing the `CREATE2`, i.e., the sender of a deployment transaction or a factory contract. (Address)
+/// * `salt` - Salt of the deployment. (H256)
+/// * `init_code_hash` - Keccak-256 hash of the init code, i.e., the bytecode followed by the encoded constructor arguments. (H256)
+/// # Returns
+/// * `Address` - Address of the deployed contract.
+pub fn compute_create2_address(deployer: Address, salt: H256, init_code_hash: H256) -> Address {
+    let mut preimage = Vec::with_capacity(1 + 20 + 32 + 32);
+    preimage.push(0xff);
+    preimage.extend_from_slice(deployer.as_bytes());
+    preimage.extend_from_slice(salt.as_bytes());
+    preimage.extend_from_slice(init_code_hash.as_bytes());
+    Address::from_slice(&keccak256(preimage)[12..])
+}
+
+/// Returns the gas refunded to the sender of a transaction, e.g., for clearing storage.
+/// The refund is already deducted from the `gas_used` of the execution result and is capped by the spec the EVM runs (half of the gas used before London, a fifth after).
+/// # Arguments
+/// * `execution_result` - Result of the transaction. (ExecutionResult)
+/// # Returns
+/// * `u64` - Gas refunded. Zero for reverted or halted transactions.
+pub fn gas_refunded(execution_result: &ExecutionResult) -> u64 {
+    match execution_result {
+        ExecutionResult::Success { gas_refunded, .. } => *gas_refunded,
+        ExecutionResult::Revert { .. } | ExecutionResult::Halt { .. } => 0,
+    }
+}
+
 /// Converts a float to a WAD fixed point prepared U256 number.
 /// # Arguments
 /// * `x` - Float to convert. (f64)
@@ -21,3 +54,38 @@ pub fn recast_address(address: B160) -> Address {
 pub fn float_to_wad(x: f64) -> U256 {
     U256::from((x * 1e18) as u128)
 }
+
+/// Computes the EIP-712 digest that an owner signs to produce an EIP-2612 `permit` signature.
+/// # Arguments
+/// * `domain_separator` - The `DOMAIN_SEPARATOR` of the token. (H256)
+/// * `owner` - Owner of the tokens. (Address)
+/// * `spender` - Address that is allowed to spend the tokens. (Address)
+/// * `value` - Amount of tokens the spender is allowed to spend. (U256)
+/// * `nonce` - Current permit nonce of the owner on the token. (U256)
+/// * `deadline` - Timestamp after which the signature is no longer valid. (U256)
+/// # Returns
+/// * `H256` - Digest to be signed by the owner.
+pub fn permit_digest(
+    domain_separator: H256,
+    owner: Address,
+    spender: Address,
+    value: U256,
+    nonce: U256,
+    deadline: U256,
+) -> H256 {
+    let permit_typehash = keccak256(
+        "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)",
+    );
+    let struct_hash = keccak256(encode(&[
+        Token::FixedBytes(permit_typehash.to_vec()),
+        Token::Address(owner),
+        Token::Address(spender),
+        Token::Uint(value),
+        Token::Uint(nonce),
+        Token::Uint(deadline),
+    ]));
+    let mut digest_input = vec![0x19, 0x01];
+    digest_input.extend_from_slice(domain_separator.as_bytes());
+    digest_input.extend_from_slice(&struct_hash);
+    H256::from(keccak256(digest_input))
+}